@@ -0,0 +1,27 @@
+-- Migration: Public identity keys for sharing memories between users
+-- Each user publishes one X25519 public key; the private key never leaves
+-- their vault daemon. Senders seal a memory's DEK to this key.
+-- Last Updated: 2026-10-18
+
+CREATE TABLE IF NOT EXISTS identity_keys (
+  user_id UUID PRIMARY KEY REFERENCES auth.users(id) ON DELETE CASCADE,
+  public_key BYTEA NOT NULL CHECK (octet_length(public_key) = 32),
+  key_id TEXT NOT NULL, -- fingerprint shown to users for verification
+  created_at BIGINT NOT NULL, -- unix seconds
+  updated_at BIGINT NOT NULL
+);
+
+ALTER TABLE identity_keys ENABLE ROW LEVEL SECURITY;
+
+-- Public keys are public: any signed-in user can look one up
+CREATE POLICY "Authenticated users can view identity keys"
+  ON identity_keys FOR SELECT
+  USING (auth.role() = 'authenticated');
+
+CREATE POLICY "Users can publish their own identity key"
+  ON identity_keys FOR INSERT
+  WITH CHECK (auth.uid() = user_id);
+
+CREATE POLICY "Users can replace their own identity key"
+  ON identity_keys FOR UPDATE
+  USING (auth.uid() = user_id);
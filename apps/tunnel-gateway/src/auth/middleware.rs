@@ -1,4 +1,22 @@
-// This file is currently unused as authentication is handled directly in services.
-// Kept for potential future use or reference.
+use crate::auth::supabase_client::SupabaseClient;
+use tonic::{Request, Status};
 
+// Authentication is checked per call by the services rather than as a tower layer;
+// `AuthMiddleware` is kept for a future interceptor.
 pub struct AuthMiddleware;
+
+/// Verify the request's `authorization: Bearer <token>` and return the Supabase user id
+pub async fn authenticate<T>(supabase: &SupabaseClient, req: &Request<T>) -> Result<String, Status> {
+    let token = req.metadata().get("authorization")
+        .ok_or_else(|| Status::unauthenticated("Missing authorization token"))?
+        .to_str()
+        .map_err(|_| Status::unauthenticated("Invalid token format"))?;
+
+    let token = token.strip_prefix("Bearer ")
+        .ok_or_else(|| Status::unauthenticated("Invalid token format"))?;
+
+    let user = supabase.verify_token(token).await
+        .map_err(|_| Status::unauthenticated("Invalid or expired token"))?;
+
+    Ok(user.sub)
+}
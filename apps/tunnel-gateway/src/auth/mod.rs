@@ -2,5 +2,6 @@ pub mod service;
 pub mod middleware;
 pub mod supabase_client;
 
+pub use middleware::authenticate;
 pub use service::AuthServiceImpl;
 pub use supabase_client::SupabaseClient;
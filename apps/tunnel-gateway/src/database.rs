@@ -164,15 +164,21 @@ impl MemoryDatabase {
         content: &str,
         embedding: &[f32],
         tags: &[String],
+        metadata: Option<&HashMap<String, String>>,
         updated_at: i64,
     ) -> Result<bool, sqlx::Error> {
         let uuid = Uuid::parse_str(id).unwrap_or_default();
         let uid = Uuid::parse_str(user_id).unwrap_or_default();
+        let metadata_json = metadata
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
         
+        // Metadata is only replaced when provided (e.g. key rotation records the new key id)
         let result = sqlx::query(
             r#"
             UPDATE memories 
-            SET content = $1, embedding = $2, tags = $3, updated_at = $4 
+            SET content = $1, embedding = $2, tags = $3, updated_at = $4, metadata = COALESCE($7, metadata)
             WHERE id = $5 AND user_id = $6
             "#
         )
@@ -182,12 +188,73 @@ impl MemoryDatabase {
         .bind(updated_at)
         .bind(uuid)
         .bind(uid)
+        .bind(metadata_json)
         .execute(&self.pool)
         .await?;
         
         Ok(result.rows_affected() > 0)
     }
 
+    // Keyset pagination over all of a user's memories, ordered by id
+    pub async fn list_memories(
+        &self,
+        user_id: &str,
+        after_id: Option<&str>,
+        limit: i32,
+    ) -> Result<Vec<MemoryModel>, sqlx::Error> {
+        let uid = Uuid::parse_str(user_id).unwrap_or_default();
+        let after = after_id.map(|id| Uuid::parse_str(id).unwrap_or_default());
+        let limit = if limit <= 0 { 50 } else { limit };
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, content, metadata, tags, created_at, updated_at
+            FROM memories
+            WHERE user_id = $1 AND ($2::uuid IS NULL OR id > $2)
+            ORDER BY id
+            LIMIT $3
+            "#
+        )
+        .bind(uid)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.map_rows(rows)
+    }
+
+    pub async fn upsert_public_key(&self, user_id: &str, public_key: &[u8], key_id: &str) -> Result<(), sqlx::Error> {
+        let uid = Uuid::parse_str(user_id).unwrap_or_default();
+        let now = chrono::Utc::now().timestamp();
+        sqlx::query(
+            r#"
+            INSERT INTO identity_keys (user_id, public_key, key_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $4)
+            ON CONFLICT (user_id) DO UPDATE
+            SET public_key = EXCLUDED.public_key, key_id = EXCLUDED.key_id, updated_at = EXCLUDED.updated_at
+            "#
+        )
+        .bind(uid)
+        .bind(public_key)
+        .bind(key_id)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns (public_key, key_id, updated_at)
+    pub async fn get_public_key(&self, user_id: &str) -> Result<Option<(Vec<u8>, String, i64)>, sqlx::Error> {
+        let uid = Uuid::parse_str(user_id).unwrap_or_default();
+        let row = sqlx::query("SELECT public_key, key_id, updated_at FROM identity_keys WHERE user_id = $1")
+            .bind(uid)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| (row.get("public_key"), row.get("key_id"), row.get("updated_at"))))
+    }
+
     // Helper to map SQL rows to Rust structs
     fn map_rows(&self, rows: Vec<sqlx::postgres::PgRow>) -> Result<Vec<MemoryModel>, sqlx::Error> {
         let results = rows.into_iter().map(|row| {
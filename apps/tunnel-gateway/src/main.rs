@@ -5,12 +5,12 @@ use std::env;
 
 mod database;
 mod services;
-mod ipc_client;
 mod auth;
 
 use database::MemoryDatabase;
 use services::memory::MemoryServiceImpl;
 use services::vault::VaultServiceImpl;
+use services::identity::IdentityServiceImpl;
 use auth::{SupabaseClient, AuthServiceImpl};
 use identra_proto::auth::auth_service_server::AuthServiceServer;
 
@@ -31,7 +31,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize services
     let memory_service = MemoryServiceImpl::new(db.clone(), supabase.clone());
     let auth_service = AuthServiceImpl::new(supabase.clone());
-    let vault_service = VaultServiceImpl::new();
+    let vault_service = VaultServiceImpl::new(supabase.clone());
+    let identity_service = IdentityServiceImpl::new(db.clone(), supabase.clone());
 
     // --- Admin Seeding ---
     if let (Ok(email), Ok(password)) = (env::var("ADMIN_USERNAME"), env::var("ADMIN_PASSWORD")) {
@@ -50,6 +51,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .add_service(memory_service.into_server())
         .add_service(AuthServiceServer::new(auth_service))
         .add_service(vault_service.into_server())
+        .add_service(identity_service.into_server())
         .serve(addr)
         .await?;
 
@@ -0,0 +1,66 @@
+use identra_proto::identity::{
+    identity_service_server::{IdentityService, IdentityServiceServer},
+    PublishPublicKeyRequest, PublishPublicKeyResponse,
+    GetPublicKeyRequest, GetPublicKeyResponse,
+};
+use crate::auth::authenticate;
+use crate::auth::supabase_client::SupabaseClient;
+use crate::database::MemoryDatabase;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+/// X25519 public keys are always 32 bytes
+const PUBLIC_KEY_LEN: usize = 32;
+
+/// Public key directory; the gateway only ever sees public halves
+pub struct IdentityServiceImpl {
+    db: Arc<MemoryDatabase>,
+    supabase: Arc<SupabaseClient>,
+}
+
+impl IdentityServiceImpl {
+    pub fn new(db: Arc<MemoryDatabase>, supabase: Arc<SupabaseClient>) -> Self {
+        Self { db, supabase }
+    }
+
+    pub fn into_server(self) -> IdentityServiceServer<Self> {
+        IdentityServiceServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl IdentityService for IdentityServiceImpl {
+    async fn publish_public_key(&self, req: Request<PublishPublicKeyRequest>) -> Result<Response<PublishPublicKeyResponse>, Status> {
+        let user_id = authenticate(&self.supabase, &req).await?;
+        let r = req.into_inner();
+        if r.public_key.len() != PUBLIC_KEY_LEN {
+            return Err(Status::invalid_argument("public_key must be 32 bytes"));
+        }
+        if r.public_key.iter().all(|&b| b == 0) {
+            return Err(Status::invalid_argument("public_key is not a valid X25519 key"));
+        }
+
+        self.db.upsert_public_key(&user_id, &r.public_key, &r.key_id)
+            .await
+            .map_err(|e| Status::internal(format!("DB Error: {}", e)))?;
+
+        tracing::info!("Published identity key {} for user {}", r.key_id, user_id);
+        Ok(Response::new(PublishPublicKeyResponse { success: true, message: "Identity key published".into() }))
+    }
+
+    async fn get_public_key(&self, req: Request<GetPublicKeyRequest>) -> Result<Response<GetPublicKeyResponse>, Status> {
+        authenticate(&self.supabase, &req).await?;
+        let r = req.into_inner();
+        let user_id = Uuid::parse_str(&r.user_id)
+            .map_err(|_| Status::invalid_argument("user_id must be a UUID"))?
+            .to_string();
+
+        let (public_key, key_id, updated_at) = self.db.get_public_key(&user_id)
+            .await
+            .map_err(|e| Status::internal(format!("DB Error: {}", e)))?
+            .ok_or_else(|| Status::not_found("User has not published an identity key"))?;
+
+        Ok(Response::new(GetPublicKeyResponse { user_id, public_key, key_id, updated_at }))
+    }
+}
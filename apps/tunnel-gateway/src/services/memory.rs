@@ -8,7 +8,9 @@ use identra_proto::memory::{
     SearchMemoriesRequest, SearchMemoriesResponse,
     GetRecentMemoriesRequest, GetRecentMemoriesResponse,
     UpdateMemoryRequest, UpdateMemoryResponse,
+    ListMemoriesRequest, ListMemoriesResponse,
 };
+use crate::auth::authenticate;
 use crate::database::MemoryDatabase;
 use std::sync::{Arc, Mutex};
 use tonic::{Request, Response, Status};
@@ -69,34 +71,23 @@ impl MemoryServiceImpl {
         embeddings.into_iter().next()
             .ok_or_else(|| Status::internal("No embedding generated"))
     }
-
-    async fn check_auth<T>(&self, req: &Request<T>) -> Result<String, Status> {
-        let token = req.metadata().get("authorization")
-            .ok_or_else(|| Status::unauthenticated("Missing authorization token"))?
-            .to_str()
-            .map_err(|_| Status::unauthenticated("Invalid token format"))?;
-
-        let token = if token.starts_with("Bearer ") {
-            &token[7..]
-        } else {
-            return Err(Status::unauthenticated("Invalid token format"));
-        };
-
-        let user = self.supabase.verify_token(token).await
-            .map_err(|_| Status::unauthenticated("Invalid or expired token"))?;
-            
-        Ok(user.sub)
-    }
 }
 
 #[tonic::async_trait]
 impl MemoryService for MemoryServiceImpl {
     async fn store_memory(&self, req: Request<StoreMemoryRequest>) -> Result<Response<StoreMemoryResponse>, Status> {
-        let user_id = self.check_auth(&req).await?;
+        let user_id = authenticate(&self.supabase, &req).await?;
         let r = req.into_inner();
         if r.content.trim().is_empty() { return Err(Status::invalid_argument("Content required")); }
         
-        let id = Uuid::new_v4().to_string();
+        // Clients that bind ciphertext to the row pick the id up front
+        let id = if r.memory_id.is_empty() {
+            Uuid::new_v4().to_string()
+        } else {
+            Uuid::parse_str(&r.memory_id)
+                .map_err(|_| Status::invalid_argument("memory_id must be a UUID"))?
+                .to_string()
+        };
         let now = chrono::Utc::now().timestamp();
         
         let embedding = self.generate_embedding(&r.content)?;
@@ -110,7 +101,7 @@ impl MemoryService for MemoryServiceImpl {
     }
     
     async fn search_memories(&self, req: Request<SearchMemoriesRequest>) -> Result<Response<SearchMemoriesResponse>, Status> {
-        let user_id = self.check_auth(&req).await?;
+        let user_id = authenticate(&self.supabase, &req).await?;
         let r = req.into_inner();
         
         let matches = self.db.search_memories(&user_id, &r.query_embedding, r.limit, r.similarity_threshold)
@@ -134,7 +125,7 @@ impl MemoryService for MemoryServiceImpl {
     }
 
     async fn query_memories(&self, req: Request<QueryMemoriesRequest>) -> Result<Response<QueryMemoriesResponse>, Status> {
-        let user_id = self.check_auth(&req).await?;
+        let user_id = authenticate(&self.supabase, &req).await?;
         let r = req.into_inner();
         let limit = if r.limit > 0 { r.limit } else { 50 };
         
@@ -153,7 +144,7 @@ impl MemoryService for MemoryServiceImpl {
     }
     
     async fn get_memory(&self, req: Request<GetMemoryRequest>) -> Result<Response<GetMemoryResponse>, Status> {
-        let user_id = self.check_auth(&req).await?;
+        let user_id = authenticate(&self.supabase, &req).await?;
         let r = req.into_inner();
         let result = self.db.get_memory(&user_id, &r.memory_id)
             .await
@@ -171,7 +162,7 @@ impl MemoryService for MemoryServiceImpl {
     }
 
     async fn delete_memory(&self, req: Request<DeleteMemoryRequest>) -> Result<Response<DeleteMemoryResponse>, Status> {
-        let user_id = self.check_auth(&req).await?;
+        let user_id = authenticate(&self.supabase, &req).await?;
         let r = req.into_inner();
         let success = self.db.delete_memory(&user_id, &r.memory_id)
             .await
@@ -181,7 +172,7 @@ impl MemoryService for MemoryServiceImpl {
     }
 
     async fn get_recent_memories(&self, req: Request<GetRecentMemoriesRequest>) -> Result<Response<GetRecentMemoriesResponse>, Status> {
-        let user_id = self.check_auth(&req).await?;
+        let user_id = authenticate(&self.supabase, &req).await?;
         let r = req.into_inner();
         
         let results = self.db.get_recent_memories(&user_id, r.limit)
@@ -201,7 +192,7 @@ impl MemoryService for MemoryServiceImpl {
         Ok(Response::new(GetRecentMemoriesResponse { memories }))
     }
     async fn update_memory(&self, req: Request<UpdateMemoryRequest>) -> Result<Response<UpdateMemoryResponse>, Status> {
-        let user_id = self.check_auth(&req).await?;
+        let user_id = authenticate(&self.supabase, &req).await?;
         let r = req.into_inner();
         
         if r.content.trim().is_empty() { return Err(Status::invalid_argument("Content required")); }
@@ -209,7 +200,8 @@ impl MemoryService for MemoryServiceImpl {
         let now = chrono::Utc::now().timestamp();
         let embedding = self.generate_embedding(&r.content)?;
         
-        let success = self.db.update_memory(&user_id, &r.memory_id, &r.content, &embedding, &r.tags, now)
+        let metadata = (!r.metadata.is_empty()).then_some(&r.metadata);
+        let success = self.db.update_memory(&user_id, &r.memory_id, &r.content, &embedding, &r.tags, metadata, now)
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
             
@@ -218,4 +210,34 @@ impl MemoryService for MemoryServiceImpl {
             message: if success { "Updated".into() } else { "Not found".into() } 
         }))
     }
+
+    async fn list_memories(&self, req: Request<ListMemoriesRequest>) -> Result<Response<ListMemoriesResponse>, Status> {
+        let user_id = authenticate(&self.supabase, &req).await?;
+        let r = req.into_inner();
+        let page_size = if r.page_size > 0 { r.page_size.min(500) } else { 100 };
+        let after = (!r.page_token.is_empty()).then_some(r.page_token.as_str());
+
+        let results = self.db.list_memories(&user_id, after, page_size)
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
+        // A full page means there may be more; the last id is the cursor
+        let next_page_token = if results.len() == page_size as usize {
+            results.last().map(|m| m.id.clone()).unwrap_or_default()
+        } else {
+            String::new()
+        };
+
+        let memories: Vec<Memory> = results.into_iter().map(|m| Memory {
+            id: m.id,
+            content: m.content,
+            metadata: m.metadata,
+            embedding: vec![],
+            created_at: Some(prost_types::Timestamp { seconds: m.created_at, nanos: 0 }),
+            updated_at: Some(prost_types::Timestamp { seconds: m.updated_at, nanos: 0 }),
+            tags: m.tags,
+        }).collect();
+
+        Ok(Response::new(ListMemoriesResponse { memories, next_page_token }))
+    }
 }
\ No newline at end of file
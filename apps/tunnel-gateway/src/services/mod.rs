@@ -1,6 +1,7 @@
 pub mod health;
 pub mod vault;
 pub mod memory;
+pub mod identity;
 
 // pub use health::HealthService;
 // pub use vault::VaultServiceImpl;
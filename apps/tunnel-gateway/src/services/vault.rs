@@ -6,14 +6,60 @@ use identra_proto::vault::{
     ListKeysRequest, ListKeysResponse,
     KeyExistsRequest, KeyExistsResponse,
 };
-use crate::ipc_client::VaultClient;
+use crate::auth::authenticate;
+use crate::auth::supabase_client::SupabaseClient;
+use identra_ipc::{ErrorCode, IpcError, VaultClient};
+use std::sync::Arc;
 use tonic::{Request, Response, Status};
 
-pub struct VaultServiceImpl;
+/// Client name sent in the vault handshake
+const IPC_CLIENT_NAME: &str = "tunnel-gateway";
+
+async fn connect_vault() -> Result<VaultClient, Status> {
+    VaultClient::connect(IPC_CLIENT_NAME)
+        .await
+        .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))
+}
+
+/// Namespace holding one user's keys in the shared daemon
+fn user_prefix(user_id: &str) -> String {
+    format!("user/{}/", user_id)
+}
+
+/// Daemon key id for a caller's key id
+fn scoped_key_id(user_id: &str, key_id: &str) -> Result<String, Status> {
+    if key_id.is_empty() {
+        return Err(Status::invalid_argument("key_id must not be empty"));
+    }
+    Ok(format!("{}{}", user_prefix(user_id), key_id))
+}
+
+/// Map a daemon error onto the closest gRPC status
+fn vault_status(e: IpcError) -> Status {
+    match e.code() {
+        Some(ErrorCode::NotFound) => Status::not_found(e.to_string()),
+        Some(ErrorCode::Expired) | Some(ErrorCode::Locked) | Some(ErrorCode::ReauthRequired) => {
+            Status::failed_precondition(e.to_string())
+        }
+        Some(ErrorCode::PermissionDenied) => Status::permission_denied(e.to_string()),
+        Some(ErrorCode::UsesExhausted) => Status::resource_exhausted(e.to_string()),
+        Some(ErrorCode::InvalidRequest) => Status::invalid_argument(e.to_string()),
+        Some(ErrorCode::UnsupportedVersion) => Status::unavailable(e.to_string()),
+        _ => Status::internal(e.to_string()),
+    }
+}
+
+/// Per-user key storage backed by the local vault daemon
+///
+/// Every call is authenticated and keys live under `user/<id>/`, so one
+/// user can never name, list or probe another user's keys.
+pub struct VaultServiceImpl {
+    supabase: Arc<SupabaseClient>,
+}
 
 impl VaultServiceImpl {
-    pub fn new() -> Self {
-        Self
+    pub fn new(supabase: Arc<SupabaseClient>) -> Self {
+        Self { supabase }
     }
     
     pub fn into_server(self) -> VaultServiceServer<Self> {
@@ -27,25 +73,25 @@ impl VaultService for VaultServiceImpl {
         &self,
         request: Request<StoreKeyRequest>,
     ) -> Result<Response<StoreKeyResponse>, Status> {
+        let user_id = authenticate(&self.supabase, &request).await?;
         let req = request.into_inner();
+        let key_id = scoped_key_id(&user_id, &req.key_id)?;
         
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
+        let mut client = connect_vault().await?;
         
         // Convert protobuf expires_at (Timestamp) to Unix timestamp
         let expires_at = req.expires_at.map(|ts| ts.seconds);
         
         client.store_key(
-            req.key_id.clone(), 
-            req.key_data,
+            key_id,
+            &req.key_data,
             req.metadata,
             expires_at,
         )
             .await
-            .map_err(|e| Status::internal(format!("Failed to store key: {}", e)))?;
+            .map_err(vault_status)?;
         
-        tracing::info!("Stored key: {}", req.key_id);
+        tracing::info!("Stored key {} for user {}", req.key_id, user_id);
         
         Ok(Response::new(StoreKeyResponse {
             success: true,
@@ -57,27 +103,27 @@ impl VaultService for VaultServiceImpl {
         &self,
         request: Request<RetrieveKeyRequest>,
     ) -> Result<Response<RetrieveKeyResponse>, Status> {
+        let user_id = authenticate(&self.supabase, &request).await?;
         let req = request.into_inner();
+        let key_id = scoped_key_id(&user_id, &req.key_id)?;
         
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
+        let mut client = connect_vault().await?;
         
-        let (key_data, metadata, created_at, expires_at) = client.retrieve_key(req.key_id.clone())
+        let key = client.retrieve_key(key_id)
             .await
-            .map_err(|e| Status::not_found(format!("Key not found: {}", e)))?;
+            .map_err(vault_status)?;
         
-        tracing::info!("Retrieved key: {}", req.key_id);
+        tracing::info!("Retrieved key {} for user {}", req.key_id, user_id);
         
         // Convert Unix timestamp to protobuf Timestamp
         let created_at_ts = Some(prost_types::Timestamp {
-            seconds: created_at,
+            seconds: key.created_at,
             nanos: 0,
         });
         
         Ok(Response::new(RetrieveKeyResponse {
-            key_data,
-            metadata,
+            key_data: key.key_data.as_bytes().to_vec(),
+            metadata: key.metadata,
             created_at: created_at_ts,
         }))
     }
@@ -86,17 +132,17 @@ impl VaultService for VaultServiceImpl {
         &self,
         request: Request<DeleteKeyRequest>,
     ) -> Result<Response<DeleteKeyResponse>, Status> {
+        let user_id = authenticate(&self.supabase, &request).await?;
         let req = request.into_inner();
+        let key_id = scoped_key_id(&user_id, &req.key_id)?;
         
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
+        let mut client = connect_vault().await?;
         
-        client.delete_key(req.key_id.clone())
+        client.delete_key(key_id)
             .await
-            .map_err(|e| Status::internal(format!("Failed to delete key: {}", e)))?;
+            .map_err(vault_status)?;
         
-        tracing::info!("Deleted key: {}", req.key_id);
+        tracing::info!("Deleted key {} for user {}", req.key_id, user_id);
         
         Ok(Response::new(DeleteKeyResponse {
             success: true,
@@ -106,25 +152,35 @@ impl VaultService for VaultServiceImpl {
     
     async fn list_keys(
         &self,
-        _request: Request<ListKeysRequest>,
+        request: Request<ListKeysRequest>,
     ) -> Result<Response<ListKeysResponse>, Status> {
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
+        let user_id = authenticate(&self.supabase, &request).await?;
+        let req = request.into_inner();
+        let page_size = u32::try_from(req.page_size)
+            .map_err(|_| Status::invalid_argument("page_size must not be negative"))?;
+        let page_token = Some(req.page_token).filter(|token| !token.is_empty());
+        
+        let mut client = connect_vault().await?;
         
-        let key_ids = client.list_keys()
+        let prefix = user_prefix(&user_id);
+        let (key_ids, next_page_token) = client
+            .list_keys_page_with_prefix(Some(prefix.clone()), Some(page_size), page_token)
             .await
             .map_err(|e| {
-                tracing::warn!("list_keys not supported: {}", e);
-                // Windows Credential Manager doesn't support listing
-                Status::unimplemented("list_keys not supported by OS keychain")
+                tracing::warn!("list_keys failed: {}", e);
+                vault_status(e)
             })?;
         
-        tracing::info!("Listed {} keys", key_ids.len());
+        let key_ids: Vec<String> = key_ids
+            .into_iter()
+            .filter_map(|key_id| key_id.strip_prefix(&prefix).map(str::to_string))
+            .collect();
+        
+        tracing::info!("Listed {} keys for user {}", key_ids.len(), user_id);
         
         Ok(Response::new(ListKeysResponse {
             key_ids,
-            next_page_token: String::new(),
+            next_page_token: next_page_token.unwrap_or_default(),
         }))
     }
     
@@ -132,22 +188,16 @@ impl VaultService for VaultServiceImpl {
         &self,
         request: Request<KeyExistsRequest>,
     ) -> Result<Response<KeyExistsResponse>, Status> {
+        let user_id = authenticate(&self.supabase, &request).await?;
         let req = request.into_inner();
+        let key_id = scoped_key_id(&user_id, &req.key_id)?;
         
-        let mut client = VaultClient::connect()
-            .await
-            .map_err(|e| Status::unavailable(format!("Vault daemon not available: {}", e)))?;
+        let mut client = connect_vault().await?;
         
-        let exists = client.key_exists(req.key_id.clone())
+        let exists = client.key_exists(key_id)
             .await
-            .map_err(|e| Status::internal(format!("Failed to check key existence: {}", e)))?;
+            .map_err(vault_status)?;
         
         Ok(Response::new(KeyExistsResponse { exists }))
     }
 }
-
-impl Default for VaultServiceImpl {
-    fn default() -> Self {
-        Self::new()
-    }
-}
@@ -0,0 +1,443 @@
+use crate::error::{Result, VaultError};
+use crate::file_storage::{restrict_dir, write_atomic};
+use crate::keychain::{load_or_create_internal_key, KeyStorage};
+use crate::memory::SecureMemory;
+use crate::peer::PeerInfo;
+use identra_crypto::{mac, MemoryVault};
+use identra_ipc::AuditEntry;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Key id of the HMAC key chaining the audit log, kept in the storage backend
+pub const AUDIT_KEY_ID: &str = "identra-audit-log";
+
+/// Audit log file, in the backend's state directory
+pub const AUDIT_LOG_NAME: &str = "audit.log";
+
+/// Most entries returned by one query
+pub const MAX_AUDIT_ENTRIES: usize = 1000;
+
+/// Domain separation for the head record's MAC
+const HEAD_CONTEXT: &[u8] = b"identra-audit-head-v1";
+
+/// What happened, before it is chained into the log
+pub struct AuditEvent<'a> {
+    pub pid: Option<u32>,
+    pub uid: Option<u32>,
+    pub client: &'a str,
+    pub operation: &'a str,
+    pub key_id: Option<&'a str>,
+    /// `ok` or an error code wire name
+    pub outcome: &'a str,
+}
+
+impl<'a> AuditEvent<'a> {
+    /// Something a connected process asked for
+    pub fn peer(peer: &PeerInfo, client: &'a str, operation: &'a str, key_id: Option<&'a str>, outcome: &'a str) -> Self {
+        Self {
+            pid: peer.pid,
+            uid: peer.uid,
+            client,
+            operation,
+            key_id,
+            outcome,
+        }
+    }
+
+    /// Something the daemon did on its own
+    pub fn daemon(operation: &'a str, outcome: &'a str) -> Self {
+        Self {
+            pid: Some(std::process::id()),
+            uid: None,
+            client: "vault-daemon",
+            operation,
+            key_id: None,
+            outcome,
+        }
+    }
+}
+
+/// Why [`AuditLog::verify`] rejected the log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainBreak {
+    /// Entry `seq` was edited, removed or reordered
+    Modified { seq: u64 },
+    /// The log ends before the last entry the daemon wrote
+    Truncated { expected: u64, found: u64 },
+    /// The log or its head record can't be read or was replaced
+    Unreadable(String),
+}
+
+impl ChainBreak {
+    /// Short outcome recorded when the daemon finds a broken log at startup
+    fn outcome(&self) -> &'static str {
+        match self {
+            Self::Modified { .. } => "modified",
+            Self::Truncated { .. } => "truncated",
+            Self::Unreadable(_) => "unreadable",
+        }
+    }
+}
+
+impl fmt::Display for ChainBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Modified { seq } => write!(f, "Audit entry {} was modified", seq),
+            Self::Truncated { expected, found } => {
+                write!(f, "Audit log truncated: {} entries written, {} present", expected, found)
+            }
+            Self::Unreadable(reason) => write!(f, "Audit log unreadable: {}", reason),
+        }
+    }
+}
+
+/// Latest position in the log, MACed so the log can't be cut short unnoticed
+#[derive(Serialize, Deserialize)]
+struct Head {
+    next_seq: u64,
+    hash: String,
+    mac: String,
+}
+
+/// Append-only, hash-chained record of every request the daemon serves
+///
+/// Each entry carries an HMAC over its fields and the previous entry's
+/// hash, keyed with a secret held in the storage backend, so entries can't
+/// be edited, dropped or reordered without the key. A separate MACed head
+/// file records the last entry written, which catches truncation. Rolling
+/// back both files to an older copy is not detectable from the files alone.
+pub struct AuditLog {
+    /// JSON lines; `None` keeps entries in memory
+    path: Option<PathBuf>,
+    key: SecureMemory,
+    inner: Mutex<AuditInner>,
+}
+
+struct AuditInner {
+    next_seq: u64,
+    last_hash: String,
+    memory: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Open the log at `path`, checking its chain and continuing it
+    pub fn open(storage: &dyn KeyStorage, path: Option<PathBuf>) -> Result<Self> {
+        let key = load_or_create_internal_key(storage, AUDIT_KEY_ID)?;
+        let log = Self::with_key(key, path);
+
+        if let Some(path) = &log.path {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+                restrict_dir(dir)?;
+            }
+            if let Ok(entries) = read_entries(path) {
+                if let Some(last) = entries.last() {
+                    let mut inner = log.inner();
+                    inner.next_seq = last.seq + 1;
+                    inner.last_hash = last.hash.clone();
+                }
+            }
+        }
+
+        // Keep the evidence in the log itself: the head is about to move on
+        if let Err(chain_break) = log.verify() {
+            eprintln!("❌ {}", chain_break);
+            log.record(&AuditEvent::daemon("verify_audit_log", chain_break.outcome()))?;
+        }
+        Ok(log)
+    }
+
+    /// Log kept in memory under a throwaway key (tests and the memory backend)
+    pub fn in_memory() -> Self {
+        let key = SecureMemory::from_slice(MemoryVault::generate_key().as_bytes())
+            .expect("mapping a page of secure memory only fails when out of memory");
+        Self::with_key(key, None)
+    }
+
+    fn with_key(key: SecureMemory, path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            key,
+            inner: Mutex::new(AuditInner {
+                next_seq: 0,
+                last_hash: genesis_hash(),
+                memory: Vec::new(),
+            }),
+        }
+    }
+
+    /// Chain `event` onto the log
+    pub fn record(&self, event: &AuditEvent<'_>) -> Result<()> {
+        let mut inner = self.inner();
+        let mut entry = AuditEntry {
+            seq: inner.next_seq,
+            timestamp: chrono::Utc::now().timestamp(),
+            pid: event.pid,
+            uid: event.uid,
+            client: event.client.to_string(),
+            operation: event.operation.to_string(),
+            key_id: event.key_id.map(str::to_string),
+            outcome: event.outcome.to_string(),
+            prev_hash: inner.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry_hash(&self.key, &entry)?;
+
+        match &self.path {
+            Some(path) => {
+                let mut line = serde_json::to_vec(&entry)?;
+                line.push(b'\n');
+                let mut options = OpenOptions::new();
+                options.create(true).append(true);
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::OpenOptionsExt;
+                    options.mode(0o600);
+                }
+                let mut file = options.open(path)?;
+                file.write_all(&line)?;
+                file.sync_data()?;
+
+                let head = Head {
+                    next_seq: entry.seq + 1,
+                    mac: head_mac(&self.key, entry.seq + 1, &entry.hash),
+                    hash: entry.hash.clone(),
+                };
+                write_atomic(&head_path(path), &serde_json::to_vec(&head)?)?;
+            }
+            None => inner.memory.push(entry.clone()),
+        }
+
+        inner.next_seq = entry.seq + 1;
+        inner.last_hash = entry.hash;
+        Ok(())
+    }
+
+    /// Entries with `since <= timestamp <= until`, keeping the newest `limit`
+    pub fn query(&self, since: Option<i64>, until: Option<i64>, limit: Option<u32>) -> Result<Vec<AuditEntry>> {
+        let limit = limit
+            .map(|limit| limit as usize)
+            .filter(|&limit| limit > 0)
+            .unwrap_or(MAX_AUDIT_ENTRIES)
+            .min(MAX_AUDIT_ENTRIES);
+        let mut entries: Vec<AuditEntry> = self
+            .entries()
+            .map_err(|e| VaultError::Storage(e.to_string()))?
+            .into_iter()
+            .filter(|entry| since.is_none_or(|since| entry.timestamp >= since))
+            .filter(|entry| until.is_none_or(|until| entry.timestamp <= until))
+            .collect();
+        let skip = entries.len().saturating_sub(limit);
+        entries.drain(..skip);
+        Ok(entries)
+    }
+
+    /// Check every entry's hash and link, and that nothing was cut off the end
+    ///
+    /// Returns the number of entries on success.
+    pub fn verify(&self) -> std::result::Result<u64, ChainBreak> {
+        let _inner = self.inner();
+        let entries = match &self.path {
+            Some(path) => read_entries(path)?,
+            None => _inner.memory.clone(),
+        };
+
+        let mut prev_hash = genesis_hash();
+        for (index, entry) in entries.iter().enumerate() {
+            let seq = index as u64;
+            let valid = entry.seq == seq
+                && entry.prev_hash == prev_hash
+                && entry_hash(&self.key, entry).is_ok_and(|hash| hash == entry.hash);
+            if !valid {
+                return Err(ChainBreak::Modified { seq });
+            }
+            prev_hash = entry.hash.clone();
+        }
+
+        if let Some(path) = &self.path {
+            self.verify_head(path, &entries)?;
+        }
+        Ok(entries.len() as u64)
+    }
+
+    fn verify_head(&self, path: &Path, entries: &[AuditEntry]) -> std::result::Result<(), ChainBreak> {
+        let head = match fs::read(head_path(path)) {
+            Ok(json) => serde_json::from_slice::<Head>(&json)
+                .map_err(|e| ChainBreak::Unreadable(format!("Corrupt head record: {}", e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && entries.is_empty() => return Ok(()),
+            Err(e) => return Err(ChainBreak::Unreadable(format!("Missing head record: {}", e))),
+        };
+        if head.mac != head_mac(&self.key, head.next_seq, &head.hash) {
+            return Err(ChainBreak::Unreadable("Head record was modified".to_string()));
+        }
+
+        let found = entries.len() as u64;
+        if found < head.next_seq {
+            return Err(ChainBreak::Truncated {
+                expected: head.next_seq,
+                found,
+            });
+        }
+        // A crash between appending and moving the head can leave extra entries; the head's must match
+        match head.next_seq.checked_sub(1) {
+            Some(seq) if entries[seq as usize].hash != head.hash => Err(ChainBreak::Modified { seq }),
+            _ => Ok(()),
+        }
+    }
+
+    fn entries(&self) -> std::result::Result<Vec<AuditEntry>, ChainBreak> {
+        let inner = self.inner();
+        match &self.path {
+            Some(path) => read_entries(path),
+            None => Ok(inner.memory.clone()),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, AuditInner> {
+        // Appends finish or fail as a whole, so a poisoned lock still holds a usable head
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn read_entries(path: &Path) -> std::result::Result<Vec<AuditEntry>, ChainBreak> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(ChainBreak::Unreadable(e.to_string())),
+    };
+    contents
+        .lines()
+        .enumerate()
+        .map(|(line, json)| {
+            serde_json::from_str(json).map_err(|e| ChainBreak::Unreadable(format!("Line {}: {}", line + 1, e)))
+        })
+        .collect()
+}
+
+fn head_path(path: &Path) -> PathBuf {
+    path.with_extension("head")
+}
+
+fn genesis_hash() -> String {
+    hex(&[0u8; mac::TAG_SIZE])
+}
+
+/// HMAC over every field but `hash`, in a fixed order
+fn entry_hash(key: &SecureMemory, entry: &AuditEntry) -> Result<String> {
+    let fields = serde_json::to_vec(&(
+        entry.seq,
+        entry.timestamp,
+        entry.pid,
+        entry.uid,
+        &entry.client,
+        &entry.operation,
+        &entry.key_id,
+        &entry.outcome,
+        &entry.prev_hash,
+    ))?;
+    Ok(hex(&mac::sign(key.as_slice(), &fields)))
+}
+
+fn head_mac(key: &SecureMemory, next_seq: u64, hash: &str) -> String {
+    let message = [HEAD_CONTEXT, &next_seq.to_be_bytes(), hash.as_bytes()].concat();
+    hex(&mac::sign(key.as_slice(), &message))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::MemoryKeyStorage;
+
+    fn record(log: &AuditLog, operation: &str, key_id: &str) {
+        let peer = PeerInfo {
+            pid: Some(42),
+            uid: Some(1000),
+            executable: None,
+        };
+        log.record(&AuditEvent::peer(&peer, "test", operation, Some(key_id), "ok")).unwrap();
+    }
+
+    fn open_with_entries(dir: &Path, storage: &MemoryKeyStorage) -> (AuditLog, PathBuf) {
+        let path = dir.join(AUDIT_LOG_NAME);
+        let log = AuditLog::open(storage, Some(path.clone())).unwrap();
+        for key_id in ["a", "b", "c"] {
+            record(&log, "retrieve_key", key_id);
+        }
+        (log, path)
+    }
+
+    #[test]
+    fn test_chain_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = MemoryKeyStorage::new();
+        let (log, path) = open_with_entries(dir.path(), &storage);
+        assert_eq!(log.verify(), Ok(3));
+        drop(log);
+
+        let log = AuditLog::open(&storage, Some(path)).unwrap();
+        record(&log, "delete_key", "a");
+        assert_eq!(log.verify(), Ok(4));
+        let entries = log.query(None, None, Some(2)).unwrap();
+        assert_eq!(entries.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(entries[1].operation, "delete_key");
+    }
+
+    #[test]
+    fn test_edit_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = MemoryKeyStorage::new();
+        let (log, path) = open_with_entries(dir.path(), &storage);
+
+        let edited = fs::read_to_string(&path).unwrap().replacen("\"b\"", "\"z\"", 1);
+        fs::write(&path, edited).unwrap();
+        assert_eq!(log.verify(), Err(ChainBreak::Modified { seq: 1 }));
+    }
+
+    #[test]
+    fn test_removed_entry_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = MemoryKeyStorage::new();
+        let (log, path) = open_with_entries(dir.path(), &storage);
+
+        let lines: Vec<String> = fs::read_to_string(&path).unwrap().lines().map(str::to_string).collect();
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert_eq!(log.verify(), Err(ChainBreak::Modified { seq: 1 }));
+    }
+
+    #[test]
+    fn test_truncation_is_detected_and_logged() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = MemoryKeyStorage::new();
+        let (log, path) = open_with_entries(dir.path(), &storage);
+        drop(log);
+
+        let kept: String = fs::read_to_string(&path).unwrap().lines().take(2).map(|l| format!("{}\n", l)).collect();
+        fs::write(&path, kept).unwrap();
+
+        // Reopening records the break, which then stays in the chain
+        let log = AuditLog::open(&storage, Some(path)).unwrap();
+        let entries = log.query(None, None, None).unwrap();
+        let last = entries.last().unwrap();
+        assert_eq!((last.operation.as_str(), last.outcome.as_str()), ("verify_audit_log", "truncated"));
+        assert_eq!(log.verify(), Ok(3));
+    }
+
+    #[test]
+    fn test_query_time_range() {
+        let log = AuditLog::in_memory();
+        record(&log, "store_key", "a");
+        let now = chrono::Utc::now().timestamp();
+
+        assert_eq!(log.query(Some(now - 60), Some(now + 60), None).unwrap().len(), 1);
+        assert!(log.query(Some(now + 60), None, None).unwrap().is_empty());
+        assert!(log.query(None, Some(now - 60), None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::error::{Result, VaultError};
+use crate::identity::IDENTITY_KEY_ID;
+use crate::keychain::{is_internal_key, KeyMetadata, KeyStorage};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use identra_crypto::{KeyDerivationParams, KeyFile, MemoryVault, SecretBytes};
+use identra_ipc::{RestoreMode, RestoreSummary};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Marks a file as an Identra vault backup
+const BACKUP_FORMAT: &str = "identra-vault-backup";
+
+/// Current archive format version
+pub const BACKUP_VERSION: u8 = 1;
+
+/// Domain separation for the payload's associated data
+const PAYLOAD_CONTEXT: &[u8] = b"identra-backup-v1";
+
+/// Passphrase-encrypted archive of the vault's keys
+///
+/// A random key, wrapped under the passphrase in a [`KeyFile`], seals the
+/// key entries. The header fields are bound to the sealed payload as
+/// associated data, so editing any part of the file fails the integrity
+/// check on restore.
+#[derive(Serialize, Deserialize)]
+struct BackupArchive {
+    format: String,
+    version: u8,
+    created_at: i64,
+    key_count: u32,
+    key_file: KeyFile,
+    /// Base64 envelope of the JSON key entries
+    payload: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupEntry {
+    key_id: String,
+    key: SecretBytes,
+    metadata: KeyMetadata,
+}
+
+/// Keys that travel in backups: client keys and the identity, not the daemon's index or audit keys
+fn is_backed_up(key_id: &str) -> bool {
+    !is_internal_key(key_id) || key_id == IDENTITY_KEY_ID
+}
+
+/// Write every live key in `storage` that `include` accepts to a new archive at `path`
+///
+/// Expired keys are left out. An existing file is never replaced, and the
+/// path is checked as in [`caller_path`]. Runs Argon2id; call it off the
+/// async runtime.
+pub fn export(
+    storage: &dyn KeyStorage,
+    path: &Path,
+    owner: Option<u32>,
+    passphrase: &[u8],
+    params: KeyDerivationParams,
+    include: impl Fn(&KeyMetadata) -> bool,
+) -> Result<u32> {
+    let now = chrono::Utc::now().timestamp();
+    let mut entries = Vec::new();
+    for key_id in storage.list_keys()?.into_iter().filter(|key_id| is_backed_up(key_id)) {
+        let (key, metadata) = storage.retrieve_key(&key_id)?;
+        if !metadata.is_expired(now) && include(&metadata) {
+            entries.push(BackupEntry { key_id, key, metadata });
+        }
+    }
+
+    let (key_file, archive_key) = KeyFile::create(passphrase, params)
+        .map_err(|e| VaultError::Encryption(format!("Failed to derive backup key: {}", e)))?;
+    let key_count = entries.len() as u32;
+    let aad = payload_aad(now, key_count, &key_file);
+    let plaintext = Zeroizing::new(serde_json::to_vec(&entries)?);
+    let payload = MemoryVault::seal_bytes(&plaintext, &archive_key, &aad)
+        .map_err(|e| VaultError::Encryption(format!("Failed to seal backup: {}", e)))?;
+
+    let archive = BackupArchive {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        created_at: now,
+        key_count,
+        key_file,
+        payload: BASE64.encode(payload),
+    };
+    write_new(&caller_path(path, owner)?, &serde_json::to_vec_pretty(&archive)?)?;
+    println!("💾 Backed up {} keys to {}", key_count, path.display());
+    Ok(key_count)
+}
+
+/// Restore the archive at `path` into `storage`
+///
+/// In [`RestoreMode::Merge`] keys already in the vault win; in
+/// [`RestoreMode::Replace`] the archive's keys overwrite them and keys not
+/// in the archive are deleted. Existing keys `replaceable` rejects are
+/// never touched and count as skipped. A dry run only computes the summary.
+/// The path is checked as in [`caller_path`], and the archive itself must
+/// belong to `owner` too. Runs Argon2id; call it off the async runtime.
+pub fn import(
+    storage: &dyn KeyStorage,
+    path: &Path,
+    owner: Option<u32>,
+    passphrase: &[u8],
+    mode: RestoreMode,
+    dry_run: bool,
+    replaceable: impl Fn(&KeyMetadata) -> bool,
+) -> Result<RestoreSummary> {
+    let entries = read_archive(&caller_path(path, owner)?, owner, passphrase)?;
+    let mut existing = BTreeSet::new();
+    let mut protected = BTreeSet::new();
+    for key_id in storage.list_keys()?.into_iter().filter(|key_id| is_backed_up(key_id)) {
+        if !replaceable(&storage.key_metadata(&key_id)?) {
+            protected.insert(key_id.clone());
+        }
+        existing.insert(key_id);
+    }
+
+    let mut summary = RestoreSummary {
+        dry_run,
+        ..RestoreSummary::default()
+    };
+    let mut to_store = Vec::new();
+    for (key_id, entry) in &entries {
+        if !existing.contains(key_id) {
+            summary.added.push(key_id.clone());
+            to_store.push(entry);
+        } else if mode == RestoreMode::Replace && !protected.contains(key_id) {
+            summary.replaced.push(key_id.clone());
+            to_store.push(entry);
+        } else {
+            summary.skipped.push(key_id.clone());
+        }
+    }
+    if mode == RestoreMode::Replace {
+        for key_id in existing.into_iter().filter(|key_id| !entries.contains_key(key_id)) {
+            if protected.contains(&key_id) {
+                summary.skipped.push(key_id);
+            } else {
+                summary.removed.push(key_id);
+            }
+        }
+    }
+
+    if !dry_run {
+        // Store before deleting, so a failure part-way never loses keys the archive lacks
+        for entry in to_store {
+            storage.store_key(&entry.key_id, entry.key.as_bytes(), entry.metadata.clone())?;
+        }
+        for key_id in &summary.removed {
+            storage.delete_key(key_id)?;
+        }
+        println!(
+            "♻️ Restored backup from {}: {} added, {} replaced, {} removed",
+            path.display(),
+            summary.added.len(),
+            summary.replaced.len(),
+            summary.removed.len()
+        );
+    }
+    Ok(summary)
+}
+
+/// Resolve a client-supplied backup path
+///
+/// The daemon may be able to reach files its clients can't, so the path
+/// must be absolute and, where the caller's uid is known, its directory
+/// must belong to that uid.
+fn caller_path(path: &Path, owner: Option<u32>) -> Result<PathBuf> {
+    if !path.is_absolute() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Backup path must be absolute").into());
+    }
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Backup path must name a file").into());
+    };
+    let dir = dir.canonicalize()?;
+    check_owner(&fs::metadata(&dir)?, owner, &dir)?;
+    Ok(dir.join(name))
+}
+
+/// Refuse files and directories that don't belong to `owner`
+fn check_owner(metadata: &fs::Metadata, owner: Option<u32>, path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(uid) = owner {
+        use std::os::unix::fs::MetadataExt;
+        if metadata.uid() != uid {
+            let message = format!("{} does not belong to uid {}", path.display(), uid);
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, message).into());
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (metadata, owner, path);
+    Ok(())
+}
+
+/// Create `path` with `contents`, failing if it already exists
+///
+/// Unlike the keystore's atomic writes there is no rename, so a file or
+/// symlink already at `path` is never replaced. A partial file is removed.
+fn write_new(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    if let Err(e) = file.write_all(contents).and_then(|()| file.sync_all()) {
+        drop(file);
+        let _ = fs::remove_file(path);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Decrypt and check an archive, keyed by key id
+fn read_archive(path: &Path, owner: Option<u32>, passphrase: &[u8]) -> Result<BTreeMap<String, BackupEntry>> {
+    // Check the file that was opened, not whatever the path names afterwards
+    let mut file = File::open(path)?;
+    check_owner(&file.metadata()?, owner, path)?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    let archive: BackupArchive = serde_json::from_slice(&contents)
+        .map_err(|e| VaultError::Storage(format!("Not a vault backup: {}", e)))?;
+    if archive.format != BACKUP_FORMAT {
+        return Err(VaultError::Storage("Not a vault backup".to_string()));
+    }
+    if archive.version != BACKUP_VERSION {
+        return Err(VaultError::Storage(format!("Unsupported backup version {}", archive.version)));
+    }
+
+    let archive_key = archive.key_file.unlock(passphrase).map_err(|_| VaultError::WrongPassphrase)?;
+    let aad = payload_aad(archive.created_at, archive.key_count, &archive.key_file);
+    let integrity_error = || VaultError::Encryption("Backup failed its integrity check".to_string());
+    let payload = BASE64.decode(&archive.payload).map_err(|_| integrity_error())?;
+    let plaintext = Zeroizing::new(
+        MemoryVault::open_bytes(&payload, &archive_key, &aad).map_err(|_| integrity_error())?,
+    );
+    let entries: Vec<BackupEntry> = serde_json::from_slice(&plaintext)?;
+    if entries.len() != archive.key_count as usize {
+        return Err(integrity_error());
+    }
+
+    Ok(entries
+        .into_iter()
+        .filter(|entry| is_backed_up(&entry.key_id))
+        .map(|entry| (entry.key_id.clone(), entry))
+        .collect())
+}
+
+fn payload_aad(created_at: i64, key_count: u32, key_file: &KeyFile) -> Vec<u8> {
+    [
+        PAYLOAD_CONTEXT,
+        &created_at.to_be_bytes(),
+        &key_count.to_be_bytes(),
+        key_file.key_id.as_bytes(),
+    ]
+    .concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::MemoryKeyStorage;
+    use std::collections::HashMap;
+
+    fn metadata(expires_at: Option<i64>) -> KeyMetadata {
+        KeyMetadata {
+            created_at: 1_700_000_000,
+            expires_at,
+            custom: HashMap::from([("purpose".to_string(), "test".to_string())]),
+            ..Default::default()
+        }
+    }
+
+    fn storage_with(keys: &[(&str, &[u8])]) -> MemoryKeyStorage {
+        let storage = MemoryKeyStorage::new();
+        for (key_id, key) in keys {
+            storage.store_key(key_id, key, metadata(None)).unwrap();
+        }
+        storage
+    }
+
+    #[test]
+    fn test_roundtrip_into_empty_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.backup");
+        let source = storage_with(&[("a", b"key a"), ("b", b"key b"), (crate::audit::AUDIT_KEY_ID, b"internal")]);
+        source.store_key("old", b"stale", metadata(Some(1))).unwrap();
+
+        assert_eq!(export(&source, &path, None, b"backup pass", KeyDerivationParams::fast(), |_| true).unwrap(), 2);
+
+        let target = MemoryKeyStorage::new();
+        let summary = import(&target, &path, None, b"backup pass", RestoreMode::Merge, false, |_| true).unwrap();
+        assert_eq!(summary.added, vec!["a", "b"]);
+        let (key, restored) = target.retrieve_key("a").unwrap();
+        assert_eq!(key.as_bytes(), b"key a");
+        assert_eq!(restored.custom["purpose"], "test");
+        assert!(!target.key_exists(crate::audit::AUDIT_KEY_ID));
+    }
+
+    #[test]
+    fn test_merge_and_replace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.backup");
+        export(&storage_with(&[("a", b"old a"), ("b", b"old b")]), &path, None, b"pass", KeyDerivationParams::fast(), |_| true).unwrap();
+
+        let target = storage_with(&[("b", b"new b"), ("c", b"new c")]);
+        let dry = import(&target, &path, None, b"pass", RestoreMode::Replace, true, |_| true).unwrap();
+        assert_eq!(dry.added, vec!["a"]);
+        assert_eq!(dry.replaced, vec!["b"]);
+        assert_eq!(dry.removed, vec!["c"]);
+        assert!(dry.dry_run && !target.key_exists("a"));
+
+        let merged = import(&target, &path, None, b"pass", RestoreMode::Merge, false, |_| true).unwrap();
+        assert_eq!((merged.added, merged.skipped), (vec!["a".to_string()], vec!["b".to_string()]));
+        assert_eq!(target.retrieve_key("b").unwrap().0.as_bytes(), b"new b");
+
+        let replaced = import(&target, &path, None, b"pass", RestoreMode::Replace, false, |_| true).unwrap();
+        assert_eq!(replaced.removed, vec!["c"]);
+        assert_eq!(target.retrieve_key("b").unwrap().0.as_bytes(), b"old b");
+        assert_eq!(target.list_keys().unwrap(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_filters_limit_export_and_replacement() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.backup");
+        let source = storage_with(&[("a", b"old a"), ("b", b"old b")]);
+        let not_secret = |metadata: &KeyMetadata| !metadata.custom.contains_key("secret");
+        let mut secret = metadata(None);
+        secret.custom.insert("secret".to_string(), String::new());
+        source.store_key("secret", b"kept in", secret.clone()).unwrap();
+        assert_eq!(export(&source, &path, None, b"pass", KeyDerivationParams::fast(), not_secret).unwrap(), 2);
+
+        let target = storage_with(&[("a", b"new a")]);
+        target.store_key("b", b"new b", secret.clone()).unwrap();
+        target.store_key("c", b"new c", secret).unwrap();
+        let summary = import(&target, &path, None, b"pass", RestoreMode::Replace, false, not_secret).unwrap();
+        assert_eq!(summary.replaced, vec!["a"]);
+        assert_eq!(summary.skipped, vec!["b", "c"]);
+        assert!(summary.removed.is_empty());
+        assert_eq!(target.retrieve_key("b").unwrap().0.as_bytes(), b"new b");
+    }
+
+    #[test]
+    fn test_wrong_passphrase_and_tampering() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.backup");
+        export(&storage_with(&[("a", b"key a")]), &path, None, b"pass", KeyDerivationParams::fast(), |_| true).unwrap();
+        let target = MemoryKeyStorage::new();
+
+        let err = import(&target, &path, None, b"wrong", RestoreMode::Merge, false, |_| true).unwrap_err();
+        assert!(matches!(err, VaultError::WrongPassphrase));
+
+        let mut archive: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        archive["key_count"] = 0.into();
+        fs::write(&path, serde_json::to_vec(&archive).unwrap()).unwrap();
+        let err = import(&target, &path, None, b"pass", RestoreMode::Merge, false, |_| true).unwrap_err();
+        assert!(matches!(err, VaultError::Encryption(_)));
+        assert!(target.list_keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_backup_paths_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.backup");
+        let source = storage_with(&[("a", b"key a")]);
+        let kind = |err: VaultError| match err {
+            VaultError::Io(e) => e.kind(),
+            e => panic!("unexpected error: {}", e),
+        };
+
+        let err = export(&source, Path::new("vault.backup"), None, b"pass", KeyDerivationParams::fast(), |_| true).unwrap_err();
+        assert_eq!(kind(err), io::ErrorKind::InvalidInput);
+
+        fs::write(&path, b"keep me").unwrap();
+        let err = export(&source, &path, None, b"pass", KeyDerivationParams::fast(), |_| true).unwrap_err();
+        assert_eq!(kind(err), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"keep me");
+
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link.backup");
+            std::os::unix::fs::symlink(&path, &link).unwrap();
+            let err = export(&source, &link, None, b"pass", KeyDerivationParams::fast(), |_| true).unwrap_err();
+            assert_eq!(kind(err), io::ErrorKind::AlreadyExists);
+            assert_eq!(fs::read(&path).unwrap(), b"keep me");
+
+            let me = unsafe { libc::geteuid() };
+            let fresh = dir.path().join("fresh.backup");
+            let err = export(&source, &fresh, Some(me + 1), b"pass", KeyDerivationParams::fast(), |_| true).unwrap_err();
+            assert_eq!(kind(err), io::ErrorKind::PermissionDenied);
+            assert!(!fresh.exists());
+
+            export(&source, &fresh, Some(me), b"pass", KeyDerivationParams::fast(), |_| true).unwrap();
+            let target = MemoryKeyStorage::new();
+            let err = import(&target, &fresh, Some(me + 1), b"pass", RestoreMode::Merge, false, |_| true).unwrap_err();
+            assert_eq!(kind(err), io::ErrorKind::PermissionDenied);
+            import(&target, &fresh, Some(me), b"pass", RestoreMode::Merge, false, |_| true).unwrap();
+            assert!(target.key_exists("a"));
+        }
+    }
+}
@@ -0,0 +1,573 @@
+use crate::error::{Result, VaultError};
+use crate::keychain::{default_store_dir, StorageBackend, KEYCHAIN_SUPPORTED};
+use crate::lock::DEFAULT_AUTO_LOCK;
+use clap::{Parser, ValueEnum};
+use identra_ipc::{KeyPolicy, DEFAULT_SOCKET_NAME, SOCKET_ENV};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Config file to load instead of the default location
+pub const CONFIG_ENV: &str = "IDENTRA_VAULT_CONFIG";
+
+/// Selects the storage backend (`keychain`, `file` or `memory`)
+pub const STORAGE_ENV: &str = "IDENTRA_VAULT_STORAGE";
+
+/// State directory, and the keystore for the `file` backend
+pub const STORE_DIR_ENV: &str = "IDENTRA_VAULT_STORE_DIR";
+
+/// OS keychain service the keys (and the IPC token) are stored under
+pub use identra_ipc::token::SERVICE_ENV;
+
+/// Octal permissions for a filesystem socket
+pub const SOCKET_MODE_ENV: &str = "IDENTRA_VAULT_SOCKET_MODE";
+
+/// Idle seconds before the vault locks itself; 0 disables auto-lock
+pub const AUTO_LOCK_ENV: &str = "IDENTRA_VAULT_AUTO_LOCK";
+
+/// File the daemon logs to instead of stdout/stderr
+pub const LOG_FILE_ENV: &str = "IDENTRA_VAULT_LOG_FILE";
+
+/// Policy entries allowed to open sealed boxes with the identity key, comma-separated
+pub const IDENTITY_USERS_ENV: &str = "IDENTRA_VAULT_IDENTITY_USERS";
+
+/// Policy entries allowed to set the vault's first passphrase, comma-separated
+pub const SETUP_USERS_ENV: &str = "IDENTRA_VAULT_SETUP_USERS";
+
+/// Keychain service used unless configured otherwise
+pub const DEFAULT_SERVICE: &str = identra_ipc::token::TOKEN_KEYRING_SERVICE;
+
+/// Permissions given to filesystem sockets unless configured otherwise
+pub const DEFAULT_SOCKET_MODE: u32 = 0o600;
+
+/// Command-line flags; each overrides the config file and environment
+#[derive(Debug, Default, Parser)]
+#[command(name = "vault-daemon", version, about = "Identra vault daemon")]
+pub struct CliArgs {
+    /// Config file (default: <config dir>/identra/vault.toml, if present)
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Socket name, or a filesystem path if it contains a separator
+    #[arg(long, value_name = "NAME|PATH")]
+    pub socket: Option<String>,
+    /// Octal permissions for a filesystem socket, e.g. 660
+    #[arg(long, value_name = "OCTAL", value_parser = parse_mode)]
+    pub socket_mode: Option<u32>,
+    #[arg(long, value_enum)]
+    pub backend: Option<BackendKind>,
+    /// State directory, and the keystore for the file backend
+    #[arg(long, value_name = "DIR")]
+    pub store_dir: Option<PathBuf>,
+    /// Keychain service namespace
+    #[arg(long, value_name = "NAME")]
+    pub service: Option<String>,
+    /// Idle seconds before auto-lock; 0 disables it
+    #[arg(long, value_name = "SECS")]
+    pub auto_lock: Option<u64>,
+    /// Log to this file instead of stdout/stderr
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+    /// Policy entry allowed to set the first passphrase (repeatable)
+    #[arg(long = "setup-user", value_name = "ENTRY")]
+    pub setup_users: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    Keychain,
+    File,
+    Memory,
+}
+
+impl BackendKind {
+    /// The OS keychain where there's a backend for it, the file backend elsewhere
+    pub fn platform_default() -> Self {
+        if KEYCHAIN_SUPPORTED {
+            Self::Keychain
+        } else {
+            Self::File
+        }
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        match s {
+            "keychain" => Ok(Self::Keychain),
+            "file" => Ok(Self::File),
+            "memory" => Ok(Self::Memory),
+            other => Err(format!(
+                "Unknown storage backend {:?} (expected \"keychain\", \"file\" or \"memory\")",
+                other
+            )),
+        }
+    }
+}
+
+/// Daemon settings, layered as defaults < config file < environment < flags
+///
+/// ```toml
+/// [socket]
+/// path = "/run/identra/vault.sock"   # or a bare name
+/// mode = 0o660                       # filesystem sockets only
+///
+/// [storage]
+/// backend = "file"                   # keychain | file | memory
+/// dir = "/var/lib/identra"
+/// service = "identra-vault"          # keychain namespace
+///
+/// [lock]
+/// auto_lock_secs = 900               # 0 disables
+///
+/// [log]
+/// file = "/var/log/identra/vault.log"
+///
+/// [identity]
+/// users = ["exe:/opt/identra/ghost-desktop"]   # who may open sealed boxes
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    pub socket: SocketConfig,
+    pub storage: StorageConfig,
+    pub lock: LockConfig,
+    pub log: LogConfig,
+    pub identity: IdentityConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SocketConfig {
+    pub path: String,
+    /// Defaults to [`DEFAULT_SOCKET_MODE`] for filesystem sockets
+    pub mode: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: BackendKind,
+    /// Keystore (file backend) and state directory; per-user data dir by default
+    pub dir: Option<PathBuf>,
+    pub service: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockConfig {
+    pub auto_lock_secs: u64,
+    /// Policy entries that may set the first passphrase; any client that passes the
+    /// peer checks when unset, so the first one to connect chooses it
+    pub setup_users: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// stdout/stderr when unset
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdentityConfig {
+    /// Policy entries that may open sealed boxes; the desktop app next to the daemon by
+    /// default, or any client where the daemon can't see peers' executables
+    pub users: Option<Vec<String>>,
+}
+
+impl Default for SocketConfig {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_SOCKET_NAME.to_string(),
+            mode: None,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: BackendKind::platform_default(),
+            dir: None,
+            service: DEFAULT_SERVICE.to_string(),
+        }
+    }
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            auto_lock_secs: DEFAULT_AUTO_LOCK.as_secs(),
+            setup_users: None,
+        }
+    }
+}
+
+impl Default for IdentityConfig {
+    fn default() -> Self {
+        Self {
+            users: crate::identity::desktop_principal().map(|desktop| vec![desktop]),
+        }
+    }
+}
+
+impl DaemonConfig {
+    /// Config for this process: file, then environment, then `cli`, validated
+    pub fn load(cli: &CliArgs) -> Result<Self> {
+        let explicit = cli
+            .config
+            .clone()
+            .or_else(|| std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()).map(PathBuf::from));
+        let mut config = match explicit {
+            Some(path) => Self::from_file(&path)?,
+            None => match default_config_path().filter(|path| path.exists()) {
+                Some(path) => Self::from_file(&path)?,
+                None => Self::default(),
+            },
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.apply_cli(cli);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse a TOML config file; unknown keys are errors
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| VaultError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+        let config = toml::from_str(&contents)
+            .map_err(|e| VaultError::Config(format!("Invalid config file {}: {}", path.display(), e)))?;
+        println!("⚙️ Loaded config from {}", path.display());
+        Ok(config)
+    }
+
+    /// Apply `IDENTRA_VAULT_*` overrides read through `var`; empty values are ignored
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        let invalid = |name: &str, reason: String| VaultError::Config(format!("Invalid {}: {}", name, reason));
+
+        if let Some(path) = var(SOCKET_ENV) {
+            self.socket.path = path;
+        }
+        if let Some(mode) = var(SOCKET_MODE_ENV) {
+            self.socket.mode = Some(parse_mode(&mode).map_err(|e| invalid(SOCKET_MODE_ENV, e))?);
+        }
+        if let Some(backend) = var(STORAGE_ENV) {
+            self.storage.backend = backend.parse().map_err(|e| invalid(STORAGE_ENV, e))?;
+        }
+        if let Some(dir) = var(STORE_DIR_ENV) {
+            self.storage.dir = Some(PathBuf::from(dir));
+        }
+        if let Some(service) = var(SERVICE_ENV) {
+            self.storage.service = service;
+        }
+        if let Some(secs) = var(AUTO_LOCK_ENV) {
+            self.lock.auto_lock_secs = secs
+                .parse()
+                .map_err(|_| invalid(AUTO_LOCK_ENV, format!("{:?} is not a number of seconds", secs)))?;
+        }
+        if let Some(file) = var(LOG_FILE_ENV) {
+            self.log.file = Some(PathBuf::from(file));
+        }
+        if let Some(users) = var(IDENTITY_USERS_ENV) {
+            self.identity.users = Some(split_list(&users));
+        }
+        if let Some(users) = var(SETUP_USERS_ENV) {
+            self.lock.setup_users = Some(split_list(&users));
+        }
+        Ok(())
+    }
+
+    pub fn apply_cli(&mut self, cli: &CliArgs) {
+        if let Some(path) = &cli.socket {
+            self.socket.path = path.clone();
+        }
+        if let Some(mode) = cli.socket_mode {
+            self.socket.mode = Some(mode);
+        }
+        if let Some(backend) = cli.backend {
+            self.storage.backend = backend;
+        }
+        if let Some(dir) = &cli.store_dir {
+            self.storage.dir = Some(dir.clone());
+        }
+        if let Some(service) = &cli.service {
+            self.storage.service = service.clone();
+        }
+        if let Some(secs) = cli.auto_lock {
+            self.lock.auto_lock_secs = secs;
+        }
+        if let Some(file) = &cli.log_file {
+            self.log.file = Some(file.clone());
+        }
+        if !cli.setup_users.is_empty() {
+            self.lock.setup_users = Some(cli.setup_users.clone());
+        }
+    }
+
+    /// Reject settings the daemon can't honour, before anything is opened
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(VaultError::Config(message));
+
+        if self.socket.path.trim().is_empty() {
+            return invalid("socket.path must not be empty".to_string());
+        }
+        if let Some(mode) = self.socket.mode {
+            if mode > 0o777 {
+                return invalid(format!("socket.mode {:o} is not a permission mode (at most 777)", mode));
+            }
+            if mode & 0o600 != 0o600 {
+                return invalid(format!("socket.mode {:o} must let the daemon's user read and write", mode));
+            }
+            if !cfg!(unix) || !is_fs_socket(&self.socket.path) {
+                return invalid(format!(
+                    "socket.mode only applies to filesystem socket paths, not {:?}",
+                    self.socket.path
+                ));
+            }
+        }
+        if self.storage.service.trim().is_empty() {
+            return invalid("storage.service must not be empty".to_string());
+        }
+        if self.storage.backend == BackendKind::Keychain && !KEYCHAIN_SUPPORTED {
+            return invalid("storage.backend \"keychain\" is not supported on this platform; use \"file\"".to_string());
+        }
+        if self.storage.backend == BackendKind::Memory && self.storage.dir.is_some() {
+            return invalid("storage.dir has no effect with the memory backend".to_string());
+        }
+        if let Some(dir) = &self.storage.dir {
+            if !dir.is_absolute() {
+                return invalid(format!("storage.dir must be an absolute path, got {}", dir.display()));
+            }
+        }
+        if let Some(file) = &self.log.file {
+            if !cfg!(unix) {
+                return invalid("log.file is only supported on Unix; redirect the service's output instead".to_string());
+            }
+            if file.is_dir() {
+                return invalid(format!("log.file {} is a directory", file.display()));
+            }
+        }
+        let identity_policy = KeyPolicy {
+            users: self.identity.users.clone(),
+            ..KeyPolicy::default()
+        };
+        if let Err(reason) = crate::policy::validate(&identity_policy) {
+            return invalid(format!("identity.users: {}", reason));
+        }
+        let setup_policy = KeyPolicy {
+            users: self.lock.setup_users.clone(),
+            ..KeyPolicy::default()
+        };
+        if let Err(reason) = crate::policy::validate(&setup_policy) {
+            return invalid(format!("lock.setup_users: {}", reason));
+        }
+        Ok(())
+    }
+
+    /// Configured storage backend
+    ///
+    /// Keychain namespaces other than the default keep their state (key
+    /// index, lock, audit log) in a subdirectory named after the service,
+    /// so two namespaces never share an index.
+    pub fn storage_backend(&self) -> StorageBackend {
+        let dir = || self.storage.dir.clone().unwrap_or_else(default_store_dir);
+        match self.storage.backend {
+            BackendKind::Keychain => StorageBackend::Keychain {
+                service: self.storage.service.clone(),
+                state_dir: match &self.storage.dir {
+                    Some(dir) => dir.clone(),
+                    None if self.storage.service == DEFAULT_SERVICE => default_store_dir(),
+                    None => default_store_dir().join(&self.storage.service),
+                },
+            },
+            BackendKind::File => StorageBackend::File(dir()),
+            BackendKind::Memory => StorageBackend::Memory,
+        }
+    }
+
+    /// Permissions to set on the socket file, if it is one
+    pub fn socket_mode(&self) -> Option<u32> {
+        (cfg!(unix) && is_fs_socket(&self.socket.path)).then(|| self.socket.mode.unwrap_or(DEFAULT_SOCKET_MODE))
+    }
+
+    /// Auto-lock idle time; `None` when disabled
+    pub fn auto_lock(&self) -> Option<Duration> {
+        (self.lock.auto_lock_secs > 0).then(|| Duration::from_secs(self.lock.auto_lock_secs))
+    }
+
+    /// Send stdout and stderr to `log.file`, if set
+    ///
+    /// Call once at startup, before the runtime starts other threads.
+    pub fn redirect_logs(&self) -> Result<()> {
+        let Some(path) = &self.log.file else {
+            return Ok(());
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            use std::os::unix::io::AsRawFd;
+
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .mode(0o600)
+                .open(path)
+                .map_err(|e| VaultError::Config(format!("Failed to open log file {}: {}", path.display(), e)))?;
+            for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+                // SAFETY: both descriptors are valid; dup2 atomically replaces `fd`
+                if unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+                    return Err(VaultError::Io(std::io::Error::last_os_error()));
+                }
+            }
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        Err(VaultError::Config(format!("Cannot log to {} on this platform", path.display())))
+    }
+}
+
+/// Default config file location: `<config dir>/identra/vault.toml`
+pub fn default_config_path() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+    #[cfg(not(windows))]
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    base.map(|base| base.join("identra").join("vault.toml"))
+}
+
+/// Entries of a comma-separated list, trimmed
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',').map(|entry| entry.trim().to_string()).collect()
+}
+
+/// Socket names with a separator are filesystem paths (see [`identra_ipc::socket_name`])
+fn is_fs_socket(name: &str) -> bool {
+    name.contains(std::path::MAIN_SEPARATOR) || name.contains('/')
+}
+
+fn parse_mode(mode: &str) -> std::result::Result<u32, String> {
+    let digits = mode.trim_start_matches("0o");
+    u32::from_str_radix(digits, 8).map_err(|_| format!("{:?} is not an octal permission mode", mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn config_error(result: Result<impl std::fmt::Debug>) -> String {
+        match result {
+            Err(VaultError::Config(message)) => message,
+            other => panic!("expected a config error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_file_then_env_then_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.toml");
+        std::fs::write(
+            &path,
+            "[socket]\npath = \"/run/identra/vault.sock\"\nmode = 0o660\n\n\
+             [storage]\nbackend = \"file\"\ndir = \"/var/lib/identra\"\n\n[lock]\nauto_lock_secs = 60\n",
+        )
+        .unwrap();
+
+        let mut config = DaemonConfig::from_file(&path).unwrap();
+        assert_eq!(config.socket.mode, Some(0o660));
+        assert_eq!(config.storage.service, DEFAULT_SERVICE);
+
+        config
+            .apply_env(env(&[
+                (AUTO_LOCK_ENV, "0"),
+                (SERVICE_ENV, "work"),
+                (STORAGE_ENV, ""),
+                (IDENTITY_USERS_ENV, "exe:/opt/identra/ghost-desktop, uid:1000"),
+                (SETUP_USERS_ENV, "uid:1000"),
+            ]))
+            .unwrap();
+        config.apply_cli(&CliArgs {
+            backend: Some(BackendKind::Keychain),
+            setup_users: vec!["uid:0".to_string()],
+            ..CliArgs::default()
+        });
+        config.validate().unwrap();
+
+        assert_eq!(config.auto_lock(), None);
+        assert_eq!(config.lock.setup_users, Some(vec!["uid:0".to_string()]));
+        assert_eq!(
+            config.identity.users,
+            Some(vec!["exe:/opt/identra/ghost-desktop".to_string(), "uid:1000".to_string()])
+        );
+        assert_eq!(
+            config.storage_backend(),
+            StorageBackend::Keychain {
+                service: "work".to_string(),
+                state_dir: PathBuf::from("/var/lib/identra"),
+            }
+        );
+        #[cfg(unix)]
+        assert_eq!(config.socket_mode(), Some(0o660));
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = DaemonConfig::default();
+        config.validate().unwrap();
+
+        assert_eq!(config.socket.path, DEFAULT_SOCKET_NAME);
+        assert_eq!(config.socket_mode(), None);
+        assert_eq!(config.auto_lock(), Some(DEFAULT_AUTO_LOCK));
+        assert_eq!(config.identity.users.is_some(), cfg!(target_os = "linux"));
+        assert_eq!(config.lock.setup_users, None);
+        if KEYCHAIN_SUPPORTED {
+            assert!(matches!(config.storage_backend(), StorageBackend::Keychain { state_dir, .. } if state_dir == default_store_dir()));
+        } else {
+            assert_eq!(config.storage_backend(), StorageBackend::File(default_store_dir()));
+        }
+    }
+
+    #[test]
+    fn test_invalid_settings_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.toml");
+        std::fs::write(&path, "[storage]\nbackend = \"floppy\"\n").unwrap();
+        assert!(config_error(DaemonConfig::from_file(&path)).contains("floppy"));
+        std::fs::write(&path, "[socket]\npermissions = 0o600\n").unwrap();
+        assert!(config_error(DaemonConfig::from_file(&path)).contains("permissions"));
+
+        let mut config = DaemonConfig::default();
+        assert!(config_error(config.apply_env(env(&[(AUTO_LOCK_ENV, "soon")]))).contains(AUTO_LOCK_ENV));
+        assert!(config_error(config.apply_env(env(&[(SOCKET_MODE_ENV, "rw")]))).contains(SOCKET_MODE_ENV));
+
+        config.socket.mode = Some(0o660);
+        assert!(config_error(config.validate()).contains("filesystem socket"));
+
+        let mut config = DaemonConfig::default();
+        config.storage.backend = BackendKind::Memory;
+        config.storage.dir = Some(dir.path().to_path_buf());
+        assert!(config_error(config.validate()).contains("memory backend"));
+
+        let mut config = DaemonConfig::default();
+        config.identity.users = Some(vec!["ghost-desktop".to_string()]);
+        assert!(config_error(config.validate()).contains("identity.users"));
+
+        let mut config = DaemonConfig::default();
+        config.lock.setup_users = Some(vec!["admin".to_string()]);
+        assert!(config_error(config.validate()).contains("lock.setup_users"));
+    }
+}
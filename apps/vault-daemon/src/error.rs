@@ -5,6 +5,9 @@ pub enum VaultError {
     #[error("Keychain error: {0}")]
     Keychain(String),
     
+    #[error("Storage error: {0}")]
+    Storage(String),
+    
     #[error("Memory lock error: {0}")]
     MemoryLock(String),
     
@@ -14,6 +17,24 @@ pub enum VaultError {
     #[error("Encryption error: {0}")]
     Encryption(String),
     
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    
+    #[error("Vault is locked")]
+    Locked,
+    
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    
+    #[error("No passphrase set")]
+    NoPassphrase,
+    
+    #[error("Passphrase already set")]
+    PassphraseSet,
+    
+    #[error("Too many failed unlocks; retry in {0:?}")]
+    RetryLater(std::time::Duration),
+    
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     
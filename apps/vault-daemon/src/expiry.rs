@@ -0,0 +1,74 @@
+use crate::error::Result;
+use crate::keychain::KeyStorage;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the daemon deletes expired keys unless configured otherwise
+pub const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Delete every key whose `expires_at` is before `now`, returning their ids
+///
+/// A key that fails to delete is logged and left for the next sweep.
+pub fn sweep_expired(keychain: &dyn KeyStorage, now: i64) -> Result<Vec<String>> {
+    let mut reaped = Vec::new();
+    for key_id in keychain.list_keys()? {
+        let expired = match keychain.key_metadata(&key_id) {
+            Ok(metadata) => metadata.is_expired(now),
+            Err(e) => {
+                eprintln!("⚠️ Skipping {} during expiry sweep: {}", key_id, e);
+                continue;
+            }
+        };
+        if !expired {
+            continue;
+        }
+        match keychain.delete_key(&key_id) {
+            Ok(()) => reaped.push(key_id),
+            Err(e) => eprintln!("⚠️ Failed to delete expired key {}: {}", key_id, e),
+        }
+    }
+    Ok(reaped)
+}
+
+/// Sweep expired keys every `interval`, forever
+pub async fn run_reaper(keychain: Arc<Box<dyn KeyStorage>>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match sweep_expired(keychain.as_ref().as_ref(), chrono::Utc::now().timestamp()) {
+            Ok(reaped) if reaped.is_empty() => {}
+            Ok(reaped) => println!("⌛ Deleted {} expired key(s)", reaped.len()),
+            Err(e) => eprintln!("❌ Expiry sweep failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::{KeyMetadata, MemoryKeyStorage};
+    use std::collections::HashMap;
+
+    fn metadata(expires_at: Option<i64>) -> KeyMetadata {
+        KeyMetadata {
+            created_at: 0,
+            expires_at,
+            custom: HashMap::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sweep_deletes_only_expired_keys() {
+        let storage = MemoryKeyStorage::new();
+        storage.store_key("expired", b"k", metadata(Some(99))).unwrap();
+        storage.store_key("expires-now", b"k", metadata(Some(100))).unwrap();
+        storage.store_key("later", b"k", metadata(Some(101))).unwrap();
+        storage.store_key("forever", b"k", metadata(None)).unwrap();
+
+        assert_eq!(sweep_expired(&storage, 100).unwrap(), vec!["expired"]);
+        assert_eq!(storage.list_keys().unwrap(), vec!["expires-now", "forever", "later"]);
+        assert!(sweep_expired(&storage, 100).unwrap().is_empty());
+    }
+}
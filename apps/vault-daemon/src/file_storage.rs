@@ -0,0 +1,407 @@
+use crate::error::{Result, VaultError};
+use crate::keychain::{vault_key, KeyMetadata, KeyStorage};
+use crate::memory::SecureMemory;
+use base64::{engine::general_purpose, Engine as _};
+use identra_crypto::aead::{self, Nonce};
+use identra_crypto::{KeyDerivationParams, KeyFile, MemoryVault, SecretBytes};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Passphrase unlocking the keystore
+pub const PASSPHRASE_ENV: &str = "IDENTRA_VAULT_PASSPHRASE";
+
+/// File holding the keystore passphrase (e.g. a systemd credential), used if the variable is unset
+pub const PASSPHRASE_FILE_ENV: &str = "IDENTRA_VAULT_PASSPHRASE_FILE";
+
+/// Current keystore file format version
+const KEYSTORE_VERSION: u8 = 1;
+
+const KEYFILE_NAME: &str = "keyfile.json";
+const KEYSTORE_NAME: &str = "keystore.json";
+const LOCK_NAME: &str = "keystore.lock";
+
+/// Encrypted on-disk key storage for machines without an OS keychain
+///
+/// The directory holds a [`KeyFile`] wrapping a random master key under an
+/// Argon2id-derived key, and a keystore sealed as a whole with that master
+/// key, so neither key material nor key ids are readable at rest. Every
+/// write replaces the keystore atomically, and a lock file serializes access
+/// between processes.
+pub struct FileKeyStorage {
+    dir: PathBuf,
+    master: SecureMemory,
+}
+
+/// On-disk keystore: the sealed entry table
+#[derive(Serialize, Deserialize)]
+struct SealedKeystore {
+    version: u8,
+    /// Fingerprint of the master key that sealed `data`
+    key_id: String,
+    /// Base64 nonce
+    nonce: String,
+    /// Base64 ChaCha20-Poly1305 ciphertext of the JSON entry table
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    key: SecretBytes,
+    metadata: KeyMetadata,
+}
+
+type Entries = BTreeMap<String, Entry>;
+
+impl FileKeyStorage {
+    /// Open (or create) the keystore in `dir` with production KDF parameters
+    pub fn open(dir: impl Into<PathBuf>, passphrase: &[u8]) -> Result<Self> {
+        Self::open_with_params(dir, passphrase, KeyDerivationParams::secure())
+    }
+
+    /// Open (or create) the keystore; `params` only apply when creating it
+    pub fn open_with_params(
+        dir: impl Into<PathBuf>,
+        passphrase: &[u8],
+        params: KeyDerivationParams,
+    ) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        restrict_dir(&dir)?;
+
+        let _lock = StoreLock::exclusive(&dir)?;
+        let keyfile_path = dir.join(KEYFILE_NAME);
+        let master = if keyfile_path.exists() {
+            KeyFile::load(&keyfile_path)
+                .and_then(|key_file| key_file.unlock(passphrase))
+                .map_err(|e| VaultError::Storage(format!("Failed to unlock keystore: {}", e)))?
+        } else {
+            let (key_file, master) = KeyFile::create(passphrase, params)
+                .map_err(|e| VaultError::Storage(format!("Failed to create keystore: {}", e)))?;
+            key_file
+                .save(&keyfile_path)
+                .map_err(|e| VaultError::Storage(format!("Failed to save keyfile: {}", e)))?;
+            println!("🗄️ Created encrypted keystore in {}", dir.display());
+            master
+        };
+
+        Ok(Self {
+            dir,
+            master: SecureMemory::from_slice(master.as_bytes())?,
+        })
+    }
+
+    /// Open the keystore with the passphrase from the environment
+    pub fn open_from_env(dir: impl Into<PathBuf>) -> Result<Self> {
+        let passphrase = passphrase_from_env()?;
+        Self::open(dir, passphrase.as_bytes())
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn read_entries(&self) -> Result<Entries> {
+        let path = self.dir.join(KEYSTORE_NAME);
+        let json = match fs::read(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Entries::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let sealed: SealedKeystore = serde_json::from_slice(&json)?;
+        if sealed.version != KEYSTORE_VERSION {
+            return Err(VaultError::Storage(format!(
+                "Unsupported keystore version {}",
+                sealed.version
+            )));
+        }
+        let master = vault_key(&self.master)?;
+        if sealed.key_id != MemoryVault::key_id(&master) {
+            return Err(VaultError::Storage("Keystore was sealed with a different master key".to_string()));
+        }
+
+        let nonce = decode(&sealed.nonce)
+            .and_then(|nonce| Nonce::from_bytes(&nonce).map_err(encryption_error))?;
+        let ciphertext = decode(&sealed.data)?;
+        let plaintext = Zeroizing::new(
+            aead::decrypt_with_aad(&master, &nonce, &ciphertext, &keystore_aad(&sealed.key_id))
+                .map_err(encryption_error)?,
+        );
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    fn write_entries(&self, entries: &Entries) -> Result<()> {
+        let master = vault_key(&self.master)?;
+        let key_id = MemoryVault::key_id(&master);
+        let plaintext = Zeroizing::new(serde_json::to_vec(entries)?);
+        let nonce = Nonce::generate();
+        let ciphertext = aead::encrypt_with_aad(&master, &nonce, &plaintext, &keystore_aad(&key_id))
+            .map_err(encryption_error)?;
+
+        let sealed = SealedKeystore {
+            version: KEYSTORE_VERSION,
+            key_id,
+            nonce: general_purpose::STANDARD.encode(nonce.as_bytes()),
+            data: general_purpose::STANDARD.encode(ciphertext),
+        };
+        write_atomic(&self.dir.join(KEYSTORE_NAME), &serde_json::to_vec(&sealed)?)
+    }
+
+    /// Read-modify-write the entry table under the exclusive lock
+    fn update<T>(&self, change: impl FnOnce(&mut Entries) -> Result<T>) -> Result<T> {
+        let _lock = StoreLock::exclusive(&self.dir)?;
+        let mut entries = self.read_entries()?;
+        let result = change(&mut entries)?;
+        self.write_entries(&entries)?;
+        Ok(result)
+    }
+
+    fn read<T>(&self, view: impl FnOnce(Entries) -> Result<T>) -> Result<T> {
+        let _lock = StoreLock::shared(&self.dir)?;
+        view(self.read_entries()?)
+    }
+}
+
+impl KeyStorage for FileKeyStorage {
+    fn store_key(&self, key_id: &str, key: &[u8], metadata: KeyMetadata) -> Result<()> {
+        self.update(|entries| {
+            let entry = Entry {
+                key: SecretBytes::from_slice(key),
+                metadata,
+            };
+            entries.insert(key_id.to_string(), entry);
+            Ok(())
+        })
+    }
+
+    fn retrieve_key(&self, key_id: &str) -> Result<(SecretBytes, KeyMetadata)> {
+        self.read(|mut entries| {
+            entries
+                .remove(key_id)
+                .map(|entry| (entry.key, entry.metadata))
+                .ok_or_else(|| VaultError::Storage(format!("Key not found: {}", key_id)))
+        })
+    }
+
+    fn delete_key(&self, key_id: &str) -> Result<()> {
+        self.update(|entries| {
+            entries
+                .remove(key_id)
+                .map(|_| ())
+                .ok_or_else(|| VaultError::Storage(format!("Key not found: {}", key_id)))
+        })
+    }
+
+    fn key_exists(&self, key_id: &str) -> bool {
+        self.read(|entries| Ok(entries.contains_key(key_id)))
+            .unwrap_or(false)
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>> {
+        self.read(|entries| Ok(entries.into_keys().collect()))
+    }
+}
+
+/// Keystore passphrase from `IDENTRA_VAULT_PASSPHRASE` or `IDENTRA_VAULT_PASSPHRASE_FILE`
+pub fn passphrase_from_env() -> Result<Zeroizing<String>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+    if let Ok(path) = std::env::var(PASSPHRASE_FILE_ENV) {
+        let contents = Zeroizing::new(fs::read_to_string(&path)?);
+        return Ok(Zeroizing::new(contents.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    Err(VaultError::Storage(format!(
+        "File keystore needs a passphrase: set {} or {}",
+        PASSPHRASE_ENV, PASSPHRASE_FILE_ENV
+    )))
+}
+
+/// Advisory lock on the keystore directory, released on drop
+struct StoreLock(File);
+
+impl StoreLock {
+    fn exclusive(dir: &Path) -> Result<Self> {
+        let file = Self::open(dir)?;
+        file.lock()?;
+        Ok(Self(file))
+    }
+
+    fn shared(dir: &Path) -> Result<Self> {
+        let file = Self::open(dir)?;
+        file.lock_shared()?;
+        Ok(Self(file))
+    }
+
+    fn open(dir: &Path) -> Result<File> {
+        Ok(OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(LOCK_NAME))?)
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+/// Write next to the target then rename, so a crash never leaves a half-written keystore
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+
+    // Persist the rename itself
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Keep the keystore directory private to the daemon's user
+pub(crate) fn restrict_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Associated data binding the keystore ciphertext to its master key
+fn keystore_aad(key_id: &str) -> Vec<u8> {
+    [b"identra-keystore-v1:".as_slice(), key_id.as_bytes()].concat()
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| VaultError::Storage(format!("Corrupt keystore: {}", e)))
+}
+
+fn encryption_error(e: identra_crypto::CryptoError) -> VaultError {
+    VaultError::Encryption(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn metadata(purpose: &str) -> KeyMetadata {
+        KeyMetadata {
+            created_at: 1_700_000_000,
+            expires_at: None,
+            custom: HashMap::from([("purpose".to_string(), purpose.to_string())]),
+            ..Default::default()
+        }
+    }
+
+    fn open(dir: &Path, passphrase: &[u8]) -> Result<FileKeyStorage> {
+        FileKeyStorage::open_with_params(dir, passphrase, KeyDerivationParams::fast())
+    }
+
+    #[test]
+    fn test_store_retrieve_list_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open(dir.path(), b"passphrase").unwrap();
+
+        storage.store_key("b-key", b"second", metadata("b")).unwrap();
+        storage.store_key("a-key", b"first", metadata("a")).unwrap();
+
+        let (key, meta) = storage.retrieve_key("a-key").unwrap();
+        assert_eq!(key.as_bytes(), b"first");
+        assert_eq!(meta.custom["purpose"], "a");
+        assert_eq!(storage.list_keys().unwrap(), vec!["a-key", "b-key"]);
+
+        storage.delete_key("a-key").unwrap();
+        assert!(!storage.key_exists("a-key"));
+        assert!(storage.key_exists("b-key"));
+        assert!(storage.delete_key("a-key").is_err());
+        assert!(storage.retrieve_key("a-key").is_err());
+    }
+
+    #[test]
+    fn test_keys_persist_and_need_the_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        open(dir.path(), b"passphrase").unwrap()
+            .store_key("k", b"persisted", metadata("p")).unwrap();
+
+        let reopened = open(dir.path(), b"passphrase").unwrap();
+        assert_eq!(reopened.retrieve_key("k").unwrap().0.as_bytes(), b"persisted");
+        assert!(open(dir.path(), b"wrong").is_err());
+    }
+
+    #[test]
+    fn test_nothing_readable_at_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open(dir.path(), b"passphrase").unwrap();
+        storage.store_key("visible-key-name", b"plaintext-secret", metadata("hidden-purpose")).unwrap();
+
+        let on_disk = fs::read_to_string(dir.path().join(KEYSTORE_NAME)).unwrap();
+        assert!(!on_disk.contains("visible-key-name"));
+        assert!(!on_disk.contains("hidden-purpose"));
+        assert!(!dir.path().join("keystore.tmp").exists());
+    }
+
+    #[test]
+    fn test_tampered_keystore_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open(dir.path(), b"passphrase").unwrap();
+        storage.store_key("k", b"secret", metadata("p")).unwrap();
+
+        let path = dir.path().join(KEYSTORE_NAME);
+        let mut sealed: SealedKeystore = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let mut data = general_purpose::STANDARD.decode(&sealed.data).unwrap();
+        data[0] ^= 1;
+        sealed.data = general_purpose::STANDARD.encode(data);
+        fs::write(&path, serde_json::to_vec(&sealed).unwrap()).unwrap();
+
+        assert!(storage.list_keys().is_err());
+        assert!(!storage.key_exists("k"));
+    }
+
+    #[test]
+    fn test_concurrent_writers_do_not_lose_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        open(dir.path(), b"passphrase").unwrap();
+
+        // Separate handles, as separate processes would have
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let path = dir.path().to_path_buf();
+                std::thread::spawn(move || {
+                    let storage = open(&path, b"passphrase").unwrap();
+                    storage.store_key(&format!("key-{}", i), b"v", metadata("t")).unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(open(dir.path(), b"passphrase").unwrap().list_keys().unwrap().len(), 8);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::error::{Result, VaultError};
+use crate::keychain::{KeyMetadata, KeyStorage};
+use crate::policy::EXE_PREFIX;
+use identra_crypto::{IdentityKeyPair, IdentityPublicKey};
+use std::collections::HashMap;
+
+/// Key id under which the user's X25519 identity private key is stored
+pub const IDENTITY_KEY_ID: &str = "identra-identity-x25519";
+
+/// The desktop app, which is installed next to the daemon
+pub const DESKTOP_EXE_NAME: &str = "ghost-desktop";
+
+/// Metadata field holding the base64 public key, so it can be read without the secret
+const PUBLIC_KEY_METADATA: &str = "public_key";
+
+/// Load the user's identity keypair, generating and storing one on first use
+pub fn load_or_create_identity(keychain: &dyn KeyStorage) -> Result<IdentityKeyPair> {
+    if keychain.key_exists(IDENTITY_KEY_ID) {
+        let (secret, _) = keychain.retrieve_key(IDENTITY_KEY_ID)?;
+        return IdentityKeyPair::from_secret_bytes(secret.as_bytes())
+            .map_err(|e| VaultError::Encryption(format!("Stored identity key is invalid: {}", e)));
+    }
+
+    let identity = IdentityKeyPair::generate();
+    let mut custom = HashMap::new();
+    custom.insert(PUBLIC_KEY_METADATA.to_string(), identity.public_key().to_base64());
+    let metadata = KeyMetadata {
+        created_at: chrono::Utc::now().timestamp(),
+        expires_at: None,
+        custom,
+        ..Default::default()
+    };
+    keychain.store_key(IDENTITY_KEY_ID, identity.secret_key().as_bytes(), metadata)?;
+    println!("🪪 Generated identity key {}", identity.public_key().fingerprint());
+    Ok(identity)
+}
+
+/// Let only `users` (`None`: any client) open sealed boxes, creating the identity if needed
+///
+/// Run on every start, so the stored policy always follows the config.
+pub fn apply_policy(keychain: &dyn KeyStorage, users: Option<&[String]>) -> Result<()> {
+    load_or_create_identity(keychain)?;
+    let (secret, mut metadata) = keychain.retrieve_key(IDENTITY_KEY_ID)?;
+    if metadata.policy.users.as_deref() == users {
+        return Ok(());
+    }
+    metadata.policy.users = users.map(<[String]>::to_vec);
+    keychain.store_key(IDENTITY_KEY_ID, secret.as_bytes(), metadata)?;
+    match users {
+        Some(users) => println!("🪪 Identity key usable by: {}", users.join(", ")),
+        None => println!("🪪 Identity key usable by any client"),
+    }
+    Ok(())
+}
+
+/// Policy entry naming the desktop app installed next to this daemon's executable
+///
+/// `None` where the daemon can't see which executable is calling (see
+/// [`crate::peer::PeerInfo`]), as such an entry would match no one.
+pub fn desktop_principal() -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let exe = std::env::current_exe().ok()?;
+    let desktop = exe.parent()?.join(format!("{}{}", DESKTOP_EXE_NAME, std::env::consts::EXE_SUFFIX));
+    Some(format!("{}{}", EXE_PREFIX, desktop.display()))
+}
+
+/// Open a sealed box (e.g. a shared memory's DEK) addressed to this user
+pub fn open_sealed(keychain: &dyn KeyStorage, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let identity = load_or_create_identity(keychain)?;
+    identity
+        .open(sealed, aad)
+        .map_err(|e| VaultError::Encryption(format!("Failed to open sealed box: {}", e)))
+}
+
+/// The user's public identity key
+pub fn public_key(keychain: &dyn KeyStorage) -> Result<IdentityPublicKey> {
+    Ok(*load_or_create_identity(keychain)?.public_key())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::MemoryKeyStorage;
+
+    #[test]
+    fn test_identity_is_created_once() {
+        let storage = MemoryKeyStorage::new();
+        let first = public_key(&storage).unwrap();
+        let second = public_key(&storage).unwrap();
+
+        assert_eq!(first, second);
+        let (_, metadata) = storage.retrieve_key(IDENTITY_KEY_ID).unwrap();
+        assert_eq!(metadata.custom[PUBLIC_KEY_METADATA], first.to_base64());
+    }
+
+    #[test]
+    fn test_open_sealed_for_stored_identity() {
+        let storage = MemoryKeyStorage::new();
+        let recipient = public_key(&storage).unwrap();
+        let sealed = identra_crypto::seal(&recipient, b"shared dek", b"memory-7").unwrap();
+
+        assert_eq!(open_sealed(&storage, &sealed, b"memory-7").unwrap(), b"shared dek");
+        assert!(open_sealed(&storage, &sealed, b"memory-8").is_err());
+    }
+
+    #[test]
+    fn test_policy_follows_config() {
+        let storage = MemoryKeyStorage::new();
+        let desktop = vec!["exe:/opt/identra/ghost-desktop".to_string()];
+        apply_policy(&storage, Some(&desktop)).unwrap();
+        let created = public_key(&storage).unwrap();
+        assert_eq!(storage.key_metadata(IDENTITY_KEY_ID).unwrap().policy.users, Some(desktop));
+
+        let users = vec!["uid:1000".to_string()];
+        apply_policy(&storage, Some(&users)).unwrap();
+        assert_eq!(storage.key_metadata(IDENTITY_KEY_ID).unwrap().policy.users, Some(users));
+        apply_policy(&storage, None).unwrap();
+        assert_eq!(storage.key_metadata(IDENTITY_KEY_ID).unwrap().policy.users, None);
+        assert_eq!(public_key(&storage).unwrap(), created);
+    }
+}
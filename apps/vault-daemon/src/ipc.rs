@@ -1,58 +1,55 @@
+use crate::backup;
+use crate::config::{CliArgs, DaemonConfig};
+use crate::audit::{AuditEvent, AuditLog, AUDIT_LOG_NAME};
 use crate::error::{Result, VaultError};
-use crate::keychain::{KeyStorage, create_key_storage};
-use serde::{Deserialize, Serialize};
+use crate::expiry::{run_reaper, DEFAULT_SWEEP_INTERVAL};
+use crate::identity::{self, IDENTITY_KEY_ID};
+use crate::keychain::{is_internal_key, KeyMetadata, KeyStorage, create_key_storage};
+use crate::lock::{run_auto_lock, watch_session_events, VaultLock, LOCK_FILE_NAME};
+use crate::memory::SecureMemory;
+use crate::operations;
+use crate::peer::{PeerInfo, PeerPolicy};
+use crate::policy::{self, Access, Caller, Denied};
+use crate::shutdown::{Shutdown, DEFAULT_DRAIN_TIMEOUT};
+use base64::{engine::general_purpose, Engine as _};
+use identra_crypto::SecretBytes;
+use identra_ipc::{
+    accept_handshake, read_frame, socket_name, token::{load_or_create_token, TokenStore},
+    write_frame, ErrorCode, IpcError, KeyAlgorithm, KeyPolicy, StoredKey, VaultRequest, VaultResponse, VaultStatus,
+    ALGORITHM_METADATA, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tokio::task::JoinSet;
 use interprocess::local_socket::{
-    tokio::prelude::*,
-    GenericNamespaced, ListenerOptions, ToNsName,
+    tokio::{prelude::*, Listener, Stream},
+    ListenerOptions,
 };
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
-/// IPC pipe name
-#[cfg(windows)]
-const PIPE_NAME: &str = "@identra-vault";
-
-#[cfg(unix)]
-const PIPE_NAME: &str = "/tmp/identra-vault.sock";
-
-/// IPC message types
-#[derive(Debug, Serialize, Deserialize)]
-pub enum VaultRequest {
-    StoreKey { 
-        key_id: String, 
-        key_data: Vec<u8>,
-        metadata: std::collections::HashMap<String, String>,
-        expires_at: Option<i64>, // Unix timestamp
-    },
-    RetrieveKey { key_id: String },
-    DeleteKey { key_id: String },
-    KeyExists { key_id: String },
-    ListKeys,
-    Ping,
-    Shutdown,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub enum VaultResponse {
-    Success,
-    KeyData {
-        key_data: Vec<u8>,
-        metadata: std::collections::HashMap<String, String>,
-        created_at: i64,
-        expires_at: Option<i64>,
-    },
-    KeyList(Vec<String>),
-    Exists(bool),
-    Error(String),
-    Pong,
-    ShuttingDown,
-}
+/// Daemon name reported in the handshake
+const SERVER_NAME: &str = concat!("identra-vault-daemon/", env!("CARGO_PKG_VERSION"));
 
 /// Vault server handling IPC communication
 pub struct VaultServer {
     keychain: Arc<Box<dyn KeyStorage>>,
     state: Arc<RwLock<VaultState>>,
+    socket: String,
+    policy: Arc<PeerPolicy>,
+    /// Install token; loaded from (or created in) `token_store` on start if unset
+    token: Option<Arc<SecretBytes>>,
+    token_store: TokenStore,
+    /// How often expired keys are deleted
+    sweep_interval: Duration,
+    lock: Arc<VaultLock>,
+    audit: Arc<AuditLog>,
+    /// Permissions for a filesystem socket, applied once it is bound
+    socket_mode: Option<u32>,
+    shutdown: Shutdown,
+    /// How long open connections get to finish their requests on shutdown
+    drain_timeout: Duration,
 }
 
 struct VaultState {
@@ -61,29 +58,119 @@ struct VaultState {
 }
 
 impl VaultServer {
-    pub fn new() -> Self {
-        let keychain = create_key_storage();
-        
+    /// Server configured from the default config file and the environment
+    pub fn new() -> Result<Self> {
+        Self::from_config(&DaemonConfig::load(&CliArgs::default())?)
+    }
+
+    /// Server for a loaded config (storage backend, socket, auto-lock); peer policy from the environment
+    pub fn from_config(config: &DaemonConfig) -> Result<Self> {
+        let backend = config.storage_backend();
+        let storage = create_key_storage(&backend)?;
+        let lock = VaultLock::open(backend.state_dir().map(|dir| dir.join(LOCK_FILE_NAME)))?
+            .with_auto_lock(config.auto_lock())
+            .with_setup_users(config.lock.setup_users.clone());
+        let audit = AuditLog::open(storage.as_ref(), backend.state_dir().map(|dir| dir.join(AUDIT_LOG_NAME)))?;
+        identity::apply_policy(storage.as_ref(), config.identity.users.as_deref())?;
+        println!("🔑 Key storage: {:?}", backend);
+        let mut server = Self::with_storage(storage, config.socket.path.clone())
+            .with_policy(PeerPolicy::from_env())
+            .with_lock(lock)
+            .with_audit(audit)
+            .with_token_store(backend.token_store());
+        server.socket_mode = config.socket_mode();
+        Ok(server)
+    }
+
+    /// Serve `keychain` on a specific socket (used by tests and custom setups)
+    pub fn with_storage(keychain: Box<dyn KeyStorage>, socket: impl Into<String>) -> Self {
         Self {
             keychain: Arc::new(keychain),
             state: Arc::new(RwLock::new(VaultState {
                 initialized: false,
                 active_connections: 0,
             })),
+            socket: socket.into(),
+            policy: Arc::new(PeerPolicy::default()),
+            token: None,
+            token_store: TokenStore::default(),
+            sweep_interval: DEFAULT_SWEEP_INTERVAL,
+            lock: Arc::new(VaultLock::in_memory()),
+            audit: Arc::new(AuditLog::in_memory()),
+            socket_mode: None,
+            shutdown: Shutdown::new(),
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
         }
     }
-    
+
+    /// Restrict which local processes may connect
+    pub fn with_policy(mut self, policy: PeerPolicy) -> Self {
+        self.policy = Arc::new(policy);
+        self
+    }
+
+    /// Use an explicit install token instead of the keyring's
+    pub fn with_token(mut self, token: SecretBytes) -> Self {
+        self.token = Some(Arc::new(token));
+        self
+    }
+
+    /// Load (or create) the install token from `store` when none is given explicitly
+    pub fn with_token_store(mut self, store: TokenStore) -> Self {
+        self.token_store = store;
+        self
+    }
+
+    /// Use `lock` for the lock state (passphrase verifier, auto-lock)
+    pub fn with_lock(mut self, lock: VaultLock) -> Self {
+        self.lock = Arc::new(lock);
+        self
+    }
+
+    /// Record requests in `audit` instead of an in-memory log
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Arc::new(audit);
+        self
+    }
+
+    /// Change how often expired keys are swept
+    pub fn with_sweep_interval(mut self, interval: Duration) -> Self {
+        self.sweep_interval = interval;
+        self
+    }
+
+    /// Change how long connections may keep working once shutdown starts
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Handle that stops [`start`](Self::start) from another task, e.g. on a signal
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Serve until shutdown is triggered, then drain open connections
+    ///
+    /// Shutdown comes from a `Shutdown` request or a [`shutdown_handle`](Self::shutdown_handle).
+    /// Idle connections close at once; requests in flight get up to the drain
+    /// timeout to finish. The socket file is removed before returning.
     pub async fn start(&self) -> Result<()> {
-        println!("🔌 Starting IPC server on: {}", PIPE_NAME);
+        println!("🔌 Starting IPC server on: {}", self.socket);
+
+        let token = match &self.token {
+            Some(token) => Arc::clone(token),
+            None => Arc::new(
+                load_or_create_token(&self.token_store)
+                    .map_err(|e| VaultError::Ipc(format!("Failed to load IPC token: {}", e)))?,
+            ),
+        };
         
         // Create listener
-        let name = PIPE_NAME.to_ns_name::<GenericNamespaced>()
-            .map_err(|e| VaultError::Ipc(format!("Invalid pipe name: {}", e)))?;
-        
-        let listener = ListenerOptions::new()
-            .name(name)
-            .create_tokio()
-            .map_err(|e| VaultError::Ipc(format!("Failed to create IPC listener: {}", e)))?;
+        let listener = self.bind().await?;
+        if let Some(mode) = self.socket_mode {
+            set_socket_mode(&self.socket, mode)?;
+        }
         
         {
             let mut state = self.state.write().await;
@@ -91,11 +178,24 @@ impl VaultServer {
         }
         
         println!("✅ IPC server ready, waiting for connections...");
+
+        // Stops with the accept loop
+        let reaper = tokio::spawn(run_reaper(Arc::clone(&self.keychain), self.sweep_interval));
+        let _reaper = AbortOnDrop(reaper);
+        let _auto_lock = AbortOnDrop(tokio::spawn(run_auto_lock(Arc::clone(&self.lock))));
+        let _session_events = AbortOnDrop(tokio::spawn(watch_session_events(Arc::clone(&self.lock))));
         
-        // Accept connections in a loop
+        // Accept connections until shutdown
+        let mut connections = JoinSet::new();
         loop {
-            match listener.accept().await {
-                Ok(stream) => {
+            let accepted = tokio::select! {
+                _ = self.shutdown.triggered() => break,
+                // Reap finished connections so the set doesn't grow
+                Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+                accepted = listener.accept() => accepted,
+            };
+            match accepted {
+                Ok(mut stream) => {
                     println!("📥 New IPC connection accepted");
                     
                     // Increment connection counter
@@ -107,161 +207,488 @@ impl VaultServer {
                     // Handle connection in a separate task
                     let keychain = Arc::clone(&self.keychain);
                     let state = Arc::clone(&self.state);
+                    let policy = Arc::clone(&self.policy);
+                    let token = Arc::clone(&token);
+                    let lock = Arc::clone(&self.lock);
+                    let audit = Arc::clone(&self.audit);
+                    let shutdown = self.shutdown.clone();
                     
-                    tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(stream, keychain, state.clone()).await {
+                    connections.spawn(async move {
+                        let result =
+                            Self::serve_connection(&mut stream, &keychain, &policy, &token, &lock, &audit, &shutdown).await;
+                        
+                        // Decrement connection counter
+                        {
+                            let mut state_guard = state.write().await;
+                            state_guard.active_connections = state_guard.active_connections.saturating_sub(1);
+                        }
+                        
+                        if let Err(e) = result {
                             eprintln!("❌ Connection error: {}", e);
                         }
                     });
                 }
                 Err(e) => {
                     eprintln!("❌ Failed to accept connection: {}", e);
+                    self.shutdown.trigger();
                     break;
                 }
             }
         }
+
+        // New clients fail fast instead of queueing on a daemon that's going away
+        drop(listener);
+        println!("🛑 Server shutting down, draining {} connection(s)...", connections.len());
+        let drained = tokio::time::timeout(self.drain_timeout, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            eprintln!(
+                "⚠️ Drain timeout passed; aborting {} connection(s)",
+                connections.len()
+            );
+            connections.shutdown().await;
+        }
+        println!("👋 IPC server stopped");
         
         Ok(())
     }
-    
-    async fn handle_connection(
-        stream: interprocess::local_socket::tokio::Stream,
-        keychain: Arc<Box<dyn KeyStorage>>,
-        state: Arc<RwLock<VaultState>>,
+
+    /// Bind the socket, replacing a stale socket file but never a live daemon
+    async fn bind(&self) -> Result<Listener> {
+        let options = || -> Result<ListenerOptions<'static>> {
+            let name = socket_name(&self.socket)
+                .map_err(|e| VaultError::Ipc(format!("Invalid socket name: {}", e)))?;
+            // Removes the socket file when the listener is dropped
+            Ok(ListenerOptions::new().name(name).reclaim_name(true))
+        };
+
+        match options()?.create_tokio() {
+            Ok(listener) => Ok(listener),
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                let name = socket_name(&self.socket).map_err(|e| VaultError::Ipc(e.to_string()))?;
+                if Stream::connect(name).await.is_ok() {
+                    return Err(VaultError::Ipc(format!(
+                        "Another vault daemon is already listening on {}",
+                        self.socket
+                    )));
+                }
+                println!("🧹 Replacing stale socket {}", self.socket);
+                options()?
+                    .try_overwrite(true)
+                    .create_tokio()
+                    .map_err(|e| VaultError::Ipc(format!("Failed to create IPC listener: {}", e)))
+            }
+            Err(e) => Err(VaultError::Ipc(format!("Failed to create IPC listener: {}", e))),
+        }
+    }
+
+    async fn serve_connection(
+        stream: &mut Stream,
+        keychain: &Arc<Box<dyn KeyStorage>>,
+        policy: &PeerPolicy,
+        token: &SecretBytes,
+        lock: &Arc<VaultLock>,
+        audit: &AuditLog,
+        shutdown: &Shutdown,
     ) -> Result<()> {
-        let (reader, mut writer) = tokio::io::split(stream);
-        let mut buf_reader = BufReader::new(reader);
-        let mut line = String::new();
+        // Who is calling, before anything else
+        let peer = PeerInfo::of(stream).unwrap_or_default();
+        if let Err(reason) = policy.check(&peer) {
+            println!("🚫 Rejected connection from {:?}: {}", peer, reason);
+            record(audit, &AuditEvent::peer(&peer, "", "connect", None, ErrorCode::PermissionDenied.as_str()));
+            let response = VaultResponse::error(ErrorCode::PermissionDenied, reason);
+            write_frame(stream, &response).await.map_err(ipc_error)?;
+            return Ok(());
+        }
+
+        let hello = match accept_handshake(stream, SERVER_NAME, token.as_bytes()).await {
+            Ok(hello) => hello,
+            Err(e) => {
+                let outcome = match &e {
+                    IpcError::VersionMismatch { .. } => Some(ErrorCode::UnsupportedVersion),
+                    IpcError::Token(_) => Some(ErrorCode::Unauthorized),
+                    _ => None,
+                };
+                if let Some(code) = outcome {
+                    record(audit, &AuditEvent::peer(&peer, "", "connect", None, code.as_str()));
+                }
+                return Err(ipc_error(e));
+            }
+        };
+        println!("🤝 Client connected: {} (pid {:?}, uid {:?})", hello.client, peer.pid, peer.uid);
+        let caller = Caller::new(&peer);
         
         loop {
-            line.clear();
-            
-            match buf_reader.read_line(&mut line).await {
-                Ok(0) => {
-                    // Connection closed
-                    println!("📤 Client disconnected");
-                    break;
+            // Between requests is the only place a connection is cut short by shutdown
+            let frame = tokio::select! {
+                biased;
+                _ = shutdown.triggered() => {
+                    println!("📤 Closing idle connection for shutdown");
+                    return Ok(());
                 }
-                Ok(_) => {
-                    // Parse request
-                    let request: VaultRequest = match serde_json::from_str(&line) {
-                        Ok(req) => req,
-                        Err(e) => {
-                            let error_response = VaultResponse::Error(
-                                format!("Invalid request format: {}", e)
-                            );
-                            let response_json = serde_json::to_string(&error_response).unwrap();
-                            writer.write_all(response_json.as_bytes()).await
-                                .map_err(|e| VaultError::Io(e))?;
-                            writer.write_all(b"\n").await
-                                .map_err(|e| VaultError::Io(e))?;
-                            writer.flush().await
-                                .map_err(|e| VaultError::Io(e))?;
-                            continue;
-                        }
-                    };
-                    
-                    // Handle request
-                    let response = Self::handle_request(request, &keychain).await;
-                    
-                    // Send response
-                    let response_json = serde_json::to_string(&response)
-                        .map_err(|e| VaultError::Serialization(e))?;
-                    
-                    writer.write_all(response_json.as_bytes()).await
-                        .map_err(|e| VaultError::Io(e))?;
-                    writer.write_all(b"\n").await
-                        .map_err(|e| VaultError::Io(e))?;
-                    writer.flush().await
-                        .map_err(|e| VaultError::Io(e))?;
-                    
-                    // Check for shutdown
-                    if matches!(response, VaultResponse::ShuttingDown) {
-                        break;
-                    }
+                frame = read_frame::<_, VaultRequest>(stream) => frame,
+            };
+            let request = match frame {
+                Ok(Some(request)) => request,
+                Ok(None) => {
+                    println!("📤 Client disconnected");
+                    return Ok(());
                 }
-                Err(e) => {
-                    eprintln!("❌ Read error: {}", e);
-                    break;
+                Err(IpcError::Decode(e)) => {
+                    // The bad frame was consumed, so the connection is still usable
+                    let response = VaultResponse::error(
+                        ErrorCode::InvalidRequest,
+                        format!("Invalid request format: {}", e),
+                    );
+                    write_frame(stream, &response).await.map_err(ipc_error)?;
+                    continue;
                 }
+                Err(e) => return Err(ipc_error(e)),
+            };
+            
+            // Handle request
+            let operation = operation_name(&request);
+            let key_id = key_id_of(&request).map(str::to_string);
+            let response = Self::handle_request(request, &caller, keychain, lock, audit).await;
+            if operation != "ping" {
+                let outcome = match &response {
+                    VaultResponse::Error { code, .. } => code.as_str(),
+                    _ => "ok",
+                };
+                record(audit, &AuditEvent::peer(&peer, &hello.client, operation, key_id.as_deref(), outcome));
+            }
+            write_frame(stream, &response).await.map_err(ipc_error)?;
+            
+            // Check for shutdown
+            if matches!(response, VaultResponse::ShuttingDown) {
+                shutdown.trigger();
+                return Ok(());
             }
         }
-        
-        // Decrement connection counter
-        {
-            let mut state_guard = state.write().await;
-            state_guard.active_connections = state_guard.active_connections.saturating_sub(1);
-        }
-        
-        Ok(())
     }
     
     async fn handle_request(
         request: VaultRequest,
+        caller: &Caller,
         keychain: &Arc<Box<dyn KeyStorage>>,
+        lock: &Arc<VaultLock>,
+        audit: &AuditLog,
     ) -> VaultResponse {
+        if requires_unlock(&request) && lock.touch().is_err() {
+            return VaultResponse::error(ErrorCode::Locked, "Vault is locked");
+        }
+        // The daemon's own keys (index, audit, identity) are never handed to clients
+        let wrapping_key_id = match &request {
+            VaultRequest::WrapKey { wrapping_key_id, .. } | VaultRequest::UnwrapKey { wrapping_key_id, .. } => {
+                Some(wrapping_key_id.as_str())
+            }
+            _ => None,
+        };
+        // OpenSealed's key id is the identity itself, not one the client chose
+        let client_key_id = key_id_of(&request).filter(|_| !matches!(request, VaultRequest::OpenSealed { .. }));
+        if let Some(key_id) = client_key_id.into_iter().chain(wrapping_key_id).find(|id| is_internal_key(id)) {
+            return VaultResponse::error(ErrorCode::PermissionDenied, format!("Reserved key id: {}", key_id));
+        }
+
         match request {
             VaultRequest::Ping => {
                 println!("🏓 Ping received");
                 VaultResponse::Pong
             }
-            VaultRequest::StoreKey { key_id, key_data, metadata, expires_at } => {
+            VaultRequest::StoreKey { key_id, key_data, metadata, expires_at, policy } => {
                 println!("📝 Storing key: {}", key_id);
+                if let Err(e) = policy::validate(&policy) {
+                    return VaultResponse::error(ErrorCode::InvalidRequest, e);
+                }
+                if let Err(refusal) = check_overwrite(keychain, &key_id, caller, lock) {
+                    return refusal.into();
+                }
                 
-                let key_metadata = crate::keychain::KeyMetadata {
+                let key_metadata = KeyMetadata {
                     created_at: chrono::Utc::now().timestamp(),
                     expires_at,
                     custom: metadata,
+                    policy,
+                    uses: 0,
                 };
                 
-                match keychain.store_key(&key_id, &key_data, key_metadata) {
+                match keychain.store_key(&key_id, key_data.as_bytes(), key_metadata) {
                     Ok(_) => VaultResponse::Success,
-                    Err(e) => VaultResponse::Error(format!("Failed to store key: {}", e)),
+                    Err(e) => VaultResponse::error(ErrorCode::Storage, format!("Failed to store key: {}", e)),
+                }
+            }
+            VaultRequest::GenerateKey { key_id, algorithm, exportable } => {
+                println!("🎲 Generating {} key: {}", algorithm.as_str(), key_id);
+                if let Err(refusal) = check_overwrite(keychain, &key_id, caller, lock) {
+                    return refusal.into();
+                }
+
+                let (key, public_key) = match operations::generate(algorithm) {
+                    Ok(generated) => generated,
+                    Err(e) => return VaultResponse::error(ErrorCode::Internal, format!("Failed to generate key: {}", e)),
+                };
+                let key_metadata = KeyMetadata {
+                    created_at: chrono::Utc::now().timestamp(),
+                    expires_at: None,
+                    custom: HashMap::from([(ALGORITHM_METADATA.to_string(), algorithm.as_str().to_string())]),
+                    policy: KeyPolicy {
+                        exportable,
+                        ..KeyPolicy::default()
+                    },
+                    uses: 0,
+                };
+                match keychain.store_key(&key_id, key.as_slice(), key_metadata) {
+                    Ok(_) => VaultResponse::GeneratedKey {
+                        public_key: public_key.map(|public_key| general_purpose::STANDARD.encode(public_key)),
+                    },
+                    Err(e) => VaultResponse::error(ErrorCode::Storage, format!("Failed to store key: {}", e)),
                 }
             }
             VaultRequest::RetrieveKey { key_id } => {
                 println!("🔍 Retrieving key: {}", key_id);
-                match keychain.retrieve_key(&key_id) {
-                    Ok((key_data, metadata)) => {
-                        // Check expiration
-                        if let Some(expires_at) = metadata.expires_at {
-                            let now = chrono::Utc::now().timestamp();
-                            if now > expires_at {
-                                return VaultResponse::Error("Key has expired".to_string());
-                            }
-                        }
-                        
-                        VaultResponse::KeyData {
-                            key_data,
-                            metadata: metadata.custom,
-                            created_at: metadata.created_at,
-                            expires_at: metadata.expires_at,
-                        }
-                    }
-                    Err(e) => VaultResponse::Error(format!("Failed to retrieve key: {}", e)),
+                match load_key(keychain, &key_id, caller, Access::Read, lock) {
+                    Ok((key, metadata)) => VaultResponse::KeyData(StoredKey {
+                        key_data: SecretBytes::from_slice(key.as_slice()),
+                        metadata: metadata.custom,
+                        created_at: metadata.created_at,
+                        expires_at: metadata.expires_at,
+                        policy: metadata.policy,
+                        uses: metadata.uses,
+                    }),
+                    Err(refusal) => refusal.into(),
                 }
             }
             VaultRequest::DeleteKey { key_id } => {
                 println!("🗑️ Deleting key: {}", key_id);
+                if !keychain.key_exists(&key_id) {
+                    return VaultResponse::error(ErrorCode::NotFound, format!("Key not found: {}", key_id));
+                }
+                if let Err(refusal) = check_overwrite(keychain, &key_id, caller, lock) {
+                    return refusal.into();
+                }
                 match keychain.delete_key(&key_id) {
                     Ok(_) => VaultResponse::Success,
-                    Err(e) => VaultResponse::Error(format!("Failed to delete key: {}", e)),
+                    Err(e) => VaultResponse::error(ErrorCode::Storage, format!("Failed to delete key: {}", e)),
                 }
             }
             VaultRequest::KeyExists { key_id } => {
-                let exists = keychain.key_exists(&key_id);
+                let now = chrono::Utc::now().timestamp();
+                let exists = keychain.key_exists(&key_id)
+                    && keychain
+                        .key_metadata(&key_id)
+                        .is_ok_and(|metadata| !metadata.is_expired(now));
                 println!("🔎 Key exists: {} = {}", key_id, exists);
                 VaultResponse::Exists(exists)
             }
-            VaultRequest::ListKeys => {
+            VaultRequest::ListKeys { page_size, page_token, prefix } => {
                 println!("📋 Listing keys");
+                let after = match page_token.as_deref().map(decode_page_token).transpose() {
+                    Ok(after) => after,
+                    Err(message) => return VaultResponse::error(ErrorCode::InvalidRequest, message),
+                };
                 match keychain.list_keys() {
-                    Ok(keys) => VaultResponse::KeyList(keys),
-                    Err(e) => VaultResponse::Error(format!("Failed to list keys: {}", e)),
+                    Ok(mut keys) => {
+                        keys.retain(|key_id| {
+                            !is_internal_key(key_id) && prefix.as_deref().is_none_or(|prefix| key_id.starts_with(prefix))
+                        });
+                        list_page(keys, page_size, after.as_deref())
+                    }
+                    Err(e) => VaultResponse::error(ErrorCode::Storage, format!("Failed to list keys: {}", e)),
                 }
             }
+            VaultRequest::GetIdentity => {
+                println!("🪪 Identity requested");
+                match identity::public_key(keychain.as_ref().as_ref()) {
+                    Ok(public_key) => VaultResponse::Identity {
+                        public_key: public_key.to_base64(),
+                        fingerprint: public_key.fingerprint(),
+                    },
+                    Err(e) => VaultResponse::error(ErrorCode::Storage, format!("Failed to load identity: {}", e)),
+                }
+            }
+            VaultRequest::OpenSealed { sealed, aad } => {
+                println!("📬 Opening sealed box");
+                // The identity's policy decides who may use it, like any other key
+                if keychain.key_exists(IDENTITY_KEY_ID) {
+                    if let Err(refusal) = load_key(keychain, IDENTITY_KEY_ID, caller, Access::Use, lock) {
+                        return refusal.into();
+                    }
+                }
+                match identity::open_sealed(keychain.as_ref().as_ref(), &sealed, &aad) {
+                    Ok(plaintext) => VaultResponse::Opened(SecretBytes::new(plaintext)),
+                    Err(e) => VaultResponse::error(ErrorCode::Crypto, e.to_string()),
+                }
+            }
+            VaultRequest::Encrypt { key_id, plaintext, aad, suite } => {
+                println!("🔐 Encrypting with key: {}", key_id);
+                with_key(keychain, &key_id, caller, lock, |key, algorithm| {
+                    operations::encrypt(key, algorithm, suite, plaintext.as_bytes(), &aad).map(VaultResponse::Ciphertext)
+                })
+            }
+            VaultRequest::Decrypt { key_id, ciphertext, aad } => {
+                println!("🔓 Decrypting with key: {}", key_id);
+                with_key(keychain, &key_id, caller, lock, |key, algorithm| {
+                    operations::decrypt(key, algorithm, &ciphertext, &aad).map(VaultResponse::Plaintext)
+                })
+            }
+            VaultRequest::Sign { key_id, message } => {
+                println!("✍️ Signing with key: {}", key_id);
+                with_key(keychain, &key_id, caller, lock, |key, algorithm| {
+                    operations::sign(key, algorithm, &message).map(VaultResponse::Signature)
+                })
+            }
+            VaultRequest::Verify { key_id, message, signature } => {
+                println!("✅ Verifying with key: {}", key_id);
+                with_key(keychain, &key_id, caller, lock, |key, algorithm| {
+                    operations::verify(key, algorithm, &message, &signature).map(VaultResponse::Verified)
+                })
+            }
+            VaultRequest::WrapKey { wrapping_key_id, key_id } => {
+                println!("📦 Wrapping key {} with {}", key_id, wrapping_key_id);
+                // Wrapping hands the key's bytes out, so it needs read access
+                let (key, _) = match load_key(keychain, &key_id, caller, Access::Read, lock) {
+                    Ok(loaded) => loaded,
+                    Err(refusal) => return refusal.into(),
+                };
+                with_key(keychain, &wrapping_key_id, caller, lock, |wrapping_key, algorithm| {
+                    operations::wrap_key(wrapping_key, algorithm, &key).map(VaultResponse::Ciphertext)
+                })
+            }
+            VaultRequest::UnwrapKey { wrapping_key_id, wrapped, key_id, metadata, expires_at, policy } => {
+                println!("📦 Unwrapping key {} with {}", key_id, wrapping_key_id);
+                if let Err(e) = policy::validate(&policy) {
+                    return VaultResponse::error(ErrorCode::InvalidRequest, e);
+                }
+                if let Err(refusal) = check_overwrite(keychain, &key_id, caller, lock) {
+                    return refusal.into();
+                }
+                let key = match load_key(keychain, &wrapping_key_id, caller, Access::Use, lock) {
+                    Ok((wrapping_key, metadata)) => {
+                        let unwrapped = key_algorithm(&metadata)
+                            .and_then(|algorithm| operations::unwrap_key(&wrapping_key, algorithm, &wrapped));
+                        match unwrapped {
+                            Ok(key) => key,
+                            Err(e) => return VaultResponse::error(ErrorCode::Crypto, e.to_string()),
+                        }
+                    }
+                    Err(refusal) => return refusal.into(),
+                };
+                let key_metadata = KeyMetadata {
+                    created_at: chrono::Utc::now().timestamp(),
+                    expires_at,
+                    custom: metadata,
+                    policy,
+                    uses: 0,
+                };
+                match keychain.store_key(&key_id, key.as_slice(), key_metadata) {
+                    Ok(_) => VaultResponse::Success,
+                    Err(e) => VaultResponse::error(ErrorCode::Storage, format!("Failed to store key: {}", e)),
+                }
+            }
+            VaultRequest::GetAuditLog { since, until, limit } => {
+                println!("📜 Audit log requested");
+                match audit.query(since, until, limit) {
+                    Ok(entries) => VaultResponse::AuditLog {
+                        entries,
+                        verified: audit.verify().is_ok(),
+                    },
+                    Err(e) => VaultResponse::error(ErrorCode::Storage, format!("Failed to read audit log: {}", e)),
+                }
+            }
+            VaultRequest::ExportBackup { path, passphrase } => {
+                println!("💾 Backup export requested");
+                let keychain = Arc::clone(keychain);
+                let (caller, lock) = (caller.clone(), Arc::clone(lock));
+                let params = lock.kdf_params();
+                let result = tokio::task::spawn_blocking(move || {
+                    // Only keys the caller could retrieve one by one go into the archive
+                    let readable = |metadata: &KeyMetadata| policy::authorize(metadata, &caller, Access::Read, &lock).is_ok();
+                    backup::export(keychain.as_ref().as_ref(), Path::new(&path), caller.uid, passphrase.as_bytes(), params, readable)
+                })
+                .await;
+                match result {
+                    Ok(Ok(key_count)) => VaultResponse::BackupExported { key_count },
+                    Ok(Err(e)) => backup_error(e),
+                    Err(e) => VaultResponse::error(ErrorCode::Internal, format!("Backup task failed: {}", e)),
+                }
+            }
+            VaultRequest::ImportBackup { path, passphrase, mode, dry_run } => {
+                println!("♻️ Backup import requested ({:?}{})", mode, if dry_run { ", dry run" } else { "" });
+                let keychain = Arc::clone(keychain);
+                let (caller, lock) = (caller.clone(), Arc::clone(lock));
+                let result = tokio::task::spawn_blocking(move || {
+                    let deletable = |metadata: &KeyMetadata| policy::authorize(metadata, &caller, Access::Delete, &lock).is_ok();
+                    backup::import(
+                        keychain.as_ref().as_ref(),
+                        Path::new(&path),
+                        caller.uid,
+                        passphrase.as_bytes(),
+                        mode,
+                        dry_run,
+                        deletable,
+                    )
+                })
+                .await;
+                match result {
+                    Ok(Ok(summary)) => VaultResponse::BackupImported(summary),
+                    Ok(Err(e)) => backup_error(e),
+                    Err(e) => VaultResponse::error(ErrorCode::Internal, format!("Restore task failed: {}", e)),
+                }
+            }
+            VaultRequest::SetPassphrase { passphrase } => {
+                println!("🔏 Passphrase setup requested");
+                if !caller.listed_in(lock.setup_users()) {
+                    return VaultResponse::error(ErrorCode::PermissionDenied, "Not allowed to set the vault passphrase");
+                }
+                let lock = Arc::clone(lock);
+                match tokio::task::spawn_blocking(move || lock.set_passphrase(passphrase.as_bytes())).await {
+                    Ok(Ok(())) => VaultResponse::Success,
+                    Ok(Err(VaultError::PassphraseSet)) => {
+                        VaultResponse::error(ErrorCode::InvalidRequest, "A passphrase is already set")
+                    }
+                    Ok(Err(e)) => VaultResponse::error(ErrorCode::Storage, format!("Failed to set passphrase: {}", e)),
+                    Err(e) => VaultResponse::error(ErrorCode::Internal, format!("Passphrase task failed: {}", e)),
+                }
+            }
+            VaultRequest::Unlock { passphrase } => {
+                println!("🔑 Unlock requested");
+                // Argon2id is slow on purpose; keep it off the runtime threads
+                let lock = Arc::clone(lock);
+                match tokio::task::spawn_blocking(move || lock.unlock(passphrase.as_bytes())).await {
+                    Ok(Ok(())) => VaultResponse::Success,
+                    Ok(Err(VaultError::WrongPassphrase)) => {
+                        VaultResponse::error(ErrorCode::Unauthorized, "Wrong passphrase")
+                    }
+                    Ok(Err(VaultError::RetryLater(wait))) => VaultResponse::error(
+                        ErrorCode::RateLimited,
+                        format!("Too many failed unlocks; try again in {}s", wait.as_secs() + 1),
+                    ),
+                    Ok(Err(VaultError::NoPassphrase)) => {
+                        VaultResponse::error(ErrorCode::InvalidRequest, "No passphrase set; send SetPassphrase first")
+                    }
+                    Ok(Err(e)) => VaultResponse::error(ErrorCode::Storage, format!("Failed to unlock: {}", e)),
+                    Err(e) => VaultResponse::error(ErrorCode::Internal, format!("Unlock task failed: {}", e)),
+                }
+            }
+            VaultRequest::Lock => {
+                if lock.lock() {
+                    VaultResponse::Success
+                } else {
+                    VaultResponse::error(
+                        ErrorCode::InvalidRequest,
+                        "No passphrase set; send SetPassphrase first",
+                    )
+                }
+            }
+            VaultRequest::Status => VaultResponse::Status(VaultStatus {
+                locked: lock.is_locked(),
+                passphrase_set: lock.passphrase_set(),
+                auto_lock_secs: lock.auto_lock().map(|auto_lock| auto_lock.as_secs()),
+            }),
             VaultRequest::Shutdown => {
-                println!("🛑 Shutdown requested");
+                println!("🛑 Shutdown requested by client");
                 VaultResponse::ShuttingDown
             }
         }
@@ -272,8 +699,604 @@ impl VaultServer {
     }
 }
 
-impl Default for VaultServer {
-    fn default() -> Self {
-        Self::new()
+/// A live key the caller may access, or the error response explaining why not
+///
+/// The key is copied into [`SecureMemory`] for as long as the request holds
+/// it. Counts a use against the key's `max_uses`, if it has one.
+fn load_key(
+    keychain: &Arc<Box<dyn KeyStorage>>,
+    key_id: &str,
+    caller: &Caller,
+    access: Access,
+    lock: &VaultLock,
+) -> std::result::Result<(SecureMemory, KeyMetadata), Refusal> {
+    if !keychain.key_exists(key_id) {
+        return Err(Refusal::new(ErrorCode::NotFound, format!("Key not found: {}", key_id)));
+    }
+    let (key, metadata) = keychain
+        .retrieve_key(key_id)
+        .map_err(|e| Refusal::new(ErrorCode::Storage, format!("Failed to retrieve key: {}", e)))?;
+    let key = SecureMemory::from_slice(key.as_bytes())
+        .map_err(|e| Refusal::new(ErrorCode::Internal, format!("Failed to load key: {}", e)))?;
+    // Expired keys stay unusable until the reaper deletes them
+    if metadata.is_expired(chrono::Utc::now().timestamp()) {
+        return Err(Refusal::new(ErrorCode::Expired, format!("Key has expired: {}", key_id)));
+    }
+    policy::authorize(&metadata, caller, access, lock).map_err(|denied| denied_response(denied, key_id))?;
+    if metadata.policy.max_uses.is_none() {
+        return Ok((key, metadata));
+    }
+    match policy::record_use(keychain.as_ref().as_ref(), key_id) {
+        Ok(Some(metadata)) => Ok((key, metadata)),
+        Ok(None) => Err(denied_response(Denied::UsesExhausted, key_id)),
+        Err(e) => Err(Refusal::new(ErrorCode::Storage, format!("Failed to count key use: {}", e))),
+    }
+}
+
+/// Refuse to replace or delete an existing key unless its policy lets the caller
+fn check_overwrite(
+    keychain: &Arc<Box<dyn KeyStorage>>,
+    key_id: &str,
+    caller: &Caller,
+    lock: &VaultLock,
+) -> std::result::Result<(), Refusal> {
+    if !keychain.key_exists(key_id) {
+        return Ok(());
+    }
+    let metadata = keychain
+        .key_metadata(key_id)
+        .map_err(|e| Refusal::new(ErrorCode::Storage, format!("Failed to read key metadata: {}", e)))?;
+    policy::authorize(&metadata, caller, Access::Delete, lock).map_err(|denied| denied_response(denied, key_id))
+}
+
+fn denied_response(denied: Denied, key_id: &str) -> Refusal {
+    Refusal::new(denied.code(), format!("{}: {}", denied, key_id))
+}
+
+/// An error reply, kept small until it becomes a [`VaultResponse`]
+struct Refusal {
+    code: ErrorCode,
+    message: String,
+}
+
+impl Refusal {
+    fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Refusal> for VaultResponse {
+    fn from(refusal: Refusal) -> Self {
+        VaultResponse::error(refusal.code, refusal.message)
+    }
+}
+
+/// Run a crypto operation with a stored key; its failures are `Crypto` errors
+fn with_key(
+    keychain: &Arc<Box<dyn KeyStorage>>,
+    key_id: &str,
+    caller: &Caller,
+    lock: &VaultLock,
+    operation: impl FnOnce(&SecureMemory, KeyAlgorithm) -> Result<VaultResponse>,
+) -> VaultResponse {
+    match load_key(keychain, key_id, caller, Access::Use, lock) {
+        Ok((key, metadata)) => key_algorithm(&metadata)
+            .and_then(|algorithm| operation(&key, algorithm))
+            .unwrap_or_else(|e| VaultResponse::error(ErrorCode::Crypto, e.to_string())),
+        Err(refusal) => refusal.into(),
+    }
+}
+
+/// The algorithm a key's metadata records
+fn key_algorithm(metadata: &KeyMetadata) -> Result<KeyAlgorithm> {
+    KeyAlgorithm::of(&metadata.custom).ok_or_else(|| {
+        VaultError::Encryption(format!("Unknown key algorithm: {}", metadata.custom[ALGORITHM_METADATA]))
+    })
+}
+
+/// Error response for a failed export or import
+fn backup_error(e: VaultError) -> VaultResponse {
+    match e {
+        VaultError::WrongPassphrase => VaultResponse::error(ErrorCode::Unauthorized, "Wrong backup passphrase"),
+        VaultError::Io(e) => match e.kind() {
+            std::io::ErrorKind::NotFound => VaultResponse::error(ErrorCode::NotFound, format!("Backup file not found: {}", e)),
+            std::io::ErrorKind::AlreadyExists => {
+                VaultResponse::error(ErrorCode::InvalidRequest, "Backup file already exists; choose a new path")
+            }
+            std::io::ErrorKind::InvalidInput => VaultResponse::error(ErrorCode::InvalidRequest, e.to_string()),
+            std::io::ErrorKind::PermissionDenied => VaultResponse::error(ErrorCode::PermissionDenied, e.to_string()),
+            _ => VaultResponse::error(ErrorCode::Storage, e.to_string()),
+        },
+        VaultError::Encryption(message) => VaultResponse::error(ErrorCode::Crypto, message),
+        e => VaultResponse::error(ErrorCode::Storage, e.to_string()),
+    }
+}
+
+/// Name recorded in the audit log for `request`
+fn operation_name(request: &VaultRequest) -> &'static str {
+    match request {
+        VaultRequest::Ping => "ping",
+        VaultRequest::StoreKey { .. } => "store_key",
+        VaultRequest::GenerateKey { .. } => "generate_key",
+        VaultRequest::RetrieveKey { .. } => "retrieve_key",
+        VaultRequest::DeleteKey { .. } => "delete_key",
+        VaultRequest::KeyExists { .. } => "key_exists",
+        VaultRequest::ListKeys { .. } => "list_keys",
+        VaultRequest::GetIdentity => "get_identity",
+        VaultRequest::OpenSealed { .. } => "open_sealed",
+        VaultRequest::Encrypt { .. } => "encrypt",
+        VaultRequest::Decrypt { .. } => "decrypt",
+        VaultRequest::Sign { .. } => "sign",
+        VaultRequest::Verify { .. } => "verify",
+        VaultRequest::WrapKey { .. } => "wrap_key",
+        VaultRequest::UnwrapKey { .. } => "unwrap_key",
+        VaultRequest::GetAuditLog { .. } => "get_audit_log",
+        VaultRequest::ExportBackup { .. } => "export_backup",
+        VaultRequest::ImportBackup { .. } => "import_backup",
+        VaultRequest::SetPassphrase { .. } => "set_passphrase",
+        VaultRequest::Unlock { .. } => "unlock",
+        VaultRequest::Lock => "lock",
+        VaultRequest::Status => "status",
+        VaultRequest::Shutdown => "shutdown",
+    }
+}
+
+/// The key a request acts on, if any
+fn key_id_of(request: &VaultRequest) -> Option<&str> {
+    match request {
+        VaultRequest::StoreKey { key_id, .. }
+        | VaultRequest::GenerateKey { key_id, .. }
+        | VaultRequest::RetrieveKey { key_id }
+        | VaultRequest::DeleteKey { key_id }
+        | VaultRequest::KeyExists { key_id }
+        | VaultRequest::Encrypt { key_id, .. }
+        | VaultRequest::Decrypt { key_id, .. }
+        | VaultRequest::Sign { key_id, .. }
+        | VaultRequest::Verify { key_id, .. }
+        | VaultRequest::WrapKey { key_id, .. }
+        | VaultRequest::UnwrapKey { key_id, .. } => Some(key_id),
+        VaultRequest::OpenSealed { .. } => Some(IDENTITY_KEY_ID),
+        _ => None,
+    }
+}
+
+/// Record `event`; a failed write is reported but doesn't fail the request
+fn record(audit: &AuditLog, event: &AuditEvent<'_>) {
+    if let Err(e) = audit.record(event) {
+        eprintln!("❌ Failed to write audit entry: {}", e);
+    }
+}
+
+/// Requests that touch keys, and so are refused while the vault is locked
+fn requires_unlock(request: &VaultRequest) -> bool {
+    !matches!(
+        request,
+        VaultRequest::SetPassphrase { .. }
+            | VaultRequest::Unlock { .. }
+            | VaultRequest::Lock
+            | VaultRequest::Status
+            | VaultRequest::Ping
+            | VaultRequest::Shutdown
+    )
+}
+
+/// One page of `keys` after the cursor `after`
+fn list_page(mut keys: Vec<String>, page_size: Option<u32>, after: Option<&str>) -> VaultResponse {
+    keys.sort();
+    let page_size = page_size
+        .filter(|&size| size > 0)
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .min(MAX_PAGE_SIZE) as usize;
+    let start = after.map_or(0, |after| keys.partition_point(|key| key.as_str() <= after));
+
+    let mut key_ids: Vec<String> = keys.drain(start..).take(page_size + 1).collect();
+    let next_page_token = if key_ids.len() > page_size {
+        key_ids.truncate(page_size);
+        key_ids.last().map(|last| encode_page_token(last))
+    } else {
+        None
+    };
+    VaultResponse::KeyList { key_ids, next_page_token }
+}
+
+/// Page tokens are the last key id of the previous page, kept opaque to clients
+fn encode_page_token(last_key: &str) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(last_key)
+}
+
+fn decode_page_token(token: &str) -> std::result::Result<String, String> {
+    general_purpose::URL_SAFE_NO_PAD
+        .decode(token)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| "Invalid page token".to_string())
+}
+
+/// Restrict who may open a filesystem socket
+#[cfg(unix)]
+fn set_socket_mode(socket: &str, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(socket, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| VaultError::Ipc(format!("Failed to set permissions on {}: {}", socket, e)))
+}
+
+#[cfg(not(unix))]
+fn set_socket_mode(_socket: &str, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Aborts a background task when dropped
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn ipc_error(e: IpcError) -> VaultError {
+    VaultError::Ipc(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::MemoryKeyStorage;
+    use std::path::PathBuf;
+
+    fn storage() -> Arc<Box<dyn KeyStorage>> {
+        Arc::new(Box::new(MemoryKeyStorage::new()))
+    }
+
+    fn unlocked() -> Arc<VaultLock> {
+        Arc::new(VaultLock::in_memory().with_kdf_params(identra_crypto::KeyDerivationParams::fast()))
+    }
+
+    fn store(key_id: &str, expires_at: Option<i64>) -> VaultRequest {
+        VaultRequest::StoreKey {
+            key_id: key_id.to_string(),
+            key_data: SecretBytes::from_slice(b"key material"),
+            metadata: HashMap::new(),
+            expires_at,
+            policy: KeyPolicy::default(),
+        }
+    }
+
+    fn store_with(key_id: &str, policy: KeyPolicy) -> VaultRequest {
+        VaultRequest::StoreKey {
+            key_id: key_id.to_string(),
+            key_data: SecretBytes::from_slice(&[7u8; 32]),
+            metadata: HashMap::new(),
+            expires_at: None,
+            policy,
+        }
+    }
+
+    fn caller() -> Caller {
+        Caller::new(&PeerInfo::default())
+    }
+
+    fn error_code(response: VaultResponse) -> Option<ErrorCode> {
+        match response {
+            VaultResponse::Error { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_key() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+
+        let retrieve = VaultRequest::RetrieveKey { key_id: "missing".to_string() };
+        let delete = VaultRequest::DeleteKey { key_id: "missing".to_string() };
+        assert_eq!(error_code(VaultServer::handle_request(retrieve, &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::NotFound));
+        assert_eq!(error_code(VaultServer::handle_request(delete, &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::NotFound));
+    }
+
+    #[tokio::test]
+    async fn test_expired_key() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+        let tomorrow = chrono::Utc::now().timestamp() + 86_400;
+        VaultServer::handle_request(store("old", Some(1)), &caller(), &keychain, &lock, &audit).await;
+        VaultServer::handle_request(store("fresh", Some(tomorrow)), &caller(), &keychain, &lock, &audit).await;
+
+        let old = VaultRequest::RetrieveKey { key_id: "old".to_string() };
+        assert_eq!(error_code(VaultServer::handle_request(old, &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::Expired));
+        for (key_id, expected) in [("old", false), ("fresh", true), ("missing", false)] {
+            let exists = VaultRequest::KeyExists { key_id: key_id.to_string() };
+            match VaultServer::handle_request(exists, &caller(), &keychain, &lock, &audit).await {
+                VaultResponse::Exists(exists) => assert_eq!(exists, expected, "{}", key_id),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+
+        let fresh = VaultRequest::RetrieveKey { key_id: "fresh".to_string() };
+        match VaultServer::handle_request(fresh, &caller(), &keychain, &lock, &audit).await {
+            VaultResponse::KeyData(key) => {
+                assert_eq!(key.key_data.as_bytes(), b"key material");
+                assert_eq!(key.expires_at, Some(tomorrow));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_keys_pages() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+        for id in ["c", "a", "e", "b", "d"] {
+            VaultServer::handle_request(store(id, None), &caller(), &keychain, &lock, &audit).await;
+        }
+
+        let mut pages = Vec::new();
+        let mut page_token = None;
+        loop {
+            let request = VaultRequest::ListKeys { page_size: Some(2), page_token, prefix: None };
+            match VaultServer::handle_request(request, &caller(), &keychain, &lock, &audit).await {
+                VaultResponse::KeyList { key_ids, next_page_token } => {
+                    pages.push(key_ids);
+                    page_token = next_page_token;
+                }
+                other => panic!("unexpected response: {:?}", other),
+            }
+            if page_token.is_none() {
+                break;
+            }
+        }
+        assert_eq!(pages, vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+
+        let request = VaultRequest::ListKeys { page_size: None, page_token: Some("%%%".to_string()), prefix: None };
+        assert_eq!(error_code(VaultServer::handle_request(request, &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::InvalidRequest));
+    }
+
+    #[tokio::test]
+    async fn test_list_keys_with_prefix() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+        for id in ["user/a/1", "user/b/1", "user/a/2", "user/ab/1"] {
+            VaultServer::handle_request(store(id, None), &caller(), &keychain, &lock, &audit).await;
+        }
+
+        let request = VaultRequest::ListKeys { page_size: Some(1), page_token: None, prefix: Some("user/a/".to_string()) };
+        let token = match VaultServer::handle_request(request, &caller(), &keychain, &lock, &audit).await {
+            VaultResponse::KeyList { key_ids, next_page_token } => {
+                assert_eq!(key_ids, vec!["user/a/1"]);
+                next_page_token
+            }
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let request = VaultRequest::ListKeys { page_size: Some(1), page_token: token, prefix: Some("user/a/".to_string()) };
+        match VaultServer::handle_request(request, &caller(), &keychain, &lock, &audit).await {
+            VaultResponse::KeyList { key_ids, next_page_token } => {
+                assert_eq!(key_ids, vec!["user/a/2"]);
+                assert!(next_page_token.is_none());
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_locked_vault_refuses_key_operations() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+        let unlock = |passphrase: &[u8]| VaultRequest::Unlock { passphrase: SecretBytes::from_slice(passphrase) };
+        let response = VaultServer::handle_request(unlock(b"passphrase"), &caller(), &keychain, &lock, &audit).await;
+        assert_eq!(error_code(response), Some(ErrorCode::InvalidRequest));
+        let set = |passphrase: &[u8]| VaultRequest::SetPassphrase { passphrase: SecretBytes::from_slice(passphrase) };
+        assert!(matches!(VaultServer::handle_request(set(b"passphrase"), &caller(), &keychain, &lock, &audit).await, VaultResponse::Success));
+        let response = VaultServer::handle_request(set(b"takeover"), &caller(), &keychain, &lock, &audit).await;
+        assert_eq!(error_code(response), Some(ErrorCode::InvalidRequest));
+        VaultServer::handle_request(store("k1", None), &caller(), &keychain, &lock, &audit).await;
+        assert!(matches!(VaultServer::handle_request(VaultRequest::Lock, &caller(), &keychain, &lock, &audit).await, VaultResponse::Success));
+
+        let retrieve = VaultRequest::RetrieveKey { key_id: "k1".to_string() };
+        assert_eq!(error_code(VaultServer::handle_request(retrieve, &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::Locked));
+        let list = VaultRequest::ListKeys { page_size: None, page_token: None, prefix: None };
+        assert_eq!(error_code(VaultServer::handle_request(list, &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::Locked));
+        assert!(matches!(VaultServer::handle_request(VaultRequest::Ping, &caller(), &keychain, &lock, &audit).await, VaultResponse::Pong));
+
+        let response = VaultServer::handle_request(unlock(b"wrong"), &caller(), &keychain, &lock, &audit).await;
+        assert_eq!(error_code(response), Some(ErrorCode::Unauthorized));
+        match VaultServer::handle_request(VaultRequest::Status, &caller(), &keychain, &lock, &audit).await {
+            VaultResponse::Status(status) => assert!(status.locked && status.passphrase_set),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        VaultServer::handle_request(unlock(b"passphrase"), &caller(), &keychain, &lock, &audit).await;
+        let retrieve = VaultRequest::RetrieveKey { key_id: "k1".to_string() };
+        assert!(matches!(VaultServer::handle_request(retrieve, &caller(), &keychain, &lock, &audit).await, VaultResponse::KeyData(_)));
+    }
+
+    #[tokio::test]
+    async fn test_only_setup_users_set_the_first_passphrase() {
+        let keychain = storage();
+        let lock = Arc::new(
+            VaultLock::in_memory()
+                .with_kdf_params(identra_crypto::KeyDerivationParams::fast())
+                .with_setup_users(Some(vec!["uid:1000".to_string()])),
+        );
+        let audit = AuditLog::in_memory();
+        let admin = Caller::new(&PeerInfo {
+            uid: Some(1000),
+            ..PeerInfo::default()
+        });
+        let set = || VaultRequest::SetPassphrase { passphrase: SecretBytes::from_slice(b"passphrase") };
+
+        let response = VaultServer::handle_request(set(), &caller(), &keychain, &lock, &audit).await;
+        assert_eq!(error_code(response), Some(ErrorCode::PermissionDenied));
+        assert!(!lock.passphrase_set());
+        assert!(matches!(VaultServer::handle_request(set(), &admin, &keychain, &lock, &audit).await, VaultResponse::Success));
+    }
+
+    #[tokio::test]
+    async fn test_key_policy_enforced() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+        let desktop = Caller::new(&PeerInfo {
+            executable: Some(PathBuf::from("/usr/bin/identra-desktop")),
+            ..PeerInfo::default()
+        });
+        let policy = KeyPolicy {
+            users: Some(vec!["exe:/usr/bin/identra-desktop".to_string()]),
+            deleters: Some(vec!["exe:/usr/bin/identra-desktop".to_string()]),
+            exportable: false,
+            max_uses: Some(2),
+            ..KeyPolicy::default()
+        };
+        let named = KeyPolicy {
+            users: Some(vec!["desktop".to_string()]),
+            ..KeyPolicy::default()
+        };
+        let response = VaultServer::handle_request(store_with("sealed", named), &desktop, &keychain, &lock, &audit).await;
+        assert_eq!(error_code(response), Some(ErrorCode::InvalidRequest));
+        let request = store_with("sealed", policy);
+        VaultServer::handle_request(request, &desktop, &keychain, &lock, &audit).await;
+
+        let retrieve = || VaultRequest::RetrieveKey { key_id: "sealed".to_string() };
+        assert_eq!(error_code(VaultServer::handle_request(retrieve(), &desktop, &keychain, &lock, &audit).await), Some(ErrorCode::PermissionDenied));
+        let encrypt = || VaultRequest::Encrypt {
+            key_id: "sealed".to_string(),
+            plaintext: SecretBytes::from_slice(b"hello"),
+            aad: Vec::new(),
+            suite: Default::default(),
+        };
+        assert_eq!(error_code(VaultServer::handle_request(encrypt(), &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::PermissionDenied));
+        for _ in 0..2 {
+            let response = VaultServer::handle_request(encrypt(), &desktop, &keychain, &lock, &audit).await;
+            assert!(matches!(response, VaultResponse::Ciphertext(_)));
+        }
+        assert_eq!(error_code(VaultServer::handle_request(encrypt(), &desktop, &keychain, &lock, &audit).await), Some(ErrorCode::UsesExhausted));
+
+        // Only listed clients may overwrite or delete it
+        let overwrite = store("sealed", None);
+        assert_eq!(error_code(VaultServer::handle_request(overwrite, &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::PermissionDenied));
+        let delete = || VaultRequest::DeleteKey { key_id: "sealed".to_string() };
+        assert_eq!(error_code(VaultServer::handle_request(delete(), &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::PermissionDenied));
+        assert!(matches!(VaultServer::handle_request(delete(), &desktop, &keychain, &lock, &audit).await, VaultResponse::Success));
+    }
+
+    #[tokio::test]
+    async fn test_open_sealed_follows_identity_policy() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+        let desktop = Caller::new(&PeerInfo {
+            executable: Some(PathBuf::from("/opt/identra/ghost-desktop")),
+            ..PeerInfo::default()
+        });
+        identity::apply_policy(keychain.as_ref().as_ref(), Some(&["exe:/opt/identra/ghost-desktop".to_string()])).unwrap();
+        let recipient = identity::public_key(keychain.as_ref().as_ref()).unwrap();
+        let open = || VaultRequest::OpenSealed {
+            sealed: identra_crypto::seal(&recipient, b"shared dek", b"memory-1").unwrap(),
+            aad: b"memory-1".to_vec(),
+        };
+
+        assert_eq!(key_id_of(&open()), Some(IDENTITY_KEY_ID));
+        assert_eq!(error_code(VaultServer::handle_request(open(), &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::PermissionDenied));
+        match VaultServer::handle_request(open(), &desktop, &keychain, &lock, &audit).await {
+            VaultResponse::Opened(plaintext) => assert_eq!(plaintext.as_bytes(), b"shared dek"),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_key_data_reports_policy_and_uses() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+        let policy = KeyPolicy {
+            max_uses: Some(5),
+            ..KeyPolicy::default()
+        };
+        let request = store_with("counted", policy.clone());
+        VaultServer::handle_request(request, &caller(), &keychain, &lock, &audit).await;
+
+        let retrieve = VaultRequest::RetrieveKey { key_id: "counted".to_string() };
+        match VaultServer::handle_request(retrieve, &caller(), &keychain, &lock, &audit).await {
+            VaultResponse::KeyData(stored) => assert_eq!((stored.policy, stored.uses), (policy, 1)),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reauth_required() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+        let policy = KeyPolicy {
+            reauth_secs: Some(300),
+            ..KeyPolicy::default()
+        };
+        let request = store_with("fresh", policy);
+        VaultServer::handle_request(request, &caller(), &keychain, &lock, &audit).await;
+
+        let retrieve = || VaultRequest::RetrieveKey { key_id: "fresh".to_string() };
+        assert_eq!(error_code(VaultServer::handle_request(retrieve(), &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::ReauthRequired));
+        let set = VaultRequest::SetPassphrase { passphrase: SecretBytes::from_slice(b"passphrase") };
+        VaultServer::handle_request(set, &caller(), &keychain, &lock, &audit).await;
+        assert!(matches!(VaultServer::handle_request(retrieve(), &caller(), &keychain, &lock, &audit).await, VaultResponse::KeyData(_)));
+    }
+
+    #[tokio::test]
+    async fn test_generated_keys_stay_in_daemon() {
+        let keychain = storage();
+        let lock = unlocked();
+        let audit = AuditLog::in_memory();
+        let generate = |key_id: &str, algorithm, exportable| VaultRequest::GenerateKey {
+            key_id: key_id.to_string(),
+            algorithm,
+            exportable,
+        };
+
+        let request = generate("sealed", KeyAlgorithm::Symmetric, false);
+        match VaultServer::handle_request(request, &caller(), &keychain, &lock, &audit).await {
+            VaultResponse::GeneratedKey { public_key } => assert!(public_key.is_none()),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        let retrieve = VaultRequest::RetrieveKey { key_id: "sealed".to_string() };
+        assert_eq!(error_code(VaultServer::handle_request(retrieve, &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::PermissionDenied));
+        let metadata = keychain.key_metadata("sealed").unwrap();
+        assert_eq!(metadata.custom[ALGORITHM_METADATA], "symmetric");
+
+        let request = generate("agreement", KeyAlgorithm::X25519, true);
+        let VaultResponse::GeneratedKey { public_key: Some(public_key) } =
+            VaultServer::handle_request(request, &caller(), &keychain, &lock, &audit).await
+        else {
+            panic!("expected a public key");
+        };
+        let retrieve = VaultRequest::RetrieveKey { key_id: "agreement".to_string() };
+        match VaultServer::handle_request(retrieve, &caller(), &keychain, &lock, &audit).await {
+            VaultResponse::KeyData(stored) => {
+                let identity = identra_crypto::IdentityKeyPair::from_secret_bytes(stored.key_data.as_bytes()).unwrap();
+                assert_eq!(identity.public_key().to_base64(), public_key);
+                assert_eq!(stored.metadata[ALGORITHM_METADATA], "x25519");
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        let encrypt = VaultRequest::Encrypt {
+            key_id: "agreement".to_string(),
+            plaintext: SecretBytes::from_slice(b"hello"),
+            aad: Vec::new(),
+            suite: Default::default(),
+        };
+        assert_eq!(error_code(VaultServer::handle_request(encrypt, &caller(), &keychain, &lock, &audit).await), Some(ErrorCode::Crypto));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let response = VaultServer::handle_request(VaultRequest::Shutdown, &caller(), &storage(), &unlocked(), &AuditLog::in_memory()).await;
+
+        assert!(matches!(response, VaultResponse::ShuttingDown));
     }
 }
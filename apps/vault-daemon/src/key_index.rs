@@ -0,0 +1,287 @@
+use crate::error::{Result, VaultError};
+use crate::file_storage::{restrict_dir, write_atomic};
+use crate::keychain::{load_or_create_internal_key, vault_key, KeyMetadata, KeyStorage};
+use crate::memory::SecureMemory;
+use identra_crypto::MemoryVault;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use zeroize::Zeroizing;
+
+/// Key id of the random key sealing the index, kept in the wrapped backend
+pub const INDEX_KEY_ID: &str = "identra-key-index";
+
+/// Associated data binding the sealed index to its purpose
+const INDEX_AAD: &[u8] = b"identra-key-index-v1";
+
+type Index = BTreeMap<String, KeyMetadata>;
+
+/// Keeps an encrypted index of key ids and metadata next to any backend
+///
+/// OS keychains can store and fetch entries but not enumerate them, so
+/// `list_keys` is answered from the index instead of the backend. The index
+/// is sealed with its own key, itself stored in the backend, and written to
+/// `path` on every change. It is a cache: backends that can enumerate
+/// rebuild it on open, and entries missing from it are added back when read.
+pub struct IndexedKeyStorage {
+    inner: Box<dyn KeyStorage>,
+    index_key: SecureMemory,
+    /// `None` keeps the index in memory only
+    path: Option<PathBuf>,
+    entries: RwLock<Index>,
+}
+
+impl IndexedKeyStorage {
+    /// Wrap `inner`, loading the index sealed at `path` (if any)
+    pub fn open(inner: Box<dyn KeyStorage>, path: Option<PathBuf>) -> Result<Self> {
+        let index_key = load_or_create_internal_key(inner.as_ref(), INDEX_KEY_ID)?;
+        let entries = match &path {
+            Some(path) => load_index(path, &index_key).unwrap_or_else(|e| {
+                eprintln!("⚠️ Ignoring unreadable key index {}: {}", path.display(), e);
+                Index::new()
+            }),
+            None => Index::new(),
+        };
+
+        let storage = Self {
+            inner,
+            index_key,
+            path,
+            entries: RwLock::new(entries),
+        };
+        storage.rebuild()?;
+        Ok(storage)
+    }
+
+    /// Resync with backends that can enumerate their own keys
+    fn rebuild(&self) -> Result<()> {
+        let Ok(ids) = self.inner.list_keys() else {
+            return Ok(());
+        };
+
+        let mut index = Index::new();
+        for id in ids.into_iter().filter(|id| id != INDEX_KEY_ID) {
+            let (_, metadata) = self.inner.retrieve_key(&id)?;
+            index.insert(id, metadata);
+        }
+        self.update(|entries| *entries = index)
+    }
+
+    /// Apply `change` to the index and persist it
+    fn update(&self, change: impl FnOnce(&mut Index)) -> Result<()> {
+        let mut entries = self.entries
+            .write()
+            .map_err(|_| VaultError::Storage("Key index lock poisoned".to_string()))?;
+        change(&mut entries);
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = Zeroizing::new(serde_json::to_string(&*entries)?);
+        let sealed = MemoryVault::lock_with_aad(&json, &vault_key(&self.index_key)?, INDEX_AAD)
+            .map_err(|e| VaultError::Encryption(format!("Failed to seal key index: {}", e)))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+            restrict_dir(dir)?;
+        }
+        write_atomic(path, sealed.as_bytes())
+    }
+
+    /// Persisting the index is best effort: the key itself is already stored
+    fn update_or_warn(&self, change: impl FnOnce(&mut Index)) {
+        if let Err(e) = self.update(change) {
+            eprintln!("⚠️ Failed to update key index: {}", e);
+        }
+    }
+
+    fn is_indexed(&self, key_id: &str) -> bool {
+        self.entries
+            .read()
+            .map(|entries| entries.contains_key(key_id))
+            .unwrap_or(false)
+    }
+}
+
+impl KeyStorage for IndexedKeyStorage {
+    fn store_key(&self, key_id: &str, key: &[u8], metadata: KeyMetadata) -> Result<()> {
+        reject_reserved(key_id)?;
+        self.inner.store_key(key_id, key, metadata.clone())?;
+        self.update_or_warn(|entries| {
+            entries.insert(key_id.to_string(), metadata);
+        });
+        Ok(())
+    }
+
+    fn retrieve_key(&self, key_id: &str) -> Result<(identra_crypto::SecretBytes, KeyMetadata)> {
+        reject_reserved(key_id)?;
+        let (key, metadata) = self.inner.retrieve_key(key_id)?;
+        if !self.is_indexed(key_id) {
+            // Stored before the index existed, or the index was lost
+            let entry = metadata.clone();
+            self.update_or_warn(|entries| {
+                entries.insert(key_id.to_string(), entry);
+            });
+        }
+        Ok((key, metadata))
+    }
+
+    fn delete_key(&self, key_id: &str) -> Result<()> {
+        reject_reserved(key_id)?;
+        self.inner.delete_key(key_id)?;
+        self.update_or_warn(|entries| {
+            entries.remove(key_id);
+        });
+        Ok(())
+    }
+
+    fn key_exists(&self, key_id: &str) -> bool {
+        if key_id == INDEX_KEY_ID {
+            return false;
+        }
+        let exists = self.inner.key_exists(key_id);
+        if !exists && self.is_indexed(key_id) {
+            // Removed behind the daemon's back (e.g. from the OS keychain UI)
+            self.update_or_warn(|entries| {
+                entries.remove(key_id);
+            });
+        }
+        exists
+    }
+
+    /// Key ids in lexicographic order
+    fn list_keys(&self) -> Result<Vec<String>> {
+        let entries = self.entries
+            .read()
+            .map_err(|_| VaultError::Storage("Key index lock poisoned".to_string()))?;
+        Ok(entries.keys().cloned().collect())
+    }
+
+    /// Served from the index, so the key itself stays in the backend
+    fn key_metadata(&self, key_id: &str) -> Result<KeyMetadata> {
+        reject_reserved(key_id)?;
+        let indexed = self.entries
+            .read()
+            .map_err(|_| VaultError::Storage("Key index lock poisoned".to_string()))?
+            .get(key_id)
+            .cloned();
+        match indexed {
+            Some(metadata) => Ok(metadata),
+            None => self.retrieve_key(key_id).map(|(_, metadata)| metadata),
+        }
+    }
+}
+
+fn reject_reserved(key_id: &str) -> Result<()> {
+    if key_id == INDEX_KEY_ID {
+        return Err(VaultError::Storage(format!("Key id {} is reserved", INDEX_KEY_ID)));
+    }
+    Ok(())
+}
+
+fn load_index(path: &std::path::Path, key: &SecureMemory) -> Result<Index> {
+    let sealed = match fs::read_to_string(path) {
+        Ok(sealed) => sealed,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Index::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let json = Zeroizing::new(
+        MemoryVault::open_with_aad(sealed.trim(), &vault_key(key)?, INDEX_AAD)
+            .map_err(|e| VaultError::Encryption(format!("Failed to open key index: {}", e)))?,
+    );
+    Ok(serde_json::from_str(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::MemoryKeyStorage;
+    use identra_crypto::SecretBytes;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn metadata(purpose: &str) -> KeyMetadata {
+        KeyMetadata {
+            created_at: 0,
+            expires_at: None,
+            custom: HashMap::from([("purpose".to_string(), purpose.to_string())]),
+            ..Default::default()
+        }
+    }
+
+    /// A backend that, like the OS keychains, can't enumerate
+    struct Unlistable(Arc<MemoryKeyStorage>);
+
+    impl KeyStorage for Unlistable {
+        fn store_key(&self, key_id: &str, key: &[u8], metadata: KeyMetadata) -> Result<()> {
+            self.0.store_key(key_id, key, metadata)
+        }
+
+        fn retrieve_key(&self, key_id: &str) -> Result<(SecretBytes, KeyMetadata)> {
+            self.0.retrieve_key(key_id)
+        }
+
+        fn delete_key(&self, key_id: &str) -> Result<()> {
+            self.0.delete_key(key_id)
+        }
+
+        fn key_exists(&self, key_id: &str) -> bool {
+            self.0.key_exists(key_id)
+        }
+
+        fn list_keys(&self) -> Result<Vec<String>> {
+            Err(VaultError::Keychain("list_keys not supported".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_index_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key-index");
+        let backend = Arc::new(MemoryKeyStorage::new());
+
+        let storage = IndexedKeyStorage::open(Box::new(Unlistable(backend.clone())), Some(path.clone())).unwrap();
+        storage.store_key("b", b"second", metadata("dek")).unwrap();
+        storage.store_key("a", b"first", metadata("dek")).unwrap();
+        storage.store_key("c", b"third", metadata("dek")).unwrap();
+        storage.delete_key("c").unwrap();
+        assert_eq!(storage.list_keys().unwrap(), vec!["a", "b"]);
+        drop(storage);
+
+        // Key ids aren't readable at rest
+        assert!(!fs::read_to_string(&path).unwrap().contains("dek"));
+
+        let storage = IndexedKeyStorage::open(Box::new(Unlistable(backend)), Some(path)).unwrap();
+        assert_eq!(storage.list_keys().unwrap(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_index_heals_from_backend() {
+        let backend = Arc::new(MemoryKeyStorage::new());
+        backend.store_key("legacy", b"old", metadata("dek")).unwrap();
+        let storage = IndexedKeyStorage::open(Box::new(Unlistable(backend.clone())), None).unwrap();
+        assert!(storage.list_keys().unwrap().is_empty());
+
+        // Reading an unindexed key adds it
+        storage.retrieve_key("legacy").unwrap();
+        assert_eq!(storage.list_keys().unwrap(), vec!["legacy"]);
+
+        // Deleting behind the daemon's back drops it on the next lookup
+        backend.delete_key("legacy").unwrap();
+        assert!(!storage.key_exists("legacy"));
+        assert!(storage.list_keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_listable_backend_rebuilds_index() {
+        let backend = MemoryKeyStorage::new();
+        backend.store_key("existing", b"key", metadata("dek")).unwrap();
+
+        let storage = IndexedKeyStorage::open(Box::new(backend), None).unwrap();
+
+        assert_eq!(storage.list_keys().unwrap(), vec!["existing"]);
+        assert!(!storage.key_exists(INDEX_KEY_ID));
+        assert!(storage.retrieve_key(INDEX_KEY_ID).is_err());
+        assert!(storage.delete_key(INDEX_KEY_ID).is_err());
+    }
+}
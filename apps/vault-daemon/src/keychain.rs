@@ -1,23 +1,146 @@
 use crate::error::Result;
+use crate::file_storage::FileKeyStorage;
+use crate::key_index::IndexedKeyStorage;
+use crate::memory::SecureMemory;
 use base64::{engine::general_purpose, Engine as _};
+use identra_crypto::{MemoryVault, SecretBytes, VaultKey};
+use identra_ipc::token::{TokenStore, TOKEN_FILE_NAME};
+use identra_ipc::KeyPolicy;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use zeroize::Zeroizing;
 
 /// Metadata stored alongside keys
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct KeyMetadata {
     pub created_at: i64,
     pub expires_at: Option<i64>,
     pub custom: HashMap<String, String>,
+    /// Who may read, use and delete the key; keys stored before policies allow everyone
+    #[serde(default)]
+    pub policy: KeyPolicy,
+    /// Successful uses so far, counted when the policy caps them
+    #[serde(default)]
+    pub uses: u64,
+}
+
+impl KeyMetadata {
+    /// Whether `expires_at` has passed at `now` (Unix seconds)
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now > expires_at)
+    }
 }
 
 /// Trait for cross-platform key storage
 pub trait KeyStorage: Send + Sync {
     fn store_key(&self, key_id: &str, key: &[u8], metadata: KeyMetadata) -> Result<()>;
-    fn retrieve_key(&self, key_id: &str) -> Result<(Vec<u8>, KeyMetadata)>;
+    fn retrieve_key(&self, key_id: &str) -> Result<(SecretBytes, KeyMetadata)>;
     fn delete_key(&self, key_id: &str) -> Result<()>;
     fn key_exists(&self, key_id: &str) -> bool;
     fn list_keys(&self) -> Result<Vec<String>>;
+
+    /// A key's metadata; override when it can be read without the key material
+    fn key_metadata(&self, key_id: &str) -> Result<KeyMetadata> {
+        self.retrieve_key(key_id).map(|(_, metadata)| metadata)
+    }
+}
+
+/// Key ids the daemon keeps for itself; clients may not read, change or list them
+pub fn is_internal_key(key_id: &str) -> bool {
+    [
+        crate::key_index::INDEX_KEY_ID,
+        crate::audit::AUDIT_KEY_ID,
+        crate::identity::IDENTITY_KEY_ID,
+    ]
+    .contains(&key_id)
+}
+
+/// Random 256-bit key for the daemon's own use, created in `storage` on first use
+pub(crate) fn load_or_create_internal_key(storage: &dyn KeyStorage, key_id: &str) -> Result<SecureMemory> {
+    if storage.key_exists(key_id) {
+        let (key, _) = storage.retrieve_key(key_id)?;
+        let key = SecureMemory::from_slice(key.as_bytes())?;
+        vault_key(&key)
+            .map_err(|e| crate::error::VaultError::Encryption(format!("Stored key {} is invalid: {}", key_id, e)))?;
+        return Ok(key);
+    }
+
+    let key = SecureMemory::from_slice(MemoryVault::generate_key().as_bytes())?;
+    let metadata = KeyMetadata {
+        created_at: chrono::Utc::now().timestamp(),
+        expires_at: None,
+        custom: HashMap::new(),
+        ..Default::default()
+    };
+    storage.store_key(key_id, key.as_slice(), metadata)?;
+    Ok(key)
+}
+
+/// A [`VaultKey`] for one operation with a key the daemon holds in [`SecureMemory`]
+pub(crate) fn vault_key(key: &SecureMemory) -> Result<VaultKey> {
+    MemoryVault::key_from_bytes(key.as_slice()).map_err(|e| crate::error::VaultError::Encryption(e.to_string()))
+}
+
+/// Volatile storage kept in process memory
+///
+/// Keys are lost when the daemon exits. Meant for tests and throwaway
+/// development daemons, never for real vaults.
+#[derive(Default)]
+pub struct MemoryKeyStorage {
+    keys: RwLock<HashMap<String, (SecureMemory, KeyMetadata)>>,
+}
+
+impl MemoryKeyStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KeyStorage for MemoryKeyStorage {
+    fn store_key(&self, key_id: &str, key: &[u8], metadata: KeyMetadata) -> Result<()> {
+        self.keys
+            .write()
+            .map_err(|_| crate::error::VaultError::Storage("Memory storage lock poisoned".to_string()))?
+            .insert(key_id.to_string(), (SecureMemory::from_slice(key)?, metadata));
+        Ok(())
+    }
+
+    fn retrieve_key(&self, key_id: &str) -> Result<(SecretBytes, KeyMetadata)> {
+        let keys = self.keys
+            .read()
+            .map_err(|_| crate::error::VaultError::Storage("Memory storage lock poisoned".to_string()))?;
+        let (key, metadata) = keys
+            .get(key_id)
+            .ok_or_else(|| crate::error::VaultError::Storage(format!("Key not found: {}", key_id)))?;
+        Ok((SecretBytes::from_slice(key.as_slice()), metadata.clone()))
+    }
+
+    fn delete_key(&self, key_id: &str) -> Result<()> {
+        self.keys
+            .write()
+            .map_err(|_| crate::error::VaultError::Storage("Memory storage lock poisoned".to_string()))?
+            .remove(key_id)
+            .map(|_| ())
+            .ok_or_else(|| crate::error::VaultError::Storage(format!("Key not found: {}", key_id)))
+    }
+
+    fn key_exists(&self, key_id: &str) -> bool {
+        self.keys
+            .read()
+            .map(|keys| keys.contains_key(key_id))
+            .unwrap_or(false)
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>> {
+        let keys = self.keys
+            .read()
+            .map_err(|_| crate::error::VaultError::Storage("Memory storage lock poisoned".to_string()))?;
+        let mut ids: Vec<String> = keys.keys().cloned().collect();
+        ids.sort();
+        Ok(ids)
+    }
 }
 
 /// Windows implementation using DPAPI via keyring crate
@@ -51,7 +174,7 @@ impl KeyStorage for WindowsKeyStorage {
     fn store_key(&self, key_id: &str, key: &[u8], metadata: KeyMetadata) -> Result<()> {
         // Store the key
         let entry = self.get_entry(key_id)?;
-        let key_str = general_purpose::STANDARD.encode(key);
+        let key_str = Zeroizing::new(general_purpose::STANDARD.encode(key));
         entry
             .set_password(&key_str)
             .map_err(|e| crate::error::VaultError::Keychain(format!("Failed to store key: {}", e)))?;
@@ -67,14 +190,15 @@ impl KeyStorage for WindowsKeyStorage {
         Ok(())
     }
     
-    fn retrieve_key(&self, key_id: &str) -> Result<(Vec<u8>, KeyMetadata)> {
+    fn retrieve_key(&self, key_id: &str) -> Result<(SecretBytes, KeyMetadata)> {
         // Retrieve the key
         let entry = self.get_entry(key_id)?;
-        let key_str = entry
+        let key_str = Zeroizing::new(entry
             .get_password()
-            .map_err(|e| crate::error::VaultError::Keychain(format!("Failed to retrieve key: {}", e)))?;
+            .map_err(|e| crate::error::VaultError::Keychain(format!("Failed to retrieve key: {}", e)))?);
         
-        let key_data = general_purpose::STANDARD.decode(&key_str)
+        let key_data = general_purpose::STANDARD.decode(key_str.as_bytes())
+            .map(SecretBytes::new)
             .map_err(|e| crate::error::VaultError::Keychain(format!("Failed to decode key: {}", e)))?;
         
         // Retrieve metadata
@@ -128,6 +252,10 @@ impl KeyStorage for WindowsKeyStorage {
 #[cfg(target_os = "macos")]
 pub struct MacOSKeyStorage;
 
+/// Entry looked up (never created) to check the Secret Service answers
+#[cfg(target_os = "linux")]
+const PROBE_ENTRY: &str = "identra-probe";
+
 /// Linux implementation using Secret Service (via keyring crate)
 #[cfg(target_os = "linux")]
 pub struct LinuxKeyStorage {
@@ -152,13 +280,25 @@ impl LinuxKeyStorage {
         keyring::Entry::new(&self.service_name, &metadata_key)
             .map_err(|e| crate::error::VaultError::Keychain(format!("Failed to create metadata entry: {}", e)))
     }
+
+    /// Check that a Secret Service answers, so a headless machine fails at startup with a way out
+    pub fn probe(&self) -> Result<()> {
+        match self.get_entry(PROBE_ENTRY)?.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(crate::error::VaultError::Keychain(format!(
+                "No Secret Service keyring is reachable ({}); start one, or pass --backend file \
+                 (storage.backend = \"file\") to keep keys in an encrypted file instead",
+                e
+            ))),
+        }
+    }
 }
 
 #[cfg(target_os = "linux")]
 impl KeyStorage for LinuxKeyStorage {
     fn store_key(&self, key_id: &str, key: &[u8], metadata: KeyMetadata) -> Result<()> {
         let entry = self.get_entry(key_id)?;
-        let key_str = general_purpose::STANDARD.encode(key);
+        let key_str = Zeroizing::new(general_purpose::STANDARD.encode(key));
         entry
             .set_password(&key_str)
             .map_err(|e| crate::error::VaultError::Keychain(format!("Failed to store key: {}", e)))?;
@@ -173,13 +313,14 @@ impl KeyStorage for LinuxKeyStorage {
         Ok(())
     }
 
-    fn retrieve_key(&self, key_id: &str) -> Result<(Vec<u8>, KeyMetadata)> {
+    fn retrieve_key(&self, key_id: &str) -> Result<(SecretBytes, KeyMetadata)> {
         let entry = self.get_entry(key_id)?;
-        let key_str = entry
+        let key_str = Zeroizing::new(entry
             .get_password()
-            .map_err(|e| crate::error::VaultError::Keychain(format!("Failed to retrieve key: {}", e)))?;
+            .map_err(|e| crate::error::VaultError::Keychain(format!("Failed to retrieve key: {}", e)))?);
 
-        let key_data = general_purpose::STANDARD.decode(&key_str)
+        let key_data = general_purpose::STANDARD.decode(key_str.as_bytes())
+            .map(SecretBytes::new)
             .map_err(|e| crate::error::VaultError::Keychain(format!("Failed to decode key: {}", e)))?;
 
         let metadata_entry = self.get_metadata_entry(key_id)?;
@@ -223,28 +364,114 @@ impl KeyStorage for LinuxKeyStorage {
     }
 }
 
-/// Factory function to create platform-specific key storage
-pub fn create_key_storage() -> Box<dyn KeyStorage> {
+/// Sealed key index file, in the backend's state directory
+const KEY_INDEX_NAME: &str = "key-index";
+
+/// Where the daemon keeps keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// The OS keychain (Credential Manager, Secret Service), under `service`
+    Keychain { service: String, state_dir: PathBuf },
+    /// Encrypted keystore in a directory, for headless machines
+    File(PathBuf),
+    /// Process memory only; keys vanish on exit (development and tests)
+    Memory,
+}
+
+impl StorageBackend {
+    /// Where the daemon keeps its own files (key index, lock verifier); `None` for memory
+    pub fn state_dir(&self) -> Option<PathBuf> {
+        match self {
+            Self::File(dir) | Self::Keychain { state_dir: dir, .. } => Some(dir.clone()),
+            Self::Memory => None,
+        }
+    }
+
+    /// Where the IPC install token lives: a token file next to the file
+    /// keystore, so headless machines need no keyring; the keyring otherwise,
+    /// under the keychain's own service so namespaces don't share a token
+    pub fn token_store(&self) -> TokenStore {
+        match self {
+            Self::File(dir) => TokenStore::File(dir.join(TOKEN_FILE_NAME)),
+            Self::Keychain { service, .. } => TokenStore::Keyring(service.clone()),
+            Self::Memory => TokenStore::default(),
+        }
+    }
+}
+
+/// Per-user data directory for the file keystore
+pub(crate) fn default_store_dir() -> PathBuf {
+    identra_ipc::token::default_vault_dir()
+}
+
+/// Factory function to create the configured key storage, behind its key index
+pub fn create_key_storage(backend: &StorageBackend) -> Result<Box<dyn KeyStorage>> {
+    let storage: Box<dyn KeyStorage> = match backend {
+        StorageBackend::File(dir) => Box::new(FileKeyStorage::open_from_env(dir.clone())?),
+        StorageBackend::Keychain { service, .. } => create_keychain_storage(service)?,
+        StorageBackend::Memory => {
+            eprintln!("⚠️ Using in-memory key storage: keys will not survive a restart");
+            Box::new(MemoryKeyStorage::new())
+        }
+    };
+    let index_path = backend.state_dir().map(|dir| dir.join(KEY_INDEX_NAME));
+    Ok(Box::new(IndexedKeyStorage::open(storage, index_path)?))
+}
+
+/// Whether this platform has an OS keychain backend
+pub const KEYCHAIN_SUPPORTED: bool = cfg!(any(target_os = "windows", target_os = "linux"));
+
+/// Platform-specific OS keychain storage
+fn create_keychain_storage(service: &str) -> Result<Box<dyn KeyStorage>> {
     #[cfg(target_os = "windows")]
     {
-        Box::new(WindowsKeyStorage::new("identra-vault"))
+        Ok(Box::new(WindowsKeyStorage::new(service)))
     }
     
-    #[cfg(target_os = "macos")]
+    #[cfg(target_os = "linux")]
     {
-        // TODO: Implement macOS Keychain
-        unimplemented!("macOS keychain not yet implemented")
+        let storage = LinuxKeyStorage::new(service);
+        storage.probe()?;
+        Ok(Box::new(storage))
     }
     
-    #[cfg(target_os = "linux")]
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
     {
-        Box::new(LinuxKeyStorage::new("identra-vault"))
+        // TODO: Implement macOS Keychain
+        let _ = service;
+        Err(crate::error::VaultError::Storage(
+            "No OS keychain backend on this platform yet; use the file backend".to_string(),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn metadata() -> KeyMetadata {
+        KeyMetadata {
+            created_at: 0,
+            expires_at: None,
+            custom: HashMap::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        let storage = MemoryKeyStorage::new();
+        storage.store_key("b", b"second", metadata()).unwrap();
+        storage.store_key("a", b"first", metadata()).unwrap();
+
+        assert_eq!(storage.retrieve_key("a").unwrap().0.as_bytes(), b"first");
+        assert_eq!(storage.list_keys().unwrap(), vec!["a", "b"]);
+
+        storage.delete_key("a").unwrap();
+        assert!(!storage.key_exists("a"));
+        assert!(storage.retrieve_key("a").is_err());
+        assert!(storage.delete_key("a").is_err());
+    }
     
     #[test]
     #[cfg(target_os = "windows")]
@@ -253,11 +480,11 @@ mod tests {
         let test_key = b"test_secret_key_12345678901234567890";
         
         // Store key
-        storage.store_key("test-key", test_key).unwrap();
+        storage.store_key("test-key", test_key, metadata()).unwrap();
         
         // Retrieve key
-        let retrieved = storage.retrieve_key("test-key").unwrap();
-        assert_eq!(test_key, &retrieved[..]);
+        let (retrieved, _) = storage.retrieve_key("test-key").unwrap();
+        assert_eq!(&test_key[..], retrieved.as_bytes());
         
         // Delete key
         storage.delete_key("test-key").unwrap();
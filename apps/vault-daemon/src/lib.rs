@@ -1,16 +1,59 @@
+// Daemon configuration (file, environment, flags)
+pub mod config;
+
 // Keychain integration module
 pub mod keychain;
 
+// Encrypted file keystore for headless machines
+pub mod file_storage;
+
+// Encrypted key id index so every backend can list keys
+pub mod key_index;
+
+// Background deletion of expired keys
+pub mod expiry;
+
+// Encrypt/decrypt/sign/wrap with keys that stay in the daemon
+pub mod operations;
+
+// Passphrase-encrypted backup and restore of all keys
+pub mod backup;
+
+// Per-key access policies (who may read, use, delete)
+pub mod policy;
+
+// Hash-chained audit log of requests
+pub mod audit;
+
+// Lock/unlock state and auto-lock
+pub mod lock;
+
 // Memory security module
 pub mod memory;
 
+// X25519 identity for receiving shared memories
+pub mod identity;
+
+// Peer credential checks for IPC connections
+pub mod peer;
+
+// Shutdown signal and connection draining
+pub mod shutdown;
+
 // IPC communication module
 pub mod ipc;
 
 // Error types
 mod error;
 
+pub use audit::AuditLog;
+pub use config::{CliArgs, DaemonConfig};
 pub use error::{VaultError, Result};
-pub use keychain::KeyStorage;
+pub use file_storage::FileKeyStorage;
+pub use key_index::IndexedKeyStorage;
+pub use keychain::{KeyStorage, MemoryKeyStorage, StorageBackend};
+pub use lock::VaultLock;
 pub use memory::SecureMemory;
 pub use ipc::VaultServer;
+pub use peer::{PeerInfo, PeerPolicy};
+pub use shutdown::Shutdown;
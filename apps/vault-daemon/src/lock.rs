@@ -0,0 +1,482 @@
+use crate::error::{Result, VaultError};
+use crate::file_storage::{restrict_dir, write_atomic};
+use identra_crypto::{KeyDerivationParams, KeyFile};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Idle time after which the vault locks itself unless configured otherwise
+pub const DEFAULT_AUTO_LOCK: Duration = Duration::from_secs(15 * 60);
+
+/// Passphrase verifier file, in the same directory as the key index
+pub const LOCK_FILE_NAME: &str = "lock.json";
+
+/// Wrong passphrases allowed before unlocking backs off
+const FREE_UNLOCK_ATTEMPTS: u32 = 3;
+
+/// Wait after the first failure past the free attempts; doubles with each further one
+const UNLOCK_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest wait between unlock attempts
+const MAX_UNLOCK_BACKOFF: Duration = Duration::from_secs(15 * 60);
+
+/// Whether key operations are allowed right now
+///
+/// The passphrase is checked against a [`KeyFile`] whose wrapped key is
+/// never used: unwrapping it is the proof. Until a passphrase is set with
+/// [`set_passphrase`](Self::set_passphrase) the vault stays unlocked. Locking gates the
+/// daemon's requests; key material itself stays with the storage backend.
+///
+/// Unlock attempts run one at a time, and after [`FREE_UNLOCK_ATTEMPTS`]
+/// wrong passphrases each further attempt must wait twice as long as the
+/// last. The count lives in memory and resets on a successful unlock.
+pub struct VaultLock {
+    /// `None` keeps the verifier in memory only
+    path: Option<PathBuf>,
+    params: KeyDerivationParams,
+    auto_lock: Option<Duration>,
+    /// Policy entries that may set the first passphrase; `None` allows any client
+    setup_users: Option<Vec<String>>,
+    inner: Mutex<LockInner>,
+    /// Held while a passphrase is checked, so guesses can't run in parallel
+    attempts: Mutex<()>,
+}
+
+struct LockInner {
+    verifier: Option<KeyFile>,
+    state: LockState,
+    /// When the passphrase was last entered, for keys that require re-authentication
+    last_unlock: Option<Instant>,
+    /// Wrong passphrases since the last successful unlock
+    failed_unlocks: u32,
+    /// No unlock attempts before this
+    retry_after: Option<Instant>,
+}
+
+impl LockInner {
+    fn mark_unlocked(&mut self) {
+        let now = Instant::now();
+        self.state = LockState::Unlocked { last_activity: now };
+        self.last_unlock = Some(now);
+        self.failed_unlocks = 0;
+        self.retry_after = None;
+    }
+
+    /// Count a wrong passphrase, returning how long the next attempt must wait
+    fn mark_failed(&mut self, now: Instant) -> Option<Duration> {
+        self.failed_unlocks = self.failed_unlocks.saturating_add(1);
+        let backoff = unlock_backoff(self.failed_unlocks)?;
+        self.retry_after = Some(now + backoff);
+        Some(backoff)
+    }
+}
+
+/// Wait imposed after `failures` wrong passphrases in a row
+fn unlock_backoff(failures: u32) -> Option<Duration> {
+    let doublings = failures.checked_sub(FREE_UNLOCK_ATTEMPTS + 1)?;
+    let backoff = UNLOCK_BACKOFF.saturating_mul(2u32.saturating_pow(doublings));
+    Some(backoff.min(MAX_UNLOCK_BACKOFF))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockState {
+    Locked,
+    Unlocked { last_activity: Instant },
+}
+
+impl VaultLock {
+    /// Load the verifier at `path`; the vault starts locked if one exists
+    pub fn open(path: Option<PathBuf>) -> Result<Self> {
+        let verifier = match &path {
+            Some(path) if path.exists() => Some(
+                KeyFile::load(path)
+                    .map_err(|e| VaultError::Storage(format!("Failed to load lock file: {}", e)))?,
+            ),
+            _ => None,
+        };
+        let state = match verifier {
+            Some(_) => LockState::Locked,
+            None => LockState::Unlocked { last_activity: Instant::now() },
+        };
+
+        Ok(Self {
+            path,
+            params: KeyDerivationParams::secure(),
+            auto_lock: Some(DEFAULT_AUTO_LOCK),
+            setup_users: None,
+            inner: Mutex::new(LockInner {
+                verifier,
+                state,
+                last_unlock: None,
+                failed_unlocks: 0,
+                retry_after: None,
+            }),
+            attempts: Mutex::new(()),
+        })
+    }
+
+    /// No passphrase yet and nothing persisted (tests and the memory backend)
+    pub fn in_memory() -> Self {
+        Self::open(None).expect("nothing to load without a path")
+    }
+
+    /// KDF parameters used when the passphrase is first set
+    pub fn with_kdf_params(mut self, params: KeyDerivationParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Idle time before locking automatically; `None` disables auto-lock
+    pub fn with_auto_lock(mut self, auto_lock: Option<Duration>) -> Self {
+        self.auto_lock = auto_lock;
+        self
+    }
+
+    /// Clients allowed to set the first passphrase (policy entries); `None` allows any
+    pub fn with_setup_users(mut self, setup_users: Option<Vec<String>>) -> Self {
+        self.setup_users = setup_users;
+        self
+    }
+
+    pub fn setup_users(&self) -> &Option<Vec<String>> {
+        &self.setup_users
+    }
+
+    /// KDF parameters for passphrases this vault sets, also used for backups
+    pub fn kdf_params(&self) -> KeyDerivationParams {
+        self.params.clone()
+    }
+
+    pub fn auto_lock(&self) -> Option<Duration> {
+        self.auto_lock
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.inner().state == LockState::Locked
+    }
+
+    pub fn passphrase_set(&self) -> bool {
+        self.inner().verifier.is_some()
+    }
+
+    /// Whether the passphrase was entered within the last `window`
+    pub fn unlocked_within(&self, window: Duration) -> bool {
+        let inner = self.inner();
+        inner.state != LockState::Locked && inner.last_unlock.is_some_and(|at| at.elapsed() <= window)
+    }
+
+    /// Record activity for auto-lock, failing if the vault is locked
+    pub fn touch(&self) -> Result<()> {
+        let mut inner = self.inner();
+        match &mut inner.state {
+            LockState::Locked => Err(VaultError::Locked),
+            LockState::Unlocked { last_activity } => {
+                *last_activity = Instant::now();
+                Ok(())
+            }
+        }
+    }
+
+    /// Set the passphrase of a vault that has none; the vault stays unlocked
+    ///
+    /// Runs Argon2id; call it off the async runtime.
+    pub fn set_passphrase(&self, passphrase: &[u8]) -> Result<()> {
+        if self.passphrase_set() {
+            return Err(VaultError::PassphraseSet);
+        }
+        // Derive before taking the lock so status checks don't wait on Argon2
+        let (verifier, _) = KeyFile::create(passphrase, self.params.clone())
+            .map_err(|e| VaultError::Encryption(format!("Failed to set passphrase: {}", e)))?;
+
+        let mut inner = self.inner();
+        // Another connection may have set one while we were deriving
+        if inner.verifier.is_some() {
+            return Err(VaultError::PassphraseSet);
+        }
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+                restrict_dir(dir)?;
+            }
+            write_atomic(path, &serde_json::to_vec_pretty(&verifier)?)?;
+        }
+        inner.verifier = Some(verifier);
+        inner.mark_unlocked();
+        println!("🔏 Vault passphrase set");
+        Ok(())
+    }
+
+    /// Check `passphrase` and unlock
+    ///
+    /// Refused with [`VaultError::RetryLater`] while backing off from wrong
+    /// passphrases. Runs Argon2id; call it off the async runtime.
+    pub fn unlock(&self, passphrase: &[u8]) -> Result<()> {
+        let _attempt = self.attempts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Check against a copy so the KDF runs without holding the state lock
+        let verifier = {
+            let inner = self.inner();
+            if let Some(wait) = inner.retry_after.map(|at| at.saturating_duration_since(Instant::now())) {
+                if !wait.is_zero() {
+                    return Err(VaultError::RetryLater(wait));
+                }
+            }
+            inner.verifier.clone().ok_or(VaultError::NoPassphrase)?
+        };
+        if verifier.unlock(passphrase).is_err() {
+            let mut inner = self.inner();
+            if let Some(backoff) = inner.mark_failed(Instant::now()) {
+                eprintln!(
+                    "⚠️ {} failed unlock attempts; next one allowed in {:?}",
+                    inner.failed_unlocks, backoff
+                );
+            }
+            return Err(VaultError::WrongPassphrase);
+        }
+
+        self.inner().mark_unlocked();
+        println!("🔓 Vault unlocked");
+        Ok(())
+    }
+
+    /// Lock until the next successful [`unlock`](Self::unlock)
+    ///
+    /// Returns `false` if no passphrase is set, as there'd be no way back in.
+    pub fn lock(&self) -> bool {
+        let mut inner = self.inner();
+        if inner.verifier.is_none() {
+            return false;
+        }
+        if inner.state != LockState::Locked {
+            inner.state = LockState::Locked;
+            println!("🔒 Vault locked");
+        }
+        true
+    }
+
+    /// Lock because of `reason` (idle timeout, suspend, ...), if a passphrase is set
+    pub fn lock_for(&self, reason: &str) {
+        let mut inner = self.inner();
+        if inner.verifier.is_some() && inner.state != LockState::Locked {
+            inner.state = LockState::Locked;
+            println!("🔒 Vault locked: {}", reason);
+        }
+    }
+
+    /// Lock if nothing used the vault for the auto-lock period
+    pub fn lock_if_idle(&self, now: Instant) {
+        let Some(auto_lock) = self.auto_lock else {
+            return;
+        };
+        let idle = match self.inner().state {
+            LockState::Unlocked { last_activity } => now.saturating_duration_since(last_activity),
+            LockState::Locked => return,
+        };
+        if idle >= auto_lock {
+            self.lock_for("idle timeout");
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, LockInner> {
+        // The state is a plain enum; a panic elsewhere can't leave it half-written
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Check for idle time every so often, forever
+pub async fn run_auto_lock(lock: std::sync::Arc<VaultLock>) {
+    let Some(auto_lock) = lock.auto_lock() else {
+        return;
+    };
+    let period = (auto_lock / 4).clamp(Duration::from_millis(10), Duration::from_secs(30));
+    let mut ticker = tokio::time::interval(period);
+    loop {
+        ticker.tick().await;
+        lock.lock_if_idle(Instant::now());
+    }
+}
+
+/// Lock when the machine suspends or the user's session locks
+///
+/// Listens to logind (`PrepareForSleep`, the session's `Lock`) on the
+/// system bus and to the freedesktop screensaver on the session bus. Each
+/// source is optional; missing buses are logged and skipped.
+#[cfg(target_os = "linux")]
+pub async fn watch_session_events(lock: std::sync::Arc<VaultLock>) {
+    session_events::watch(lock).await
+}
+
+/// Suspend and screen-lock events aren't wired up on this platform yet
+#[cfg(not(target_os = "linux"))]
+pub async fn watch_session_events(_lock: std::sync::Arc<VaultLock>) {}
+
+#[cfg(target_os = "linux")]
+mod session_events {
+    use super::VaultLock;
+    use futures_util::StreamExt;
+    use std::sync::Arc;
+    use zbus::zvariant::OwnedObjectPath;
+    use zbus::{Connection, MatchRule, MessageStream, MessageType};
+
+    const LOGIND: &str = "org.freedesktop.login1";
+    const LOGIND_PATH: &str = "/org/freedesktop/login1";
+    const LOGIND_MANAGER: &str = "org.freedesktop.login1.Manager";
+
+    pub async fn watch(lock: Arc<VaultLock>) {
+        let system = async {
+            match Connection::system().await {
+                Ok(conn) => {
+                    let session = our_session(&conn).await;
+                    tokio::join!(
+                        watch_signal(&conn, LOGIND_MANAGER, "PrepareForSleep", None, "suspend", &lock),
+                        async {
+                            match session {
+                                Some(path) => {
+                                    watch_signal(&conn, "org.freedesktop.login1.Session", "Lock", Some(path), "session locked", &lock)
+                                        .await
+                                }
+                                None => println!("ℹ️ Not in a logind session; lock-on-session-lock disabled"),
+                            }
+                        }
+                    );
+                }
+                Err(e) => println!("ℹ️ System bus unavailable; lock-on-suspend disabled: {}", e),
+            }
+        };
+        let session = async {
+            match Connection::session().await {
+                Ok(conn) => {
+                    watch_signal(&conn, "org.freedesktop.ScreenSaver", "ActiveChanged", None, "screen locked", &lock)
+                        .await
+                }
+                Err(e) => println!("ℹ️ Session bus unavailable; lock-on-screen-lock disabled: {}", e),
+            }
+        };
+        tokio::join!(system, session);
+    }
+
+    /// The logind session the daemon runs in, if any
+    async fn our_session(conn: &Connection) -> Option<OwnedObjectPath> {
+        let reply = conn
+            .call_method(Some(LOGIND), LOGIND_PATH, Some(LOGIND_MANAGER), "GetSessionByPID", &(std::process::id(),))
+            .await
+            .ok()?;
+        reply.body().ok()
+    }
+
+    /// Lock on every `member` signal, ignoring those whose boolean argument is `false`
+    async fn watch_signal(
+        conn: &Connection,
+        interface: &'static str,
+        member: &'static str,
+        path: Option<OwnedObjectPath>,
+        reason: &'static str,
+        lock: &VaultLock,
+    ) {
+        let mut stream = match signal_stream(conn, interface, member, path).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("ℹ️ Can't watch {}.{}: {}", interface, member, e);
+                return;
+            }
+        };
+        while let Some(message) = stream.next().await {
+            let Ok(message) = message else { continue };
+            // `PrepareForSleep(true)` / `ActiveChanged(true)`; the session `Lock` signal has no body
+            if message.body::<bool>().unwrap_or(true) {
+                lock.lock_for(reason);
+            }
+        }
+    }
+
+    async fn signal_stream(
+        conn: &Connection,
+        interface: &'static str,
+        member: &'static str,
+        path: Option<OwnedObjectPath>,
+    ) -> zbus::Result<MessageStream> {
+        let mut rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface(interface)?
+            .member(member)?;
+        if let Some(path) = path {
+            rule = rule.path(path)?;
+        }
+        MessageStream::for_match_rule(rule.build(), conn, None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault_lock(path: Option<PathBuf>) -> VaultLock {
+        VaultLock::open(path).unwrap().with_kdf_params(KeyDerivationParams::fast())
+    }
+
+    #[test]
+    fn test_set_passphrase_then_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCK_FILE_NAME);
+
+        let lock = vault_lock(Some(path.clone()));
+        assert!(!lock.is_locked());
+        assert!(!lock.lock());
+        assert!(matches!(lock.unlock(b"correct horse"), Err(VaultError::NoPassphrase)));
+        assert!(!lock.passphrase_set());
+        lock.set_passphrase(b"correct horse").unwrap();
+        assert!(!lock.is_locked());
+        assert!(matches!(lock.set_passphrase(b"other"), Err(VaultError::PassphraseSet)));
+        assert!(lock.lock());
+        assert!(matches!(lock.touch(), Err(VaultError::Locked)));
+
+        // A restarted daemon starts locked
+        let lock = vault_lock(Some(path));
+        assert!(lock.is_locked());
+        assert!(matches!(lock.unlock(b"wrong"), Err(VaultError::WrongPassphrase)));
+        assert!(lock.is_locked());
+        lock.unlock(b"correct horse").unwrap();
+        lock.touch().unwrap();
+    }
+
+    #[test]
+    fn test_wrong_passphrases_back_off() {
+        assert_eq!(unlock_backoff(FREE_UNLOCK_ATTEMPTS), None);
+        assert_eq!(unlock_backoff(FREE_UNLOCK_ATTEMPTS + 1), Some(UNLOCK_BACKOFF));
+        assert_eq!(unlock_backoff(FREE_UNLOCK_ATTEMPTS + 3), Some(UNLOCK_BACKOFF * 4));
+        assert_eq!(unlock_backoff(u32::MAX), Some(MAX_UNLOCK_BACKOFF));
+
+        let lock = vault_lock(None);
+        lock.set_passphrase(b"passphrase").unwrap();
+        assert!(lock.lock());
+        for _ in 0..=FREE_UNLOCK_ATTEMPTS {
+            assert!(matches!(lock.unlock(b"guess"), Err(VaultError::WrongPassphrase)));
+        }
+        // Even the right passphrase waits out the backoff
+        assert!(matches!(lock.unlock(b"passphrase"), Err(VaultError::RetryLater(_))));
+        assert!(lock.is_locked());
+
+        lock.inner().retry_after = Some(Instant::now());
+        lock.unlock(b"passphrase").unwrap();
+        assert_eq!(lock.inner().failed_unlocks, 0);
+    }
+
+    #[test]
+    fn test_idle_vault_locks() {
+        let lock = vault_lock(None).with_auto_lock(Some(Duration::from_secs(60)));
+        lock.set_passphrase(b"passphrase").unwrap();
+
+        lock.lock_if_idle(Instant::now() + Duration::from_secs(30));
+        assert!(!lock.is_locked());
+        lock.lock_if_idle(Instant::now() + Duration::from_secs(61));
+        assert!(lock.is_locked());
+    }
+
+    #[test]
+    fn test_no_auto_lock_without_passphrase() {
+        let lock = vault_lock(None).with_auto_lock(Some(Duration::ZERO));
+
+        lock.lock_if_idle(Instant::now());
+        lock.lock_for("suspend");
+        assert!(!lock.is_locked());
+    }
+}
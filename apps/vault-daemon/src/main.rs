@@ -1,28 +1,37 @@
 use anyhow::Result;
-use vault_daemon::VaultServer;
+use clap::Parser;
+use vault_daemon::shutdown::termination_signal;
+use vault_daemon::{CliArgs, DaemonConfig, VaultServer};
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
+    // Validate the config and move logs before the runtime starts any threads
+    let config = DaemonConfig::load(&CliArgs::parse())?;
+    config.redirect_logs()?;
+
+    tokio::runtime::Runtime::new()?.block_on(run(config))
+}
+
+async fn run(config: DaemonConfig) -> Result<()> {
     println!("🔐 Identra Vault Daemon starting...");
     println!("📍 Local secure storage initialized");
-    println!("🔑 OS Keychain integration active");
     
     // Initialize IPC server
-    let server = VaultServer::new();
+    let server = VaultServer::from_config(&config)?;
+    
+    // Ctrl-C / SIGTERM start the same graceful shutdown as a Shutdown request
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(async move {
+        termination_signal().await;
+        println!("\n🛑 Shutdown signal received");
+        shutdown.trigger();
+    });
     
-    // Start listening for IPC connections
-    // This will block until shutdown signal
-    tokio::select! {
-        result = server.start() => {
-            if let Err(e) = result {
-                eprintln!("❌ Server error: {}", e);
-            }
-        }
-        _ = tokio::signal::ctrl_c() => {
-            println!("\n🛑 Shutdown signal received");
-        }
+    // Serve until shutdown, draining open connections before returning
+    if let Err(e) = server.start().await {
+        eprintln!("❌ Server error: {}", e);
+        return Err(e.into());
     }
     
-    println!("🛑 Shutting down Vault Daemon...");
+    println!("🛑 Vault Daemon stopped");
     Ok(())
 }
@@ -1,122 +1,240 @@
-use crate::error::Result;
+use crate::error::{Result, VaultError};
+use region::Protection;
 use secrecy::Secret;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use zeroize::Zeroize;
 
-/// Secure memory container that locks pages and zeros on drop
+/// Bytes currently mlocked by all [`SecureMemory`] regions
+static LOCKED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Set once the "not locked" warning has been printed
+static LOCK_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Fixed-size secret buffer in its own page-aligned mapping
+///
+/// The data pages sit between two inaccessible guard pages, so running off
+/// either end faults instead of reading a neighbour. They are left out of
+/// core dumps (Linux) and mlocked while the process's `RLIMIT_MEMLOCK`
+/// budget lasts; past it, or if locking fails, they stay unlocked with a
+/// warning instead of failing the allocation. The buffer never grows or
+/// moves, and is zeroed before it is unmapped.
 pub struct SecureMemory {
-    data: Vec<u8>,
-    locked: bool,
+    /// Declared before `allocation` so the pages are unlocked before they're unmapped
+    lock: Option<PageLock>,
+    /// Guard page, data pages, guard page
+    allocation: region::Allocation,
+    len: usize,
+}
+
+/// An mlock on the data pages, counted in [`LOCKED_BYTES`] while it lives
+struct PageLock {
+    _guard: region::LockGuard,
+    len: usize,
+}
+
+impl Drop for PageLock {
+    fn drop(&mut self) {
+        LOCKED_BYTES.fetch_sub(self.len, Ordering::SeqCst);
+    }
 }
 
+// SAFETY: the mapping is owned by this value alone and only reached through `&self`/`&mut self`
+unsafe impl Send for SecureMemory {}
+unsafe impl Sync for SecureMemory {}
+
 impl SecureMemory {
-    /// Create new secure memory region
+    /// Zeroed region of `size` bytes
     pub fn new(size: usize) -> Result<Self> {
-        let data = vec![0u8; size];
-        
-        // Lock memory pages to prevent swapping to disk
-        let locked = Self::lock_memory(&data);
-        
-        Ok(Self { data, locked })
-    }
-    
-    /// Create from existing data (will be zeroized in source)
-    pub fn from_vec(data: Vec<u8>) -> Result<Self> {
-        let locked = Self::lock_memory(&data);
-        Ok(Self { data, locked })
-    }
-    
-    /// Lock memory pages (platform-specific)
-    fn lock_memory(data: &[u8]) -> bool {
-        #[cfg(windows)]
-        {
-            // Lock the memory pages
-            unsafe {
-                protect(
-                    data.as_ptr(),
-                    data.len(),
-                    Protection::READ_WRITE,
-                ).is_ok()
-            }
-        }
-        
-        #[cfg(not(windows))]
-        {
-            // On Unix, use mlock
-            unsafe {
-                libc::mlock(data.as_ptr() as *const libc::c_void, data.len()) == 0
-            }
-        }
+        let page = region::page::size();
+        let data_len = size.div_ceil(page).max(1) * page;
+        let mut allocation = region::alloc(data_len + 2 * page, Protection::READ_WRITE).map_err(memory_error)?;
+
+        let base = allocation.as_mut_ptr::<u8>();
+        // SAFETY: both guard pages lie inside the allocation, and nothing points into them
+        let data = unsafe {
+            region::protect(base, page, Protection::NONE).map_err(memory_error)?;
+            region::protect(base.add(page + data_len), page, Protection::NONE).map_err(memory_error)?;
+            base.add(page)
+        };
+        exclude_from_core_dumps(data, data_len);
+
+        Ok(Self {
+            lock: lock_pages(data, data_len),
+            allocation,
+            len: size,
+        })
     }
-    
-    /// Get immutable reference to data
+
+    /// Copy `bytes` into a new region
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let mut memory = Self::new(bytes.len())?;
+        memory.as_mut_slice().copy_from_slice(bytes);
+        Ok(memory)
+    }
+
+    /// Move `data` into a new region; the vector is zeroized either way
+    pub fn from_vec(mut data: Vec<u8>) -> Result<Self> {
+        let memory = Self::from_slice(&data);
+        data.zeroize();
+        memory
+    }
+
     pub fn as_slice(&self) -> &[u8] {
-        &self.data
+        // SAFETY: the data pages start one page in, are readable and hold at least `len` bytes
+        unsafe { std::slice::from_raw_parts(self.allocation.as_ptr::<u8>().add(region::page::size()), self.len) }
     }
-    
-    /// Get mutable reference to data
+
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
-        &mut self.data
+        let page = region::page::size();
+        // SAFETY: as in `as_slice`, and `&mut self` makes the access exclusive
+        unsafe { std::slice::from_raw_parts_mut(self.allocation.as_mut_ptr::<u8>().add(page), self.len) }
     }
-    
-    /// Get length of secure memory
+
     pub fn len(&self) -> usize {
-        self.data.len()
+        self.len
     }
-    
-    /// Check if empty
+
     pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+        self.len == 0
+    }
+
+    /// Whether the data pages are mlocked
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
     }
 }
 
 impl Drop for SecureMemory {
     fn drop(&mut self) {
-        // Zero out memory before dropping
-        self.data.zeroize();
-        
-        // Unlock memory if it was locked
-        if self.locked {
-            #[cfg(windows)]
-            {
-                // Windows automatically unlocks on drop
-            }
-            
-            #[cfg(not(windows))]
-            {
-                unsafe {
-                    libc::munlock(self.data.as_ptr() as *const libc::c_void, self.data.len());
-                }
+        self.as_mut_slice().zeroize();
+    }
+}
+
+impl fmt::Debug for SecureMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecureMemory")
+            .field("len", &self.len)
+            .field("locked", &self.is_locked())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Bytes of secure memory currently locked into RAM
+pub fn locked_bytes() -> usize {
+    LOCKED_BYTES.load(Ordering::SeqCst)
+}
+
+/// Mlock the data pages if the `RLIMIT_MEMLOCK` budget has room
+fn lock_pages(data: *const u8, len: usize) -> Option<PageLock> {
+    // Reserve first, so concurrent allocations can't overshoot the limit together
+    let reserved = LOCKED_BYTES.fetch_add(len, Ordering::SeqCst) + len;
+    let result = match memlock_limit() {
+        Some(limit) if reserved > limit => Err(format!("{} of {} bytes already locked", reserved - len, limit)),
+        _ => region::lock(data, len).map_err(|e| e.to_string()),
+    };
+    match result {
+        Ok(guard) => Some(PageLock { _guard: guard, len }),
+        Err(reason) => {
+            LOCKED_BYTES.fetch_sub(len, Ordering::SeqCst);
+            if !LOCK_WARNED.swap(true, Ordering::SeqCst) {
+                eprintln!("⚠️ Secure memory not locked ({}); secrets may reach swap", reason);
             }
+            None
         }
     }
 }
 
+/// The soft `RLIMIT_MEMLOCK`, or `None` if unlimited or unknown
+#[cfg(unix)]
+fn memlock_limit() -> Option<usize> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: getrlimit only writes the struct it is given
+    if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } != 0 || limit.rlim_cur == libc::RLIM_INFINITY {
+        return None;
+    }
+    usize::try_from(limit.rlim_cur).ok()
+}
+
+/// Windows bounds locking by working-set size instead; `VirtualLock` reports when it runs out
+#[cfg(not(unix))]
+fn memlock_limit() -> Option<usize> {
+    None
+}
+
+/// Keep the pages out of core dumps
+#[cfg(target_os = "linux")]
+fn exclude_from_core_dumps(data: *mut u8, len: usize) {
+    // SAFETY: the range is a page-aligned part of a live mapping
+    if unsafe { libc::madvise(data.cast(), len, libc::MADV_DONTDUMP) } != 0 {
+        eprintln!("⚠️ Cannot exclude secure memory from core dumps: {}", std::io::Error::last_os_error());
+    }
+}
+
+/// No per-mapping core dump opt-out on this platform
+#[cfg(not(target_os = "linux"))]
+fn exclude_from_core_dumps(_data: *mut u8, _len: usize) {}
+
+fn memory_error(e: region::Error) -> VaultError {
+    VaultError::MemoryLock(format!("Failed to map secure memory: {}", e))
+}
+
 /// Secure string wrapper
 pub type SecureString = Secret<String>;
 
-/// Secure bytes wrapper  
+/// Secure bytes wrapper
 pub type SecureBytes = Secret<Vec<u8>>;
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
     fn test_secure_memory_creation() {
         let mem = SecureMemory::new(32).unwrap();
         assert_eq!(mem.len(), 32);
+        assert!(mem.as_slice().iter().all(|&b| b == 0));
+        assert!(SecureMemory::new(0).unwrap().is_empty());
     }
-    
+
     #[test]
     fn test_secure_memory_zeroization() {
         let data = vec![1, 2, 3, 4, 5];
         let mut mem = SecureMemory::from_vec(data).unwrap();
-        
+
         // Modify data
         mem.as_mut_slice()[0] = 99;
-        assert_eq!(mem.as_slice()[0], 99);
-        
+        assert_eq!(mem.as_slice(), [99, 2, 3, 4, 5]);
+
         // Drop will zeroize
         drop(mem);
     }
+
+    #[test]
+    fn test_data_is_page_aligned_between_guard_pages() {
+        let page = region::page::size();
+        let mem = SecureMemory::from_slice(&vec![7u8; page + 1]).unwrap();
+        let data = mem.as_slice().as_ptr();
+
+        assert_eq!(data as usize % page, 0);
+        let before = region::query(data.wrapping_sub(page)).unwrap();
+        let after = region::query(data.wrapping_add(2 * page)).unwrap();
+        assert_eq!(before.protection(), Protection::NONE);
+        assert_eq!(after.protection(), Protection::NONE);
+        assert_eq!(region::query(data).unwrap().protection(), Protection::READ_WRITE);
+    }
+
+    #[test]
+    fn test_locked_pages_are_accounted() {
+        let mem = SecureMemory::new(1).unwrap();
+        if mem.is_locked() {
+            assert!(locked_bytes() >= region::page::size());
+        }
+        // Falling back to unlocked memory is allowed, failing is not
+        let many: Vec<_> = (0..64).map(|_| SecureMemory::new(4096).unwrap()).collect();
+        assert!(many.iter().all(|mem| mem.len() == 4096));
+    }
 }
@@ -0,0 +1,142 @@
+use crate::error::{Result, VaultError};
+use crate::memory::SecureMemory;
+use identra_crypto::{mac, CipherSuiteId, IdentityKeyPair, MemoryVault, SecretBytes, SecretKey, SigningKeyPair, VaultKey};
+use identra_ipc::KeyAlgorithm;
+
+/// Associated data for keys wrapped by [`wrap_key`]
+const WRAP_AAD: &[u8] = b"identra-key-wrap-v1";
+
+/// Fresh key material for `algorithm`, with the public key of a keypair
+pub fn generate(algorithm: KeyAlgorithm) -> Result<(SecureMemory, Option<Vec<u8>>)> {
+    Ok(match algorithm {
+        KeyAlgorithm::Symmetric => (SecureMemory::from_slice(SecretKey::generate().as_bytes())?, None),
+        KeyAlgorithm::Ed25519 => {
+            let keypair = SigningKeyPair::generate();
+            let secret = SecureMemory::from_slice(keypair.secret_key().as_bytes())?;
+            (secret, Some(keypair.public_key().to_vec()))
+        }
+        KeyAlgorithm::X25519 => {
+            let keypair = IdentityKeyPair::generate();
+            let secret = SecureMemory::from_slice(keypair.secret_key().as_bytes())?;
+            (secret, Some(keypair.public_key().as_bytes().to_vec()))
+        }
+    })
+}
+
+/// Encrypt under a stored key, producing a binary envelope
+pub fn encrypt(
+    key: &SecureMemory,
+    algorithm: KeyAlgorithm,
+    suite: CipherSuiteId,
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>> {
+    MemoryVault::seal_bytes_with_suite(suite, plaintext, &vault_key(key, algorithm)?, aad).map_err(encryption_error)
+}
+
+pub fn decrypt(key: &SecureMemory, algorithm: KeyAlgorithm, ciphertext: &[u8], aad: &[u8]) -> Result<SecretBytes> {
+    MemoryVault::open_bytes(ciphertext, &vault_key(key, algorithm)?, aad)
+        .map(SecretBytes::new)
+        .map_err(encryption_error)
+}
+
+/// HMAC-SHA256 tag with a symmetric key (any length works), Ed25519 signature with an Ed25519 key
+pub fn sign(key: &SecureMemory, algorithm: KeyAlgorithm, message: &[u8]) -> Result<Vec<u8>> {
+    match algorithm {
+        KeyAlgorithm::Symmetric => Ok(mac::sign(key.as_slice(), message).to_vec()),
+        KeyAlgorithm::Ed25519 => Ok(signing_key(key)?.sign(message).to_vec()),
+        KeyAlgorithm::X25519 => Err(unsupported(algorithm, "sign")),
+    }
+}
+
+pub fn verify(key: &SecureMemory, algorithm: KeyAlgorithm, message: &[u8], signature: &[u8]) -> Result<bool> {
+    match algorithm {
+        KeyAlgorithm::Symmetric => Ok(mac::verify(key.as_slice(), message, signature)),
+        KeyAlgorithm::Ed25519 => Ok(signing_key(key)?.verify(message, signature)),
+        KeyAlgorithm::X25519 => Err(unsupported(algorithm, "verify")),
+    }
+}
+
+/// Encrypt `key` under `wrapping_key`; keys of any length can be wrapped
+pub fn wrap_key(wrapping_key: &SecureMemory, algorithm: KeyAlgorithm, key: &SecureMemory) -> Result<Vec<u8>> {
+    MemoryVault::seal_bytes(key.as_slice(), &vault_key(wrapping_key, algorithm)?, WRAP_AAD).map_err(encryption_error)
+}
+
+pub fn unwrap_key(wrapping_key: &SecureMemory, algorithm: KeyAlgorithm, wrapped: &[u8]) -> Result<SecureMemory> {
+    SecureMemory::from_slice(decrypt(wrapping_key, algorithm, wrapped, WRAP_AAD)?.as_bytes())
+}
+
+/// Stored keys used for encryption must be symmetric and 256-bit
+fn vault_key(key: &SecureMemory, algorithm: KeyAlgorithm) -> Result<VaultKey> {
+    if algorithm != KeyAlgorithm::Symmetric {
+        return Err(unsupported(algorithm, "encrypt"));
+    }
+    MemoryVault::key_from_bytes(key.as_slice())
+        .map_err(|_| VaultError::Encryption(format!("Encryption keys must be 32 bytes, got {}", key.len())))
+}
+
+fn signing_key(key: &SecureMemory) -> Result<SigningKeyPair> {
+    SigningKeyPair::from_secret_bytes(key.as_slice()).map_err(encryption_error)
+}
+
+fn unsupported(algorithm: KeyAlgorithm, operation: &str) -> VaultError {
+    VaultError::Encryption(format!("{} keys can't {}", algorithm.as_str(), operation))
+}
+
+fn encryption_error(e: identra_crypto::CryptoError) -> VaultError {
+    VaultError::Encryption(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMMETRIC: KeyAlgorithm = KeyAlgorithm::Symmetric;
+
+    fn key(byte: u8) -> SecureMemory {
+        SecureMemory::from_slice(&[byte; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let ciphertext = encrypt(&key(1), SYMMETRIC, CipherSuiteId::default(), b"memory", b"row-1").unwrap();
+
+        assert_eq!(decrypt(&key(1), SYMMETRIC, &ciphertext, b"row-1").unwrap().as_bytes(), b"memory");
+        assert!(decrypt(&key(2), SYMMETRIC, &ciphertext, b"row-1").is_err());
+        assert!(decrypt(&key(1), SYMMETRIC, &ciphertext, b"row-2").is_err());
+        assert!(encrypt(&SecureMemory::from_slice(b"short").unwrap(), SYMMETRIC, CipherSuiteId::default(), b"memory", b"").is_err());
+        assert!(encrypt(&key(1), KeyAlgorithm::Ed25519, CipherSuiteId::default(), b"memory", b"").is_err());
+
+        // The suite is read back from the envelope
+        let ciphertext = encrypt(&key(1), SYMMETRIC, CipherSuiteId::ChaCha20Poly1305, b"memory", b"").unwrap();
+        assert_eq!(decrypt(&key(1), SYMMETRIC, &ciphertext, b"").unwrap().as_bytes(), b"memory");
+    }
+
+    #[test]
+    fn test_wrap_unwrap() {
+        let secret = SecureMemory::from_slice(b"an api token of any length").unwrap();
+        let wrapped = wrap_key(&key(1), SYMMETRIC, &secret).unwrap();
+
+        assert_eq!(unwrap_key(&key(1), SYMMETRIC, &wrapped).unwrap().as_slice(), secret.as_slice());
+        assert!(unwrap_key(&key(2), SYMMETRIC, &wrapped).is_err());
+        // Wrapped keys aren't interchangeable with ordinary ciphertexts
+        assert!(decrypt(&key(1), SYMMETRIC, &wrapped, b"").is_err());
+    }
+
+    #[test]
+    fn test_generated_keypairs() {
+        let (seed, public_key) = generate(KeyAlgorithm::Ed25519).unwrap();
+        let signature = sign(&seed, KeyAlgorithm::Ed25519, b"message").unwrap();
+        assert!(identra_crypto::verify_signature(&public_key.unwrap(), b"message", &signature).unwrap());
+        assert!(verify(&seed, KeyAlgorithm::Ed25519, b"message", &signature).unwrap());
+        assert!(!verify(&seed, SYMMETRIC, b"message", &signature).unwrap());
+
+        let (secret, public_key) = generate(KeyAlgorithm::X25519).unwrap();
+        let identity = IdentityKeyPair::from_secret_bytes(secret.as_slice()).unwrap();
+        assert_eq!(public_key.unwrap(), identity.public_key().as_bytes());
+        assert!(sign(&secret, KeyAlgorithm::X25519, b"message").is_err());
+
+        let (key, public_key) = generate(SYMMETRIC).unwrap();
+        assert_eq!((key.len(), public_key), (32, None));
+    }
+}
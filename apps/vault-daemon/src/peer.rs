@@ -0,0 +1,195 @@
+use interprocess::local_socket::tokio::{prelude::*, Stream};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Comma-separated UIDs allowed to connect (replaces the default of the daemon's own UID)
+pub const ALLOWED_UIDS_ENV: &str = "IDENTRA_VAULT_ALLOWED_UIDS";
+
+/// Executables allowed to connect, separated like `PATH`
+pub const ALLOWED_EXES_ENV: &str = "IDENTRA_VAULT_ALLOWED_EXES";
+
+/// The process on the other end of a connection, as reported by the kernel
+#[derive(Debug, Clone, Default)]
+pub struct PeerInfo {
+    pub pid: Option<u32>,
+    pub uid: Option<u32>,
+    pub executable: Option<PathBuf>,
+}
+
+impl PeerInfo {
+    /// Read the peer's credentials (`SO_PEERCRED` on Linux)
+    pub fn of(stream: &Stream) -> io::Result<Self> {
+        let creds = stream.peer_creds()?;
+        let pid = creds.pid().and_then(|pid| u32::try_from(pid).ok());
+
+        #[cfg(unix)]
+        let uid = creds.euid();
+        #[cfg(not(unix))]
+        let uid = None;
+
+        Ok(Self {
+            pid,
+            uid,
+            executable: pid.and_then(executable_of),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn executable_of(pid: u32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/exe", pid)).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn executable_of(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+/// Which local processes may talk to the daemon
+///
+/// Checked before the token handshake, so a rejected process never learns
+/// anything beyond the refusal.
+#[derive(Debug, Clone)]
+pub struct PeerPolicy {
+    /// `None` accepts any UID
+    allowed_uids: Option<Vec<u32>>,
+    /// Empty accepts any executable
+    allowed_executables: Vec<PathBuf>,
+}
+
+impl PeerPolicy {
+    /// Only processes running as the daemon's own user
+    ///
+    /// On Windows UIDs aren't available; the named pipe's default ACL
+    /// already limits access to the current user.
+    pub fn current_user() -> Self {
+        #[cfg(unix)]
+        let allowed_uids = Some(vec![unsafe { libc::geteuid() }]);
+        #[cfg(not(unix))]
+        let allowed_uids = None;
+
+        Self {
+            allowed_uids,
+            allowed_executables: Vec::new(),
+        }
+    }
+
+    /// Any user and executable; only the token handshake applies
+    pub fn any_user() -> Self {
+        Self {
+            allowed_uids: None,
+            allowed_executables: Vec::new(),
+        }
+    }
+
+    /// The default policy with `IDENTRA_VAULT_ALLOWED_UIDS` / `IDENTRA_VAULT_ALLOWED_EXES` applied
+    pub fn from_env() -> Self {
+        let mut policy = Self::current_user();
+
+        if let Ok(uids) = std::env::var(ALLOWED_UIDS_ENV) {
+            let uids: Vec<u32> = uids
+                .split(',')
+                .filter_map(|uid| {
+                    let uid = uid.trim();
+                    uid.parse()
+                        .map_err(|_| eprintln!("⚠️ Ignoring invalid UID in {}: {:?}", ALLOWED_UIDS_ENV, uid))
+                        .ok()
+                })
+                .collect();
+            policy.allowed_uids = Some(uids);
+        }
+
+        if let Some(exes) = std::env::var_os(ALLOWED_EXES_ENV) {
+            for exe in std::env::split_paths(&exes).filter(|exe| !exe.as_os_str().is_empty()) {
+                policy = policy.allow_executable(exe);
+            }
+        }
+
+        policy
+    }
+
+    /// Also accept `uid` (turns "any user" into an explicit list)
+    pub fn allow_uid(mut self, uid: u32) -> Self {
+        self.allowed_uids.get_or_insert_with(Vec::new).push(uid);
+        self
+    }
+
+    /// Restrict connections to the listed executables
+    pub fn allow_executable(mut self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        // Compare resolved paths, as the kernel reports them
+        self.allowed_executables
+            .push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+        self
+    }
+
+    /// Why `peer` may not connect, if it may not
+    pub fn check(&self, peer: &PeerInfo) -> Result<(), String> {
+        if let Some(allowed) = &self.allowed_uids {
+            match peer.uid {
+                Some(uid) if allowed.contains(&uid) => {}
+                Some(uid) => return Err(format!("UID {} is not allowed", uid)),
+                None => return Err("Peer UID unavailable".to_string()),
+            }
+        }
+
+        if !self.allowed_executables.is_empty() {
+            match &peer.executable {
+                Some(exe) if self.allowed_executables.contains(exe) => {}
+                Some(exe) => return Err(format!("Executable {} is not allowed", exe.display())),
+                None => return Err("Peer executable unavailable".to_string()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PeerPolicy {
+    fn default() -> Self {
+        Self::current_user()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(uid: Option<u32>, exe: Option<&str>) -> PeerInfo {
+        PeerInfo {
+            pid: Some(42),
+            uid,
+            executable: exe.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn test_uid_allow_list() {
+        let policy = PeerPolicy::any_user().allow_uid(1000);
+
+        assert!(policy.check(&peer(Some(1000), None)).is_ok());
+        assert!(policy.check(&peer(Some(1001), None)).is_err());
+        // Fail closed when the kernel can't tell us
+        assert!(policy.check(&peer(None, None)).is_err());
+        assert!(PeerPolicy::any_user().check(&peer(None, None)).is_ok());
+    }
+
+    #[test]
+    fn test_executable_allow_list() {
+        let policy = PeerPolicy::any_user().allow_executable("/opt/identra/tunnel-gateway");
+
+        assert!(policy.check(&peer(Some(0), Some("/opt/identra/tunnel-gateway"))).is_ok());
+        assert!(policy.check(&peer(Some(0), Some("/usr/bin/python3"))).is_err());
+        assert!(policy.check(&peer(Some(0), None)).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_current_user_policy() {
+        let me = unsafe { libc::geteuid() };
+        let policy = PeerPolicy::current_user();
+
+        assert!(policy.check(&peer(Some(me), None)).is_ok());
+        assert!(policy.check(&peer(Some(me.wrapping_add(1)), None)).is_err());
+    }
+}
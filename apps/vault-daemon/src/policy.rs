@@ -0,0 +1,313 @@
+use crate::error::Result;
+use crate::keychain::{KeyMetadata, KeyStorage};
+use crate::lock::VaultLock;
+use crate::peer::PeerInfo;
+use identra_ipc::{ErrorCode, KeyPolicy};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Prefix of policy entries that name the connecting executable
+pub const EXE_PREFIX: &str = "exe:";
+
+/// Prefix of policy entries that name the connecting user id
+pub const UID_PREFIX: &str = "uid:";
+
+/// Serializes use-counter updates, so concurrent requests can't overspend a key
+static USES: Mutex<()> = Mutex::new(());
+
+/// The identity a key policy is checked against
+///
+/// Only what the kernel reports about the peer counts; the name a client
+/// gives in its hello is self-declared, so policies can't refer to it.
+#[derive(Debug, Clone, Default)]
+pub struct Caller {
+    /// User id of the connecting process, when the platform reports it
+    pub uid: Option<u32>,
+    /// Executable of the connecting process, when the kernel reports it
+    pub executable: Option<PathBuf>,
+}
+
+/// One parsed policy entry
+#[derive(Debug, PartialEq, Eq)]
+enum Principal<'a> {
+    /// `exe:<path>`
+    Exe(&'a Path),
+    /// `uid:<n>`, or `uid:<n>:exe:<path>` to require both
+    Uid(u32, Option<&'a Path>),
+}
+
+impl<'a> Principal<'a> {
+    fn parse(entry: &'a str) -> Option<Self> {
+        if let Some(path) = entry.strip_prefix(EXE_PREFIX) {
+            return (!path.is_empty()).then(|| Self::Exe(Path::new(path)));
+        }
+        let rest = entry.strip_prefix(UID_PREFIX)?;
+        let (uid, exe) = match rest.split_once(':') {
+            Some((uid, exe)) => (uid, Some(exe.strip_prefix(EXE_PREFIX).filter(|path| !path.is_empty())?)),
+            None => (rest, None),
+        };
+        Some(Self::Uid(uid.parse().ok()?, exe.map(Path::new)))
+    }
+}
+
+/// Check that every entry of `policy`'s client lists is one the daemon can verify
+///
+/// Entries are `exe:<path>`, `uid:<n>` or `uid:<n>:exe:<path>`. Bare client
+/// names are refused rather than stored, as any process can claim any name.
+pub fn validate(policy: &KeyPolicy) -> std::result::Result<(), String> {
+    let lists = [&policy.readers, &policy.users, &policy.deleters];
+    match lists.into_iter().flatten().flatten().find(|entry| Principal::parse(entry).is_none()) {
+        Some(entry) => Err(format!(
+            "Policy entry {:?} must be {}<path>, {}<n> or {}<n>:{}<path>; client names are not verified",
+            entry, EXE_PREFIX, UID_PREFIX, UID_PREFIX, EXE_PREFIX
+        )),
+        None => Ok(()),
+    }
+}
+
+impl Caller {
+    pub fn new(peer: &PeerInfo) -> Self {
+        Self {
+            uid: peer.uid,
+            executable: peer.executable.clone(),
+        }
+    }
+
+    /// Whether one policy entry names this caller; entries that don't parse match no one
+    fn matches(&self, principal: &str) -> bool {
+        let exe_matches = |path: &Path| self.executable.as_deref() == Some(path);
+        match Principal::parse(principal) {
+            Some(Principal::Exe(path)) => exe_matches(path),
+            Some(Principal::Uid(uid, exe)) => self.uid == Some(uid) && exe.is_none_or(exe_matches),
+            None => false,
+        }
+    }
+
+    /// Whether a policy client list lets this caller in; `None` allows anyone
+    pub fn listed_in(&self, principals: &Option<Vec<String>>) -> bool {
+        principals.as_ref().is_none_or(|principals| principals.iter().any(|p| self.matches(p)))
+    }
+}
+
+/// What a request does with a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// The key's bytes leave the daemon
+    Read,
+    /// The daemon runs an operation with the key
+    Use,
+    /// The key is deleted or overwritten
+    Delete,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => "read",
+            Self::Use => "use",
+            Self::Delete => "delete",
+        })
+    }
+}
+
+/// Why a key's policy refused a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denied {
+    NotAllowed(Access),
+    NotExportable,
+    UsesExhausted,
+    ReauthRequired,
+}
+
+impl Denied {
+    pub fn code(self) -> ErrorCode {
+        match self {
+            Self::NotAllowed(_) | Self::NotExportable => ErrorCode::PermissionDenied,
+            Self::UsesExhausted => ErrorCode::UsesExhausted,
+            Self::ReauthRequired => ErrorCode::ReauthRequired,
+        }
+    }
+}
+
+impl fmt::Display for Denied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAllowed(access) => write!(f, "Not allowed to {} key", access),
+            Self::NotExportable => f.write_str("Key is not exportable"),
+            Self::UsesExhausted => f.write_str("Key has no uses left"),
+            Self::ReauthRequired => f.write_str("Unlock again to use key"),
+        }
+    }
+}
+
+/// Check `access` by `caller` against a key's policy
+///
+/// Reads and uses also need uses left and, if the policy asks for it, a
+/// recent unlock; deleting only needs the caller to be listed.
+pub fn authorize(
+    metadata: &KeyMetadata,
+    caller: &Caller,
+    access: Access,
+    lock: &VaultLock,
+) -> std::result::Result<(), Denied> {
+    let policy = &metadata.policy;
+    let listed = match access {
+        Access::Read => caller.listed_in(&policy.readers),
+        Access::Use => caller.listed_in(&policy.users),
+        Access::Delete => caller.listed_in(&policy.deleters),
+    };
+    if !listed {
+        return Err(Denied::NotAllowed(access));
+    }
+    if access == Access::Delete {
+        return Ok(());
+    }
+    if access == Access::Read && !policy.exportable {
+        return Err(Denied::NotExportable);
+    }
+    if policy.max_uses.is_some_and(|max_uses| metadata.uses >= max_uses) {
+        return Err(Denied::UsesExhausted);
+    }
+    if let Some(secs) = policy.reauth_secs {
+        if !lock.unlocked_within(Duration::from_secs(secs)) {
+            return Err(Denied::ReauthRequired);
+        }
+    }
+    Ok(())
+}
+
+/// Count one use of a key whose policy caps them, returning its updated metadata
+///
+/// Re-reads the key under a lock so the cap holds across connections;
+/// `Ok(None)` means the last use went to someone else first.
+pub fn record_use(storage: &dyn KeyStorage, key_id: &str) -> Result<Option<KeyMetadata>> {
+    let _guard = USES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (key, mut metadata) = storage.retrieve_key(key_id)?;
+    if metadata.policy.max_uses.is_some_and(|max_uses| metadata.uses >= max_uses) {
+        return Ok(None);
+    }
+    metadata.uses += 1;
+    storage.store_key(key_id, key.as_bytes(), metadata.clone())?;
+    Ok(Some(metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::MemoryKeyStorage;
+    use identra_crypto::KeyDerivationParams;
+
+    fn caller(uid: Option<u32>, executable: Option<&str>) -> Caller {
+        Caller {
+            uid,
+            executable: executable.map(PathBuf::from),
+        }
+    }
+
+    fn entries(entries: &[&str]) -> Option<Vec<String>> {
+        Some(entries.iter().map(|entry| entry.to_string()).collect())
+    }
+
+    fn metadata(policy: KeyPolicy) -> KeyMetadata {
+        KeyMetadata {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_client_lists() {
+        let lock = VaultLock::in_memory();
+        let key = metadata(KeyPolicy {
+            readers: entries(&["uid:1001:exe:/usr/bin/identra-backup"]),
+            users: entries(&["exe:/usr/bin/identra-desktop", "uid:1002"]),
+            deleters: Some(vec![]),
+            ..KeyPolicy::default()
+        });
+        let desktop = caller(Some(1000), Some("/usr/bin/identra-desktop"));
+        let backup = caller(Some(1001), Some("/usr/bin/identra-backup"));
+        let impostor = caller(Some(1000), Some("/usr/bin/identra-backup"));
+
+        assert_eq!(authorize(&key, &backup, Access::Read, &lock), Ok(()));
+        assert_eq!(authorize(&key, &impostor, Access::Read, &lock), Err(Denied::NotAllowed(Access::Read)));
+        assert_eq!(authorize(&key, &desktop, Access::Read, &lock), Err(Denied::NotAllowed(Access::Read)));
+        assert_eq!(authorize(&key, &desktop, Access::Use, &lock), Ok(()));
+        assert_eq!(authorize(&key, &caller(Some(1002), None), Access::Use, &lock), Ok(()));
+        assert_eq!(authorize(&key, &backup, Access::Use, &lock), Err(Denied::NotAllowed(Access::Use)));
+        assert_eq!(authorize(&key, &desktop, Access::Delete, &lock), Err(Denied::NotAllowed(Access::Delete)));
+        assert_eq!(authorize(&metadata(KeyPolicy::default()), &desktop, Access::Delete, &lock), Ok(()));
+    }
+
+    #[test]
+    fn test_client_names_are_not_principals() {
+        let named = KeyPolicy {
+            users: entries(&["exe:/usr/bin/identra-desktop", "backup-tool"]),
+            ..KeyPolicy::default()
+        };
+        assert!(validate(&named).unwrap_err().contains("backup-tool"));
+        for malformed in ["exe:", "uid:me", "uid:1000:", "uid:1000:/bin/sh"] {
+            let policy = KeyPolicy { readers: entries(&[malformed]), ..KeyPolicy::default() };
+            assert!(validate(&policy).is_err(), "{}", malformed);
+        }
+        let verified = KeyPolicy {
+            readers: entries(&["uid:0"]),
+            users: entries(&["exe:/usr/bin/identra-desktop", "uid:1000:exe:/usr/bin/identra-desktop"]),
+            ..KeyPolicy::default()
+        };
+        validate(&verified).unwrap();
+        validate(&KeyPolicy::default()).unwrap();
+
+        // Policies stored before names were refused match no one
+        let key = metadata(named);
+        assert_eq!(authorize(&key, &caller(None, None), Access::Use, &VaultLock::in_memory()), Err(Denied::NotAllowed(Access::Use)));
+    }
+
+    #[test]
+    fn test_export_uses_and_reauth() {
+        let lock = VaultLock::in_memory().with_kdf_params(KeyDerivationParams::fast());
+        let anyone = caller(None, None);
+
+        let sealed = metadata(KeyPolicy {
+            exportable: false,
+            ..KeyPolicy::default()
+        });
+        assert_eq!(authorize(&sealed, &anyone, Access::Read, &lock), Err(Denied::NotExportable));
+        assert_eq!(authorize(&sealed, &anyone, Access::Use, &lock), Ok(()));
+
+        let mut capped = metadata(KeyPolicy {
+            max_uses: Some(1),
+            ..KeyPolicy::default()
+        });
+        capped.uses = 1;
+        assert_eq!(authorize(&capped, &anyone, Access::Use, &lock), Err(Denied::UsesExhausted));
+        assert_eq!(authorize(&capped, &anyone, Access::Delete, &lock), Ok(()));
+
+        let fresh = metadata(KeyPolicy {
+            reauth_secs: Some(60),
+            ..KeyPolicy::default()
+        });
+        assert_eq!(authorize(&fresh, &anyone, Access::Use, &lock), Err(Denied::ReauthRequired));
+        lock.set_passphrase(b"passphrase").unwrap();
+        assert_eq!(authorize(&fresh, &anyone, Access::Use, &lock), Ok(()));
+        lock.lock();
+        assert_eq!(authorize(&fresh, &anyone, Access::Use, &lock), Err(Denied::ReauthRequired));
+    }
+
+    #[test]
+    fn test_record_use_stops_at_cap() {
+        let storage = MemoryKeyStorage::new();
+        let policy = KeyPolicy {
+            max_uses: Some(2),
+            ..KeyPolicy::default()
+        };
+        storage.store_key("otp", b"secret", metadata(policy)).unwrap();
+
+        assert_eq!(record_use(&storage, "otp").unwrap().unwrap().uses, 1);
+        assert_eq!(record_use(&storage, "otp").unwrap().unwrap().uses, 2);
+        assert!(record_use(&storage, "otp").unwrap().is_none());
+        let (key, metadata) = storage.retrieve_key("otp").unwrap();
+        assert_eq!((key.as_bytes(), metadata.uses), (&b"secret"[..], 2));
+    }
+}
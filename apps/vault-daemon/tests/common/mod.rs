@@ -0,0 +1,70 @@
+//! Harness running a `VaultServer` on a private socket with in-memory storage
+
+use identra_crypto::SecretBytes;
+use identra_ipc::{IpcError, VaultClient};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use vault_daemon::{MemoryKeyStorage, PeerPolicy, VaultServer};
+
+/// Install token shared by every test daemon
+pub const TOKEN: &[u8] = &[3u8; 32];
+
+/// A daemon listening on a socket unique to one test
+pub struct TestDaemon {
+    pub socket: String,
+    server: JoinHandle<vault_daemon::Result<()>>,
+}
+
+impl TestDaemon {
+    /// Daemon accepting only the current user
+    pub fn start(test: &str) -> Self {
+        Self::with_policy(test, PeerPolicy::current_user())
+    }
+
+    pub fn with_policy(test: &str, policy: PeerPolicy) -> Self {
+        Self::start_with(test, |server| server.with_policy(policy))
+    }
+
+    /// Daemon for the current user, further set up by `configure`
+    pub fn start_with(test: &str, configure: impl FnOnce(VaultServer) -> VaultServer) -> Self {
+        Self::start_on(socket_for(test), configure)
+    }
+
+    /// Daemon on an explicit socket name or path
+    pub fn start_on(socket: String, configure: impl FnOnce(VaultServer) -> VaultServer) -> Self {
+        let server = VaultServer::with_storage(Box::new(MemoryKeyStorage::new()), socket.clone())
+            .with_policy(PeerPolicy::current_user())
+            .with_token(SecretBytes::from_slice(TOKEN));
+        let server = configure(server);
+        let server = tokio::spawn(async move { server.start().await });
+        Self { socket, server }
+    }
+
+    /// What the daemon's `start` returned, once it has stopped
+    pub async fn stopped(self) -> vault_daemon::Result<()> {
+        tokio::time::timeout(Duration::from_secs(10), self.server)
+            .await
+            .expect("daemon did not stop")
+            .expect("daemon task panicked")
+    }
+
+    /// Connect with the right token once the daemon is listening
+    pub async fn client(&self) -> VaultClient {
+        self.connect(TOKEN).await.unwrap()
+    }
+
+    pub async fn connect(&self, token: &[u8]) -> Result<VaultClient, IpcError> {
+        for _ in 0..50 {
+            match VaultClient::connect_to(&self.socket, "daemon-test", token).await {
+                Err(IpcError::Connect(_)) => tokio::time::sleep(Duration::from_millis(20)).await,
+                result => return result,
+            }
+        }
+        panic!("daemon did not start listening on {}", self.socket);
+    }
+}
+
+/// Socket name unique to this test process and `test`
+pub fn socket_for(test: &str) -> String {
+    format!("identra-daemon-test-{}-{}.sock", std::process::id(), test)
+}
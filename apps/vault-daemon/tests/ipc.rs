@@ -0,0 +1,505 @@
+//! End-to-end requests against a running `VaultServer` over a real local socket
+
+mod common;
+
+use common::{TestDaemon, TOKEN};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use identra_crypto::{CipherSuiteId, KeyDerivationParams, MemoryBinding, MemoryVault};
+use identra_ipc::{ErrorCode, IpcError, KeyAlgorithm, KeyPolicy, RestoreMode, VaultClient};
+use std::collections::HashMap;
+use std::time::Duration;
+use vault_daemon::{PeerPolicy, VaultLock, VaultServer};
+
+/// Argon2 settings slow enough (about a second) to keep an `Unlock` in flight
+const SLOW_KDF: KeyDerivationParams = KeyDerivationParams {
+    memory_cost: 8192,
+    time_cost: 8,
+    parallelism: 1,
+};
+
+#[tokio::test]
+async fn test_key_lifecycle_over_socket() {
+    let mut client = TestDaemon::start("lifecycle").client().await;
+    assert!(client.server().server.starts_with("identra-vault-daemon/"));
+    client.ping().await.unwrap();
+
+    let metadata = HashMap::from([("purpose".to_string(), "dek".to_string())]);
+    client.store_key("k1", b"key material", metadata, None).await.unwrap();
+    assert!(client.key_exists("k1").await.unwrap());
+    assert_eq!(client.list_keys().await.unwrap(), vec!["k1".to_string()]);
+
+    let key = client.retrieve_key("k1").await.unwrap();
+    assert_eq!(key.key_data.as_bytes(), b"key material");
+    assert_eq!(key.metadata["purpose"], "dek");
+
+    client.delete_key("k1").await.unwrap();
+    assert!(!client.key_exists("k1").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_list_keys_pages_over_socket() {
+    let mut client = TestDaemon::start("pages").client().await;
+    for id in ["k3", "k1", "k2"] {
+        client.store_key(id, b"key material", HashMap::new(), None).await.unwrap();
+    }
+
+    let (first, token) = client.list_keys_page(Some(2), None).await.unwrap();
+    assert_eq!(first, vec!["k1", "k2"]);
+    let (rest, token) = client.list_keys_page(Some(2), token).await.unwrap();
+    assert_eq!(rest, vec!["k3"]);
+    assert!(token.is_none());
+}
+
+#[tokio::test]
+async fn test_error_codes_over_socket() {
+    let mut client = TestDaemon::start("errors").client().await;
+
+    let err = client.retrieve_key("missing").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+    let err = client.delete_key("missing").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+
+    client.store_key("old", b"stale", HashMap::new(), Some(1)).await.unwrap();
+    let err = client.retrieve_key("old").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Expired));
+
+    // The connection is still usable after errors
+    client.ping().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_expired_keys_are_swept() {
+    let daemon = TestDaemon::start_with("sweep", |server| server.with_sweep_interval(Duration::from_millis(50)));
+    let mut client = daemon.client().await;
+    let tomorrow = chrono::Utc::now().timestamp() + 86_400;
+    client.store_key("old", b"stale", HashMap::new(), Some(1)).await.unwrap();
+    client.store_key("fresh", b"current", HashMap::new(), Some(tomorrow)).await.unwrap();
+
+    assert!(!client.key_exists("old").await.unwrap());
+    for _ in 0..50 {
+        if client.list_keys().await.unwrap() == vec!["fresh"] {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(client.list_keys().await.unwrap(), vec!["fresh"]);
+    let err = client.retrieve_key("old").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+}
+
+#[tokio::test]
+async fn test_lock_and_unlock_over_socket() {
+    let daemon = TestDaemon::start_with("lock", |server| {
+        server.with_lock(VaultLock::in_memory().with_kdf_params(KeyDerivationParams::fast()))
+    });
+    let mut client = daemon.client().await;
+    assert!(!client.status().await.unwrap().passphrase_set);
+    assert_eq!(client.lock().await.unwrap_err().code(), Some(ErrorCode::InvalidRequest));
+    assert_eq!(client.unlock(b"passphrase").await.unwrap_err().code(), Some(ErrorCode::InvalidRequest));
+
+    client.set_passphrase(b"passphrase").await.unwrap();
+    client.store_key("k1", b"key material", HashMap::new(), None).await.unwrap();
+    client.lock().await.unwrap();
+
+    // Locking applies to every connection
+    let mut other = daemon.client().await;
+    assert!(other.status().await.unwrap().locked);
+    assert_eq!(other.retrieve_key("k1").await.unwrap_err().code(), Some(ErrorCode::Locked));
+    assert_eq!(other.unlock(b"guess").await.unwrap_err().code(), Some(ErrorCode::Unauthorized));
+
+    assert_eq!(other.set_passphrase(b"guess").await.unwrap_err().code(), Some(ErrorCode::InvalidRequest));
+    other.unlock(b"passphrase").await.unwrap();
+    assert_eq!(client.retrieve_key("k1").await.unwrap().key_data.as_bytes(), b"key material");
+}
+
+#[tokio::test]
+async fn test_failed_unlocks_back_off_and_are_audited() {
+    let daemon = TestDaemon::start_with("unlock-backoff", |server| {
+        server.with_lock(VaultLock::in_memory().with_kdf_params(KeyDerivationParams::fast()))
+    });
+    let mut client = daemon.client().await;
+    let start = chrono::Utc::now().timestamp();
+    client.set_passphrase(b"passphrase").await.unwrap();
+    client.lock().await.unwrap();
+
+    for _ in 0..4 {
+        assert_eq!(client.unlock(b"guess").await.unwrap_err().code(), Some(ErrorCode::Unauthorized));
+    }
+    assert_eq!(client.unlock(b"passphrase").await.unwrap_err().code(), Some(ErrorCode::RateLimited));
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    client.unlock(b"passphrase").await.unwrap();
+
+    let (entries, _) = client.get_audit_log(Some(start - 60), None, None).await.unwrap();
+    let unlocks: Vec<_> = entries
+        .iter()
+        .filter(|e| e.operation == "unlock")
+        .map(|e| e.outcome.as_str())
+        .collect();
+    assert_eq!(
+        unlocks,
+        vec!["unauthorized", "unauthorized", "unauthorized", "unauthorized", "rate_limited", "ok"]
+    );
+}
+
+#[tokio::test]
+async fn test_idle_vault_auto_locks() {
+    let daemon = TestDaemon::start_with("auto-lock", |server| {
+        let lock = VaultLock::in_memory()
+            .with_kdf_params(KeyDerivationParams::fast())
+            .with_auto_lock(Some(Duration::from_millis(100)));
+        server.with_lock(lock)
+    });
+    let mut client = daemon.client().await;
+    client.set_passphrase(b"passphrase").await.unwrap();
+    assert_eq!(client.status().await.unwrap().auto_lock_secs, Some(0));
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(client.status().await.unwrap().locked);
+    assert_eq!(client.list_keys().await.unwrap_err().code(), Some(ErrorCode::Locked));
+}
+
+#[tokio::test]
+async fn test_crypto_operations_over_socket() {
+    let mut client = TestDaemon::start("operations").client().await;
+    client.store_key("dek", &[7u8; 32], HashMap::new(), None).await.unwrap();
+    client.store_key("kek", &[8u8; 32], HashMap::new(), None).await.unwrap();
+
+    let ciphertext = client.encrypt("dek", b"remember the milk", b"memory-1").await.unwrap();
+    assert!(!ciphertext.windows(8).any(|w| w == b"remember"));
+    let plaintext = client.decrypt("dek", ciphertext.clone(), b"memory-1").await.unwrap();
+    assert_eq!(plaintext.as_bytes(), b"remember the milk");
+    let err = client.decrypt("kek", ciphertext, b"memory-1").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Crypto));
+
+    let signature = client.sign("dek", b"payload").await.unwrap();
+    assert!(client.verify("dek", b"payload", &signature).await.unwrap());
+    assert!(!client.verify("kek", b"payload", &signature).await.unwrap());
+
+    // A wrapped key comes back under a new id without passing through the client
+    let wrapped = client.wrap_key("kek", "dek").await.unwrap();
+    client.unwrap_key("kek", wrapped, "dek-copy", HashMap::new(), None, KeyPolicy::default()).await.unwrap();
+    let ciphertext = client.encrypt("dek-copy", b"same key", b"").await.unwrap();
+    assert_eq!(client.decrypt("dek", ciphertext, b"").await.unwrap().as_bytes(), b"same key");
+
+    let err = client.encrypt("missing", b"x", b"").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+}
+
+#[tokio::test]
+async fn test_memory_packets_sealed_by_the_daemon() {
+    let mut client = TestDaemon::start("memory-packets").client().await;
+    let key = MemoryVault::key_from_bytes(&[9u8; 32]).unwrap();
+    let policy = KeyPolicy { exportable: false, ..KeyPolicy::default() };
+    client.store_key_with_policy("memory-key", key.as_bytes(), HashMap::new(), None, policy).await.unwrap();
+
+    // What the desktop stores is a bound memory packet in the vault's suite
+    let metadata = HashMap::from([("owner".to_string(), "user-1".to_string())]);
+    let binding = MemoryBinding::new("memory-1", "user-1", &metadata);
+    let sealed = client
+        .encrypt_with_suite("memory-key", CipherSuiteId::XChaCha20Poly1305, b"remember the milk", &binding.associated_data())
+        .await
+        .unwrap();
+    let packet = BASE64.encode(&sealed);
+    assert_eq!(MemoryVault::open_bound_strict(&packet, &key, &binding).unwrap(), "remember the milk");
+    assert_eq!(MemoryVault::packet_key_id(&packet).unwrap(), Some(MemoryVault::key_id(&key)));
+
+    let opened = client.decrypt("memory-key", sealed, &binding.associated_data()).await.unwrap();
+    assert_eq!(opened.as_bytes(), b"remember the milk");
+    let err = client.retrieve_key("memory-key").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
+
+#[tokio::test]
+async fn test_key_policy_over_socket() {
+    let mut client = TestDaemon::start("policy").client().await;
+    let this_exe = format!("exe:{}", std::env::current_exe().unwrap().display());
+    let policy = KeyPolicy {
+        readers: Some(vec![this_exe]),
+        users: Some(vec![format!("uid:{}", u32::MAX - 1)]),
+        max_uses: Some(1),
+        ..KeyPolicy::default()
+    };
+    // Client names are self-declared, so policies can't name them
+    let named = KeyPolicy { users: Some(vec!["daemon-test".to_string()]), ..policy.clone() };
+    let err = client.store_key_with_policy("guarded", b"key material", HashMap::new(), None, named).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::InvalidRequest));
+    client.store_key_with_policy("guarded", b"key material", HashMap::new(), None, policy.clone()).await.unwrap();
+
+    // Matched by executable and user id, from the peer's credentials
+    let key = client.retrieve_key("guarded").await.unwrap();
+    assert_eq!((key.policy, key.uses), (policy, 1));
+    let err = client.retrieve_key("guarded").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::UsesExhausted));
+    let err = client.encrypt("guarded", b"x", b"").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
+
+#[tokio::test]
+async fn test_generated_signing_key_over_socket() {
+    let mut client = TestDaemon::start("generate").client().await;
+
+    let public_key = client.generate_key("signer", KeyAlgorithm::Ed25519, false).await.unwrap().unwrap();
+    let signature = client.sign("signer", b"payload").await.unwrap();
+    let public_key = BASE64.decode(public_key).unwrap();
+    assert!(identra_crypto::verify_signature(&public_key, b"payload", &signature).unwrap());
+    assert!(client.verify("signer", b"payload", &signature).await.unwrap());
+
+    // The private half never leaves the daemon
+    let err = client.retrieve_key("signer").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+    let err = client.encrypt("signer", b"x", b"").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Crypto));
+}
+
+#[tokio::test]
+async fn test_sealed_box_over_socket() {
+    let mut client = TestDaemon::start("sealed").client().await;
+
+    let (public_key, fingerprint) = client.get_identity().await.unwrap();
+    let recipient = identra_crypto::IdentityPublicKey::from_base64(&public_key).unwrap();
+    assert_eq!(recipient.fingerprint(), fingerprint);
+
+    let sealed = identra_crypto::seal(&recipient, b"shared dek", b"memory-1").unwrap();
+    let opened = client.open_sealed(sealed.clone(), b"memory-1".to_vec()).await.unwrap();
+    assert_eq!(opened.as_bytes(), b"shared dek");
+
+    let err = client.open_sealed(sealed, b"memory-2".to_vec()).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Crypto));
+}
+
+#[tokio::test]
+async fn test_audit_log_over_socket() {
+    let daemon = TestDaemon::start("audit");
+    assert!(daemon.connect(&[4u8; 32]).await.is_err());
+    let mut client = daemon.client().await;
+    let start = chrono::Utc::now().timestamp();
+
+    client.store_key("k1", b"secret", HashMap::new(), None).await.unwrap();
+    client.retrieve_key("missing").await.unwrap_err();
+    let err = client.retrieve_key("identra-audit-log").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+
+    let (entries, verified) = client.get_audit_log(Some(start - 60), None, None).await.unwrap();
+    assert!(verified);
+    let recorded: Vec<_> = entries
+        .iter()
+        .map(|e| (e.operation.as_str(), e.key_id.as_deref(), e.outcome.as_str()))
+        .collect();
+    assert_eq!(
+        recorded,
+        vec![
+            ("connect", None, "unauthorized"),
+            ("store_key", Some("k1"), "ok"),
+            ("retrieve_key", Some("missing"), "not_found"),
+            ("retrieve_key", Some("identra-audit-log"), "permission_denied"),
+        ]
+    );
+    assert_eq!(entries[1].client, "daemon-test");
+    assert_eq!(entries[1].pid, Some(std::process::id()));
+
+    let (entries, _) = client.get_audit_log(None, Some(start - 60), None).await.unwrap();
+    assert!(entries.is_empty());
+}
+
+#[tokio::test]
+async fn test_backup_and_restore_over_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vault.backup").to_string_lossy().into_owned();
+    let fast_kdf = |server: VaultServer| server.with_lock(VaultLock::in_memory().with_kdf_params(KeyDerivationParams::fast()));
+    let mut source = TestDaemon::start_with("backup-source", fast_kdf).client().await;
+    source.store_key("k1", b"one", HashMap::new(), None).await.unwrap();
+    source.store_key("k2", b"two", HashMap::new(), None).await.unwrap();
+    assert_eq!(source.export_backup(path.as_str(), b"backup pass").await.unwrap(), 2);
+    let err = source.export_backup(path.as_str(), b"backup pass").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::InvalidRequest));
+    let err = source.export_backup("vault.backup", b"backup pass").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::InvalidRequest));
+
+    let mut target = TestDaemon::start_with("backup-target", fast_kdf).client().await;
+    target.store_key("k2", b"local", HashMap::new(), None).await.unwrap();
+    let err = target.import_backup(path.as_str(), b"wrong", RestoreMode::Merge, false).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Unauthorized));
+
+    let summary = target.import_backup(path.as_str(), b"backup pass", RestoreMode::Merge, false).await.unwrap();
+    assert_eq!((summary.added, summary.skipped), (vec!["k1".to_string()], vec!["k2".to_string()]));
+    assert_eq!(target.retrieve_key("k1").await.unwrap().key_data.as_bytes(), b"one");
+    assert_eq!(target.retrieve_key("k2").await.unwrap().key_data.as_bytes(), b"local");
+}
+
+#[tokio::test]
+async fn test_wrong_token_is_rejected() {
+    let daemon = TestDaemon::start("bad-token");
+
+    let err = daemon.connect(&[4u8; 32]).await.err().unwrap();
+    assert_eq!(err.code(), Some(ErrorCode::Unauthorized));
+}
+
+#[tokio::test]
+async fn test_peer_outside_allow_list_is_rejected() {
+    // Only some other user may connect
+    let daemon = TestDaemon::with_policy("other-uid", PeerPolicy::any_user().allow_uid(u32::MAX - 1));
+    let err = daemon.connect(TOKEN).await.err().unwrap();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+
+    let daemon = TestDaemon::with_policy("other-exe", PeerPolicy::any_user().allow_executable("/nonexistent/identra"));
+    let err = daemon.connect(TOKEN).await.err().unwrap();
+    assert_eq!(err.code(), Some(ErrorCode::PermissionDenied));
+}
+
+#[tokio::test]
+#[cfg(target_os = "linux")]
+async fn test_allowed_executable_is_accepted() {
+    // The test binary is both daemon and client here
+    let exe = std::env::current_exe().unwrap();
+    let daemon = TestDaemon::with_policy("own-exe", PeerPolicy::current_user().allow_executable(exe));
+
+    daemon.client().await.ping().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_stops_daemon() {
+    let daemon = TestDaemon::start("shutdown");
+    let mut idle = daemon.client().await;
+    let client = daemon.client().await;
+
+    client.shutdown().await.unwrap();
+
+    // Idle sessions are closed and the daemon stops listening
+    let err = idle.ping().await.unwrap_err();
+    assert!(matches!(err, IpcError::Closed | IpcError::Io(_)), "{:?}", err);
+    let socket = daemon.socket.clone();
+    daemon.stopped().await.unwrap();
+    let err = VaultClient::connect_to(&socket, "late", TOKEN).await.err().unwrap();
+    assert!(matches!(err, IpcError::Connect(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_shutdown_drains_in_flight_requests() {
+    let daemon = TestDaemon::start_with("drain", |server| server.with_lock(VaultLock::in_memory().with_kdf_params(SLOW_KDF)));
+    let mut busy = daemon.client().await;
+    let shutdown = daemon.client().await;
+
+    // Setting the passphrase runs Argon2 long enough to still be running at shutdown
+    let setup = tokio::spawn(async move { busy.set_passphrase(b"passphrase").await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    shutdown.shutdown().await.unwrap();
+
+    setup.await.unwrap().unwrap();
+    daemon.stopped().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_drain_timeout_aborts_stuck_requests() {
+    let daemon = TestDaemon::start_with("drain-timeout", |server| {
+        server
+            .with_lock(VaultLock::in_memory().with_kdf_params(SLOW_KDF))
+            .with_drain_timeout(Duration::from_millis(10))
+    });
+    let mut busy = daemon.client().await;
+    let shutdown = daemon.client().await;
+
+    let setup = tokio::spawn(async move { busy.set_passphrase(b"passphrase").await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    shutdown.shutdown().await.unwrap();
+
+    daemon.stopped().await.unwrap();
+    let err = setup.await.unwrap().unwrap_err();
+    assert!(matches!(err, IpcError::Closed | IpcError::Io(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_second_daemon_on_live_socket_refuses_to_start() {
+    let daemon = TestDaemon::start("live-socket");
+    daemon.client().await.ping().await.unwrap();
+
+    let second = TestDaemon::start_on(daemon.socket.clone(), |server| server);
+    let err = second.stopped().await.unwrap_err();
+    assert!(err.to_string().contains("already listening"), "{}", err);
+    daemon.client().await.ping().await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_stale_socket_file_is_replaced_and_removed_on_exit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vault.sock");
+    // A listener that exits without cleaning up leaves its socket file behind
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let daemon = TestDaemon::start_on(path.to_string_lossy().into_owned(), |server| server);
+    daemon.client().await.shutdown().await.unwrap();
+    daemon.stopped().await.unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_requests_after_shutdown_fail() {
+    let daemon = TestDaemon::start("after-shutdown");
+    let mut client = daemon.client().await;
+
+    let response = client.request(&identra_ipc::VaultRequest::Shutdown).await.unwrap();
+    assert!(matches!(response, identra_ipc::VaultResponse::ShuttingDown));
+    let err = client.ping().await.unwrap_err();
+    assert!(matches!(err, IpcError::Closed | IpcError::Io(_)), "{:?}", err);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_file_backend_starts_without_a_keyring() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("vault.sock");
+    // No session or system bus: the keyring and logind are both unreachable
+    let mut daemon = std::process::Command::new(env!("CARGO_BIN_EXE_vault-daemon"))
+        .arg("--backend").arg("file")
+        .arg("--store-dir").arg(dir.path())
+        .arg("--socket").arg(&socket)
+        .env_clear()
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("DBUS_SYSTEM_BUS_ADDRESS", "unix:path=/nonexistent")
+        .env("IDENTRA_VAULT_PASSPHRASE", "test")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // The token is kept next to the keystore, where clients can find it
+    let token_file = dir.path().join(identra_ipc::token::TOKEN_FILE_NAME);
+    let mut client = None;
+    for _ in 0..200 {
+        if let Ok(token) = identra_ipc::token::read_token_file(&token_file) {
+            if let Ok(connected) = VaultClient::connect_to(socket.to_str().unwrap(), "headless", token.as_bytes()).await {
+                client = Some(connected);
+                break;
+            }
+        }
+        if let Some(status) = daemon.try_wait().unwrap() {
+            panic!("daemon exited early: {}", status);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let mut client = client.expect("daemon did not start");
+    client.store_key("k1", b"key material", HashMap::new(), None).await.unwrap();
+    client.shutdown().await.unwrap();
+    assert!(tokio::task::spawn_blocking(move || daemon.wait()).await.unwrap().unwrap().success());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_keychain_backend_without_secret_service_says_how_to_start() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_vault-daemon"))
+        .arg("--backend").arg("keychain")
+        .arg("--store-dir").arg(dir.path())
+        .arg("--socket").arg(dir.path().join("vault.sock"))
+        .env_clear()
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("DBUS_SESSION_BUS_ADDRESS", "unix:path=/nonexistent")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--backend file"));
+}
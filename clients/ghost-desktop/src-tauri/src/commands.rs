@@ -1,11 +1,14 @@
 use crate::state::{NexusState, VaultStatus};
-use identra_crypto::MemoryVault; 
+use identra_crypto::{
+    combine_shares, split_key, CipherSuiteId, IdentityPublicKey, KeyDerivationParams, KeyFile, KeySettings, KeyShare,
+    MemoryBinding, MemoryVault, RecoveryCode, SecretBytes, VaultKey, KEY_ID_METADATA, OWNER_METADATA,
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use tauri::{AppHandle, Manager, State};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
-use aes_gcm::{Aes256Gcm, Key}; // Removed unused KeyInit
 use fastembed::{TextEmbedding, InitOptions, EmbeddingModel};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 
 // --- Helper Functions ---
@@ -46,6 +49,23 @@ pub struct SystemStatusResponse {
     pub security_level: String,
 }
 
+#[derive(serde::Serialize)]
+pub struct RecoveryKit {
+    /// 24-word recovery code for the master key
+    pub code: String,
+    /// Encoded Shamir shares (empty unless a split was requested)
+    pub shares: Vec<String>,
+    pub threshold: Option<u8>,
+}
+
+#[derive(serde::Serialize)]
+pub struct IdentityKeyInfo {
+    /// Base64 X25519 public key
+    pub public_key: String,
+    /// Fingerprint for users to compare out of band
+    pub fingerprint: String,
+}
+
 #[derive(serde::Serialize)]
 pub struct ConversationItem {
     pub id: String,
@@ -109,69 +129,313 @@ pub async fn toggle_main_window(app: AppHandle) -> Result<(), String> {
 
 // --- Security & Vault Commands ---
 
-fn get_session_key_path() -> PathBuf {
+/// Pre-keyfile location of the raw session key (only read for migration)
+fn get_legacy_session_key_path() -> PathBuf {
     let mut path = std::env::temp_dir();
     path.push("identra_session_key.bin");
     path
 }
 
-#[tauri::command]
-pub async fn initialize_session(state: State<'_, NexusState>) -> Result<String, String> {
-    let key_path = get_session_key_path();
-    
-    let key = if key_path.exists() {
-        match fs::read(&key_path) {
-            Ok(bytes) if bytes.len() == 32 => {
-                println!("[NEXUS] Loaded existing session key from disk");
-                Key::<Aes256Gcm>::clone_from_slice(&bytes)
-            }
-            _ => {
-                let new_key = MemoryVault::generate_key();
-                let _ = fs::write(&key_path, new_key.as_slice());
-                new_key
-            }
+/// Read the legacy session key, if it is safe to adopt
+///
+/// It lives in the shared temp directory, so another local user could have
+/// planted a key they know there. Only a regular file (not a symlink) owned
+/// by us and readable by no one else is trusted.
+fn read_legacy_session_key(path: &Path) -> Option<VaultKey> {
+    let mut options = fs::OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+    let mut file = options.open(path).ok()?;
+    // Checked on the open handle, so the file can't be swapped after the check
+    let metadata = file.metadata().ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let uid = unsafe { libc::geteuid() };
+        if metadata.uid() != uid || metadata.permissions().mode() & 0o777 != 0o600 {
+            eprintln!("[NEXUS] Ignoring legacy session key {}: not a private file of ours", path.display());
+            return None;
         }
-    } else {
-        let new_key = MemoryVault::generate_key();
-        let _ = fs::write(&key_path, new_key.as_slice());
-        new_key
+    }
+
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut file, &mut bytes).ok()?;
+    MemoryVault::key_from_bytes(SecretBytes::new(bytes).as_bytes()).ok()
+}
+
+fn get_keyfile_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir()
+        .map_err(|e| format!("No app data directory: {}", e))?;
+    Ok(dir.join("identra.key"))
+}
+
+/// Cipher suite for newly created vaults (`IDENTRA_CIPHER_SUITE`, default AES-256-GCM)
+fn get_new_vault_suite() -> Result<CipherSuiteId, String> {
+    match std::env::var("IDENTRA_CIPHER_SUITE") {
+        Ok(name) => name.parse().map_err(|e| format!("IDENTRA_CIPHER_SUITE: {}", e)),
+        Err(_) => Ok(CipherSuiteId::default()),
+    }
+}
+
+/// Unlock the data-encryption key from the keyfile, creating it on first run
+fn unlock_keyfile(key_path: &Path, passphrase: &str) -> Result<(VaultKey, KeyFile), String> {
+    if key_path.exists() {
+        let key_file = KeyFile::load(key_path)
+            .map_err(|e| format!("Failed to load keyfile: {}", e))?;
+        println!("[NEXUS] Unlocking keyfile {} ({})", key_file.key_id, key_file.suite);
+        let key = key_file.unlock(passphrase.as_bytes())
+            .map_err(|e| format!("Unlock failed: {}", e))?;
+        return Ok((key, key_file));
+    }
+
+    // First run: adopt the legacy raw session key so existing memories still open
+    let legacy_path = get_legacy_session_key_path();
+    let legacy_key = read_legacy_session_key(&legacy_path);
+    let migrating = legacy_key.is_some();
+
+    let params = KeyDerivationParams::secure();
+    let suite = get_new_vault_suite()?;
+    let (key_file, key) = match legacy_key {
+        Some(key) => {
+            println!("[NEXUS] Migrating legacy session key into keyfile");
+            let settings = KeySettings { suite, bound_only: false };
+            let key_file = KeyFile::wrap_with(&key, passphrase.as_bytes(), params, settings)
+                .map_err(|e| format!("Failed to wrap key: {}", e))?;
+            (key_file, key)
+        }
+        // A fresh key has never sealed an unbound memory
+        None => KeyFile::create_with(passphrase.as_bytes(), params, KeySettings { suite, bound_only: true })
+            .map_err(|e| format!("Failed to create key: {}", e))?,
     };
 
-    *state.session_key.lock().map_err(|_| "Key poisoned")? = Some(key);
+    key_file.save(key_path)
+        .map_err(|e| format!("Failed to save keyfile: {}", e))?;
+    if migrating {
+        let _ = fs::remove_file(&legacy_path);
+    }
+
+    Ok((key, key_file))
+}
+
+#[tauri::command]
+pub async fn initialize_session(
+    app: AppHandle,
+    state: State<'_, NexusState>,
+    passphrase: Option<String>,
+) -> Result<String, String> {
+    let passphrase = match passphrase {
+        Some(p) if !p.is_empty() => p,
+        _ => return Err("PASSPHRASE_REQUIRED: Enter your vault passphrase to unlock.".to_string()),
+    };
+    let key_path = get_keyfile_path(&app)?;
+
+    // Argon2id is deliberately slow; keep it off the async runtime
+    let (key, key_file, rotation) = tokio::task::spawn_blocking(move || {
+        let (key, key_file) = unlock_keyfile(&key_path, &passphrase)?;
+        let key = Arc::new(key);
+        // Memories an interrupted rotation already moved only open with its new key
+        let paths = crate::rotation::RotationPaths::new(key_path);
+        let rotation = crate::rotation::resume(&paths, &key_file, Arc::clone(&key), &passphrase)?;
+        Ok::<_, String>((key, key_file, rotation))
+    })
+    .await
+    .map_err(|e| format!("Unlock task failed: {}", e))??;
+
+    // From here on the daemon holds the key; ours is dropped on return
+    let mut vault = connect_vault().await?;
+    match rotation {
+        Some(rotation) => {
+            println!("[NEXUS] Rotation to key {} is unfinished; run it again to complete", rotation.progress.new_key_id);
+            use_rotation_keys(&state, &mut vault, &rotation).await?;
+        }
+        None => {
+            let key_id = crate::memory_keys::store(&mut vault, &key).await?;
+            *state.session_key_id.lock().map_err(|_| "Key poisoned")? = Some(key_id);
+            *state.previous_key_id.lock().map_err(|_| "Key poisoned")? = None;
+            *state.cipher_suite.lock().map_err(|_| "Suite poisoned")? = key_file.suite;
+            *state.bound_only.lock().map_err(|_| "State poisoned")? = key_file.bound_only;
+        }
+    }
     *state.status.lock().map_err(|_| "Status poisoned")? = VaultStatus::Unlocked;
 
     println!("[NEXUS] Session Initialized. Vault UNLOCKED.");
     Ok("Vault Unlocked".to_string())
 }
 
+/// Re-encrypt every stored memory under a fresh session key (resumable)
+#[tauri::command]
+pub async fn rotate_session_key(
+    app: AppHandle,
+    state: State<'_, NexusState>,
+    passphrase: String,
+) -> Result<String, String> {
+    if state.session_key_id.lock().map_err(|_| "Key poisoned")?.is_none() {
+        return Err("VAULT_LOCKED: Please initialize session first.".to_string());
+    }
+    let paths = crate::rotation::RotationPaths::new(get_keyfile_path(&app)?);
+    // Rows are bound to the logged-in user; other users' rows are refused
+    let user_id = current_user_id(&state)?;
+
+    // Argon2id is deliberately slow; keep it off the async runtime
+    let prepare_paths = paths.clone();
+    let rotation = tokio::task::spawn_blocking(move || crate::rotation::prepare(&prepare_paths, &passphrase))
+        .await
+        .map_err(|e| format!("Rotation task failed: {}", e))??;
+
+    // New memories go under the new key from here on, so the walk can't miss any
+    let mut vault = connect_vault().await?;
+    use_rotation_keys(&state, &mut vault, &rotation).await?;
+
+    let progress = crate::rotation::rotate(rotation, &paths, &mut vault, &user_id).await?;
+
+    *state.previous_key_id.lock().map_err(|_| "Key poisoned")? = None;
+    // Every memory is bound now, so unbound packets have nothing legitimate left to open
+    *state.bound_only.lock().map_err(|_| "State poisoned")? = true;
+
+    Ok(format!(
+        "Rotated {} memories to key {} ({} already current)",
+        progress.rotated, progress.new_key_id, progress.skipped
+    ))
+}
+
+/// Seal with a rotation's new key while its old key still opens unmoved memories
+async fn use_rotation_keys(
+    state: &State<'_, NexusState>,
+    vault: &mut crate::ipc_client::VaultClient,
+    rotation: &crate::rotation::Rotation,
+) -> Result<(), String> {
+    let new_key_id = crate::memory_keys::store(vault, &rotation.new_key).await?;
+    let old_key_id = crate::memory_keys::store(vault, &rotation.old_key).await?;
+    *state.session_key_id.lock().map_err(|_| "Key poisoned")? = Some(new_key_id);
+    *state.previous_key_id.lock().map_err(|_| "Key poisoned")? = Some(old_key_id);
+    *state.cipher_suite.lock().map_err(|_| "Suite poisoned")? = rotation.suite;
+    *state.bound_only.lock().map_err(|_| "State poisoned")? = rotation.bound_only;
+    Ok(())
+}
+
+/// Produce a printable recovery code and, optionally, N-of-M key shares
+#[tauri::command]
+pub async fn export_recovery_kit(
+    app: AppHandle,
+    passphrase: String,
+    threshold: Option<u8>,
+    shares: Option<u8>,
+) -> Result<RecoveryKit, String> {
+    let key_path = get_keyfile_path(&app)?;
+
+    // Re-check the passphrase: the kit is as sensitive as the key itself
+    let key = tokio::task::spawn_blocking(move || {
+        KeyFile::load(&key_path)
+            .and_then(|key_file| key_file.unlock(passphrase.as_bytes()))
+            .map_err(|e| format!("Unlock failed: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Unlock task failed: {}", e))??;
+
+    let code = RecoveryCode::from_key(&key).map_err(|e| e.to_string())?;
+    let shares = match (threshold, shares) {
+        (Some(threshold), Some(shares)) => split_key(&key, threshold, shares)
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(KeyShare::encode)
+            .collect(),
+        (None, None) => Vec::new(),
+        _ => return Err("Both threshold and shares are required to split the key".to_string()),
+    };
+
+    println!("[NEXUS] Recovery kit exported ({} shares)", shares.len());
+    Ok(RecoveryKit { code: code.as_str().to_string(), shares, threshold })
+}
+
+/// Rebuild the master key from a recovery code or key shares and set a new passphrase
+#[tauri::command]
+pub async fn recover_vault(
+    app: AppHandle,
+    state: State<'_, NexusState>,
+    recovery: Vec<String>,
+    new_passphrase: String,
+) -> Result<String, String> {
+    if new_passphrase.is_empty() {
+        return Err("PASSPHRASE_REQUIRED: Choose a new vault passphrase.".to_string());
+    }
+    let key = match recovery.as_slice() {
+        [code] if !code.trim().starts_with(identra_crypto::recovery::SHARE_PREFIX) => {
+            RecoveryCode::parse(code).and_then(|code| code.to_key())
+        }
+        entries => entries
+            .iter()
+            .map(|entry| KeyShare::parse(entry))
+            .collect::<identra_crypto::Result<Vec<_>>>()
+            .and_then(|shares| combine_shares(&shares)),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let key_path = get_keyfile_path(&app)?;
+    let (key, key_file) = tokio::task::spawn_blocking(move || {
+        // With the keyfile gone, recovery starts a fresh one for the same key
+        let key_file = match KeyFile::load(&key_path) {
+            Ok(key_file) => key_file.recover(&key, new_passphrase.as_bytes()),
+            Err(_) => KeyFile::wrap(&key, new_passphrase.as_bytes(), KeyDerivationParams::secure()),
+        }
+        .map_err(|e| format!("Recovery failed: {}", e))?;
+
+        key_file.save(&key_path)
+            .map_err(|e| format!("Failed to save keyfile: {}", e))?;
+        Ok::<_, String>((key, key_file))
+    })
+    .await
+    .map_err(|e| format!("Recovery task failed: {}", e))??;
+
+    let key_id = crate::memory_keys::store(&mut connect_vault().await?, &key).await?;
+    *state.session_key_id.lock().map_err(|_| "Key poisoned")? = Some(key_id);
+    *state.previous_key_id.lock().map_err(|_| "Key poisoned")? = None;
+    *state.cipher_suite.lock().map_err(|_| "Suite poisoned")? = key_file.suite;
+    *state.bound_only.lock().map_err(|_| "State poisoned")? = key_file.bound_only;
+    *state.status.lock().map_err(|_| "Status poisoned")? = VaultStatus::Unlocked;
+
+    println!("[NEXUS] Vault recovered. New passphrase set.");
+    Ok("Vault Recovered".to_string())
+}
+
 #[tauri::command]
 pub async fn vault_memory(state: State<'_, NexusState>, content: String) -> Result<String, String> {
     if content.trim().is_empty() { return Err("Payload empty.".to_string()); }
 
-    let session_key = {
-        let key_guard = state.session_key.lock().map_err(|_| "Key poisoned")?;
-        match key_guard.as_ref() {
-            Some(k) => k.clone(),
-            None => return Err("VAULT_LOCKED: Please initialize session first.".to_string()),
-        }
+    let session_key_id = match state.session_key_id.lock().map_err(|_| "Key poisoned")?.clone() {
+        Some(key_id) => key_id,
+        None => return Err("VAULT_LOCKED: Please initialize session first.".to_string()),
     };
 
-    // Encrypt content
-    let encrypted_blob = MemoryVault::lock(&content, &session_key)
-        .map_err(|e| format!("Crypto Error: {}", e))?;
+    let owner = current_user_id(&state)?;
+    let metadata = std::collections::HashMap::from([
+        ("encrypted".to_string(), "true".to_string()),
+        ("timestamp".to_string(), chrono::Utc::now().to_rfc3339()),
+        (KEY_ID_METADATA.to_string(), session_key_id.clone()),
+        (OWNER_METADATA.to_string(), owner.clone()),
+    ]);
+
+    // Encrypt content, bound to the row it will live in
+    let memory_id = uuid::Uuid::new_v4().to_string();
+    let binding = MemoryBinding::new(&memory_id, &owner, &metadata);
+    let suite = *state.cipher_suite.lock().map_err(|_| "Suite poisoned")?;
+    let encrypted_blob =
+        crate::memory_keys::seal(&mut connect_vault().await?, &session_key_id, suite, content.as_bytes(), &binding)
+            .await?;
 
     // Store in DB
     let mut client = crate::grpc_client::GrpcClient::connect()
         .await
         .map_err(|e| format!("Failed to connect to gateway: {}", e))?;
     
-    let metadata = std::collections::HashMap::from([
-        ("encrypted".to_string(), "true".to_string()),
-        ("timestamp".to_string(), chrono::Utc::now().to_rfc3339()),
-    ]);
-    
     let memory_id = client
-        .store_memory(encrypted_blob.clone(), metadata, vec![])
+        .store_memory(memory_id, encrypted_blob, metadata, vec![])
         .await
         .map_err(|e| format!("Failed to store memory: {}", e))?;
 
@@ -185,18 +449,58 @@ pub async fn vault_memory(state: State<'_, NexusState>, content: String) -> Resu
     Ok(format!("Stored successfully (ID: {})", memory_id))
 }
 
+/// Owner recorded on newly stored memories; falls back to a local identity before login
+fn current_user_id(state: &State<'_, NexusState>) -> Result<String, String> {
+    let identity = state.active_identity.lock().map_err(|_| "Identity poisoned")?;
+    Ok(identity.clone().unwrap_or_else(|| "local".to_string()))
+}
+
 #[tauri::command]
-pub async fn decrypt_memory(state: State<'_, NexusState>, encrypted_val: String) -> Result<String, String> {
-    let key_guard = state.session_key.lock().map_err(|_| "Key poisoned")?;
-    let session_key = match &*key_guard {
-        Some(k) => k,
-        None => return Err("VAULT_LOCKED".to_string()),
-    };
+pub async fn decrypt_memory(
+    state: State<'_, NexusState>,
+    encrypted_val: String,
+    memory_id: String,
+) -> Result<String, String> {
+    // Bound memories need the row's metadata to rebuild their associated data
+    let mut client = crate::grpc_client::GrpcClient::connect()
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))?;
+    let (_, metadata) = client.get_memory(memory_id.clone())
+        .await
+        .map_err(|e| format!("Failed to load memory: {}", e))?;
+    // Rows from before owners were recorded were bound to whoever was logged in
+    let user_id = current_user_id(&state)?;
+    let bound_only = *state.bound_only.lock().map_err(|_| "State poisoned")?;
+
+    let key_id = opening_key_id(&state, &encrypted_val)?;
 
-    let plaintext = MemoryVault::open(&encrypted_val, session_key)
+    let binding = MemoryBinding::for_row(&memory_id, &metadata, &user_id)
         .map_err(|e| format!("Decryption Failed: {}", e))?;
+    let bound = MemoryVault::is_bound(&encrypted_val).map_err(|e| format!("Decryption Failed: {}", e))?;
+    if bound_only && !bound {
+        return Err("Decryption Failed: Packet is not bound to a row".to_string());
+    }
+    // Unbound packets were sealed before memories were bound to rows; the next rotation re-seals them bound
+    let plaintext = crate::memory_keys::open(&mut connect_vault().await?, &key_id, &encrypted_val, bound.then_some(&binding))
+        .await
+        .map_err(|e| format!("Decryption Failed: {}", e))?;
+
+    String::from_utf8(plaintext.as_bytes().to_vec()).map_err(|e| format!("Decryption Failed: UTF-8 Error: {}", e))
+}
 
-    Ok(plaintext)
+/// Key a packet was sealed with: the session key, or the old key for memories
+/// an unfinished rotation hasn't moved yet
+fn opening_key_id(state: &State<'_, NexusState>, enc_packet: &str) -> Result<String, String> {
+    let session_key_id = state.session_key_id.lock().map_err(|_| "Key poisoned")?
+        .clone()
+        .ok_or_else(|| "VAULT_LOCKED".to_string())?;
+    let previous_key_id = state.previous_key_id.lock().map_err(|_| "Key poisoned")?.clone();
+
+    let packet_key_id = MemoryVault::packet_key_id(enc_packet).ok().flatten();
+    Ok(match previous_key_id {
+        Some(previous) if packet_key_id.as_deref() == Some(previous.as_str()) => previous,
+        _ => session_key_id,
+    })
 }
 
 #[tauri::command]
@@ -260,12 +564,9 @@ async fn store_chat_interaction(
     ai_response: &str,
     model: &str,
 ) -> Result<(), String> {
-    let session_key = {
-        let key_guard = state.session_key.lock().map_err(|_| "Key poisoned")?;
-        match key_guard.as_ref() {
-            Some(k) => k.clone(),
-            None => return Ok(()), // Skip storage if vault is locked
-        }
+    let session_key_id = match state.session_key_id.lock().map_err(|_| "Key poisoned")?.clone() {
+        Some(key_id) => key_id,
+        None => return Ok(()), // Skip storage if vault is locked
     };
 
     // Create conversation record
@@ -279,21 +580,33 @@ async fn store_chat_interaction(
     let conversation_str = serde_json::to_string(&conversation)
         .map_err(|e| format!("JSON error: {}", e))?;
 
-    // Encrypt and store
-    let encrypted_blob = MemoryVault::lock(&conversation_str, &session_key)
-        .map_err(|e| format!("Encryption error: {}", e))?;
-
-    let mut client = crate::grpc_client::GrpcClient::connect()
-        .await
-        .map_err(|e| format!("Failed to connect: {}", e))?;
-
+    let owner = current_user_id(state)?;
     let metadata = HashMap::from([
         ("type".to_string(), "conversation".to_string()),
         ("model".to_string(), model.to_string()),
         ("timestamp".to_string(), chrono::Utc::now().to_rfc3339()),
+        (KEY_ID_METADATA.to_string(), session_key_id.clone()),
+        (OWNER_METADATA.to_string(), owner.clone()),
     ]);
 
-    let _ = client.store_memory(encrypted_blob, metadata, vec!["chat".to_string()])
+    // Encrypt (bound to the destination row) and store
+    let memory_id = uuid::Uuid::new_v4().to_string();
+    let binding = MemoryBinding::new(&memory_id, &owner, &metadata);
+    let suite = *state.cipher_suite.lock().map_err(|_| "Suite poisoned")?;
+    let encrypted_blob = crate::memory_keys::seal(
+        &mut connect_vault().await?,
+        &session_key_id,
+        suite,
+        conversation_str.as_bytes(),
+        &binding,
+    )
+    .await?;
+
+    let mut client = crate::grpc_client::GrpcClient::connect()
+        .await
+        .map_err(|e| format!("Failed to connect: {}", e))?;
+
+    let _ = client.store_memory(memory_id, encrypted_blob, metadata, vec!["chat".to_string()])
         .await
         .map_err(|e| format!("Storage error: {}", e))?;
 
@@ -503,6 +816,93 @@ pub async fn register_user(username: String, email: String, password: String) ->
     Ok(user_id)
 }
 
+// --- Identity & Sharing Commands ---
+
+/// Client name sent in the vault daemon handshake
+const IPC_CLIENT_NAME: &str = "ghost-desktop";
+
+/// Associated data for memories sealed to another user's identity key
+const SHARE_AAD: &[u8] = b"identra-shared-memory-v1";
+
+async fn connect_vault() -> Result<crate::ipc_client::VaultClient, String> {
+    crate::ipc_client::VaultClient::connect(IPC_CLIENT_NAME)
+        .await
+        .map_err(|e| format!("Vault daemon not available: {}", e))
+}
+
+/// Publish the daemon-held identity key so other users can share memories with us
+#[tauri::command]
+pub async fn publish_identity() -> Result<IdentityKeyInfo, String> {
+    let (public_key, fingerprint) = connect_vault().await?
+        .get_identity()
+        .await
+        .map_err(|e| format!("Failed to load identity key: {}", e))?;
+    let key = IdentityPublicKey::from_base64(&public_key).map_err(|e| e.to_string())?;
+
+    let mut client = crate::grpc_client::GrpcClient::connect()
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))?;
+    client.publish_public_key(key.as_bytes().to_vec(), fingerprint.clone())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    println!("[NEXUS] Published identity key {}", fingerprint);
+    Ok(IdentityKeyInfo { public_key, fingerprint })
+}
+
+/// Look up a recipient's identity key so the user can check its fingerprint before sharing
+#[tauri::command]
+pub async fn get_recipient_key(user_id: String) -> Result<IdentityKeyInfo, String> {
+    let key = fetch_recipient_key(user_id).await?;
+    Ok(IdentityKeyInfo { public_key: key.to_base64(), fingerprint: key.fingerprint() })
+}
+
+/// Decrypt a memory and seal it to a recipient's identity key, returning the base64 box
+#[tauri::command]
+pub async fn share_memory(
+    state: State<'_, NexusState>,
+    encrypted_val: String,
+    memory_id: String,
+    recipient_id: String,
+) -> Result<String, String> {
+    let recipient = fetch_recipient_key(recipient_id).await?;
+    let plaintext = decrypt_memory(state, encrypted_val, memory_id).await?;
+
+    let sealed = identra_crypto::seal(&recipient, plaintext.as_bytes(), SHARE_AAD)
+        .map_err(|e| format!("Sealing failed: {}", e))?;
+    Ok(BASE64.encode(sealed))
+}
+
+/// Open a memory someone sealed to our identity key; the private key stays in the daemon
+#[tauri::command]
+pub async fn open_shared_memory(sealed: String) -> Result<String, String> {
+    let sealed = BASE64.decode(sealed.trim())
+        .map_err(|e| format!("Invalid shared memory: {}", e))?;
+    let plaintext = connect_vault().await?
+        .open_sealed(sealed, SHARE_AAD.to_vec())
+        .await
+        .map_err(|e| format!("Failed to open shared memory: {}", e))?;
+
+    String::from_utf8(plaintext.as_bytes().to_vec())
+        .map_err(|e| format!("Shared memory is not text: {}", e))
+}
+
+/// A recipient's published key, checked against the fingerprint it was published with
+async fn fetch_recipient_key(user_id: String) -> Result<IdentityPublicKey, String> {
+    let mut client = crate::grpc_client::GrpcClient::connect()
+        .await
+        .map_err(|e| format!("Connection failed: {}", e))?;
+    let (public_key, fingerprint) = client.get_public_key(user_id)
+        .await
+        .map_err(|e| format!("Failed to fetch recipient key: {}", e))?;
+
+    let key = IdentityPublicKey::from_bytes(&public_key).map_err(|e| e.to_string())?;
+    if key.fingerprint() != fingerprint {
+        return Err("Recipient key does not match its published fingerprint".to_string());
+    }
+    Ok(key)
+}
+
 // --- Search & History Commands ---
 
 #[tauri::command]
@@ -571,7 +971,7 @@ pub async fn update_memory(
         .await
         .map_err(|e| format!("Connection failed: {}", e))?;
 
-    let message = client.update_memory(memory_id, content, tags)
+    let message = client.update_memory(memory_id, content, tags, HashMap::new())
         .await
         .map_err(|e| e.to_string())?;
 
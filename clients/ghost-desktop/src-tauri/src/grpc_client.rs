@@ -1,19 +1,24 @@
 use identra_proto::memory::{
     memory_service_client::MemoryServiceClient,
     StoreMemoryRequest, QueryMemoriesRequest, 
-    SearchMemoriesRequest, GetRecentMemoriesRequest,
-    UpdateMemoryRequest, DeleteMemoryRequest,
+    SearchMemoriesRequest, GetRecentMemoriesRequest, GetMemoryRequest,
+    UpdateMemoryRequest, DeleteMemoryRequest, ListMemoriesRequest, Memory,
 };
 use identra_proto::auth::{
     auth_service_client::AuthServiceClient,
     LoginRequest, RegisterRequest,
 };
+use identra_proto::identity::{
+    identity_service_client::IdentityServiceClient,
+    PublishPublicKeyRequest, GetPublicKeyRequest,
+};
 use std::collections::HashMap;
 use tonic::transport::Channel;
 
 pub struct GrpcClient {
     memory_client: MemoryServiceClient<Channel>,
     auth_client: AuthServiceClient<Channel>,
+    identity_client: IdentityServiceClient<Channel>,
 }
 
 impl GrpcClient {
@@ -28,7 +33,8 @@ impl GrpcClient {
         
         Ok(Self { 
             memory_client: MemoryServiceClient::new(channel.clone()),
-            auth_client: AuthServiceClient::new(channel),
+            auth_client: AuthServiceClient::new(channel.clone()),
+            identity_client: IdentityServiceClient::new(channel),
         })
     }
     
@@ -36,6 +42,7 @@ impl GrpcClient {
 
     pub async fn store_memory(
         &mut self,
+        memory_id: String,
         content: String,
         metadata: HashMap<String, String>,
         tags: Vec<String>,
@@ -44,6 +51,7 @@ impl GrpcClient {
             content,
             metadata,
             tags,
+            memory_id,
         });
         
         let response = self.memory_client.store_memory(request).await?;
@@ -56,6 +64,21 @@ impl GrpcClient {
         }
     }
     
+    pub async fn get_memory(
+        &mut self,
+        memory_id: String,
+    ) -> Result<(String, HashMap<String, String>), Box<dyn std::error::Error>> {
+        let request = tonic::Request::new(GetMemoryRequest {
+            memory_id,
+        });
+
+        let response = self.memory_client.get_memory(request).await?;
+        let memory = response.into_inner().memory
+            .ok_or("Memory not found")?;
+
+        Ok((memory.content, memory.metadata))
+    }
+    
     pub async fn query_memories(
         &mut self,
         query: String,
@@ -132,11 +155,13 @@ impl GrpcClient {
         memory_id: String,
         content: String,
         tags: Vec<String>,
+        metadata: HashMap<String, String>,
     ) -> Result<String, Box<dyn std::error::Error>> {
         let request = tonic::Request::new(UpdateMemoryRequest {
             memory_id,
             content,
             tags,
+            metadata,
         });
 
         let response = self.memory_client.update_memory(request).await?;
@@ -149,6 +174,21 @@ impl GrpcClient {
         }
     }
 
+    /// One page of memories in id order, plus the cursor for the next page (empty when done)
+    pub async fn list_memories(
+        &mut self,
+        page_size: i32,
+        page_token: String,
+    ) -> Result<(Vec<Memory>, String), Box<dyn std::error::Error>> {
+        let request = tonic::Request::new(ListMemoriesRequest {
+            page_size,
+            page_token,
+        });
+
+        let resp = self.memory_client.list_memories(request).await?.into_inner();
+        Ok((resp.memories, resp.next_page_token))
+    }
+
     pub async fn delete_memory(
         &mut self,
         memory_id: String,
@@ -201,4 +241,29 @@ impl GrpcClient {
             Err(format!("Registration failed: {}", resp.message).into())
         }
     }
-}
\ No newline at end of file
+
+    // --- IDENTITY METHODS ---
+
+    pub async fn publish_public_key(&mut self, public_key: Vec<u8>, key_id: String) -> Result<(), Box<dyn std::error::Error>> {
+        let request = tonic::Request::new(PublishPublicKeyRequest {
+            public_key,
+            key_id,
+        });
+
+        let resp = self.identity_client.publish_public_key(request).await?.into_inner();
+
+        if resp.success {
+            Ok(())
+        } else {
+            Err(format!("Publishing identity key failed: {}", resp.message).into())
+        }
+    }
+
+    /// Returns the recipient's public key and its fingerprint
+    pub async fn get_public_key(&mut self, user_id: String) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
+        let request = tonic::Request::new(GetPublicKeyRequest { user_id });
+
+        let resp = self.identity_client.get_public_key(request).await?.into_inner();
+        Ok((resp.public_key, resp.key_id))
+    }
+}
@@ -1,6 +1,8 @@
 pub mod commands;
 pub mod grpc_client;
-pub mod ipc_client;
+pub use identra_ipc as ipc_client;
+pub mod memory_keys;
+pub mod rotation;
 pub mod state;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -25,6 +27,15 @@ pub fn run() {
             commands::initialize_session,
             commands::login_user,
             commands::register_user,
+            commands::rotate_session_key,
+            commands::export_recovery_kit,
+            commands::recover_vault,
+
+            // --- Identity & Sharing ---
+            commands::publish_identity,
+            commands::get_recipient_key,
+            commands::share_memory,
+            commands::open_shared_memory,
             
             // --- Memory & Intelligence ---
             commands::vault_memory,     // Store
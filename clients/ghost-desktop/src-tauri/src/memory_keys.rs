@@ -0,0 +1,88 @@
+use crate::ipc_client::{KeyPolicy, VaultClient};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use identra_crypto::{CipherSuiteId, MemoryBinding, MemoryVault, Packet, SecretBytes, VaultKey};
+use std::collections::HashMap;
+
+/// Daemon key ids of memory keys: this prefix and the key's fingerprint
+const MEMORY_KEY_PREFIX: &str = "identra-memory-";
+
+/// Daemon key id of the memory key with fingerprint `key_id`
+fn daemon_key_id(key_id: &str) -> String {
+    format!("{}{}", MEMORY_KEY_PREFIX, key_id)
+}
+
+/// Hand a memory key to the vault daemon, returning its fingerprint
+///
+/// The daemon keeps it non-exportable and, where it can see which executable
+/// is calling, only lets this one use or delete it. After unlock the desktop
+/// never holds the key again.
+pub async fn store(vault: &mut VaultClient, key: &VaultKey) -> Result<String, String> {
+    let key_id = MemoryVault::key_id(key);
+    let stored = vault.key_exists(daemon_key_id(&key_id))
+        .await
+        .map_err(|e| format!("Vault daemon error: {}", e))?;
+    if !stored {
+        let principals = this_executable()?;
+        let policy = KeyPolicy {
+            users: principals.clone(),
+            deleters: principals,
+            exportable: false,
+            ..KeyPolicy::default()
+        };
+        vault.store_key_with_policy(daemon_key_id(&key_id), key.as_bytes(), HashMap::new(), None, policy)
+            .await
+            .map_err(|e| format!("Failed to store memory key in the vault daemon: {}", e))?;
+    }
+    Ok(key_id)
+}
+
+/// Policy entries naming this executable; `None` (any client) where the daemon can't check them
+fn this_executable() -> Result<Option<Vec<String>>, String> {
+    if !cfg!(target_os = "linux") {
+        return Ok(None);
+    }
+    let this_exe = std::env::current_exe()
+        .map_err(|e| format!("Cannot tell which executable is running: {}", e))?;
+    Ok(Some(vec![format!("exe:{}", this_exe.display())]))
+}
+
+/// Drop a memory key nothing is sealed with any more
+pub async fn remove(vault: &mut VaultClient, key_id: &str) -> Result<(), String> {
+    vault.delete_key(daemon_key_id(key_id))
+        .await
+        .map_err(|e| format!("Failed to delete memory key {}: {}", key_id, e))
+}
+
+/// Seal `content` for its row with the daemon-held key `key_id`, as a Base64 packet
+pub async fn seal(
+    vault: &mut VaultClient,
+    key_id: &str,
+    suite: CipherSuiteId,
+    content: &[u8],
+    binding: &MemoryBinding<'_>,
+) -> Result<String, String> {
+    let envelope = vault.encrypt_with_suite(daemon_key_id(key_id), suite, content, &binding.associated_data())
+        .await
+        .map_err(|e| format!("Encryption error: {}", e))?;
+    Ok(BASE64.encode(envelope))
+}
+
+/// Open a Base64 packet with the daemon-held key `key_id`
+///
+/// `binding` is `None` only for unbound packets. Legacy (pre-envelope)
+/// packets can't be opened by the daemon; rotation re-seals them.
+pub async fn open(
+    vault: &mut VaultClient,
+    key_id: &str,
+    enc_packet: &str,
+    binding: Option<&MemoryBinding<'_>>,
+) -> Result<SecretBytes, String> {
+    if let Packet::Legacy { .. } = MemoryVault::inspect(enc_packet).map_err(|e| e.to_string())? {
+        return Err("LEGACY_PACKET: Sealed before envelopes; rotate the session key to re-seal it.".to_string());
+    }
+    let envelope = BASE64.decode(enc_packet).map_err(|e| format!("Base64 decode failed: {}", e))?;
+    let aad = binding.map(MemoryBinding::associated_data).unwrap_or_default();
+    vault.decrypt(daemon_key_id(key_id), envelope, &aad)
+        .await
+        .map_err(|e| e.to_string())
+}
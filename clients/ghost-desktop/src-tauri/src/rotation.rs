@@ -0,0 +1,355 @@
+use crate::grpc_client::GrpcClient;
+use crate::ipc_client::VaultClient;
+use crate::memory_keys;
+use identra_crypto::{
+    CipherSuiteId, KeyFile, KeySettings, MemoryBinding, MemoryVault, SecretBytes, VaultKey, KEY_ID_METADATA,
+    OWNER_METADATA,
+};
+use identra_proto::memory::Memory;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Memories fetched from the gateway per page
+const PAGE_SIZE: i32 = 100;
+
+/// Files involved in a rotation, all next to the active keyfile
+#[derive(Clone)]
+pub struct RotationPaths {
+    /// Active keyfile (old key until the rotation commits)
+    pub keyfile: PathBuf,
+    /// New key, wrapped under the same passphrase, waiting to be committed
+    pub pending_keyfile: PathBuf,
+    /// Old key after the swap, kept until no memory is sealed with it
+    pub retired_keyfile: PathBuf,
+    /// Resumable progress record
+    pub progress: PathBuf,
+}
+
+impl RotationPaths {
+    pub fn new(keyfile: PathBuf) -> Self {
+        let dir = keyfile.parent().map(Path::to_path_buf).unwrap_or_default();
+        Self {
+            pending_keyfile: dir.join("identra.key.next"),
+            retired_keyfile: dir.join("identra.key.old"),
+            progress: dir.join("identra.rotation.json"),
+            keyfile,
+        }
+    }
+}
+
+/// Persisted after every page so an interrupted rotation picks up where it stopped
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RotationProgress {
+    pub old_key_id: String,
+    pub new_key_id: String,
+    /// Page token of the next page to process (empty = start / finished)
+    pub cursor: String,
+    pub rotated: usize,
+    pub skipped: usize,
+    pub failed: Vec<String>,
+}
+
+impl RotationProgress {
+    fn load(path: &Path) -> Option<Self> {
+        let json = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|e| format!("Failed to serialize rotation progress: {}", e))?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| format!("Failed to save rotation progress: {}", e))
+    }
+}
+
+/// Keys of a rotation in progress: memories move from `old_key` to `new_key`
+///
+/// Both come from keyfiles and are handed to the vault daemon (see
+/// [`memory_keys::store`]), which does the re-sealing.
+pub struct Rotation {
+    pub old_key: Arc<VaultKey>,
+    pub new_key: Arc<VaultKey>,
+    /// Suite memories are re-sealed with
+    pub suite: CipherSuiteId,
+    /// Whether the old key refuses unbound packets
+    pub bound_only: bool,
+    pub progress: RotationProgress,
+}
+
+/// Rotate the session key: re-encrypt every memory under a fresh key, then swap keyfiles.
+///
+/// Safe to call again after an interruption; memories already sealed with the
+/// new key are skipped. Until it finishes, memories are split between the two
+/// keys: callers keep both loaded (see [`resume`]) and seal new memories with
+/// `new_key`, so nothing stored mid-rotation is left behind the cursor. The
+/// old keyfile is kept as `identra.key.old` until a final pass finds no
+/// memory still sealed with it, and the old key is then deleted from the daemon.
+pub async fn rotate(
+    rotation: Rotation,
+    paths: &RotationPaths,
+    vault: &mut VaultClient,
+    user_id: &str,
+) -> Result<RotationProgress, String> {
+    let Rotation { old_key, new_key, suite, bound_only, mut progress } = rotation;
+    let source = RotationSource {
+        key_id: memory_keys::store(vault, &old_key).await?,
+        // Legacy packets predate envelopes, so only the old key itself opens them
+        legacy_key: (!bound_only).then_some(old_key),
+    };
+    let new_key_id = memory_keys::store(vault, &new_key).await?;
+    drop(new_key);
+    println!(
+        "[ROTATION] {} -> {} (resuming at '{}')",
+        progress.old_key_id, progress.new_key_id, progress.cursor
+    );
+
+    let mut client = GrpcClient::connect()
+        .await
+        .map_err(|e| format!("Failed to connect to gateway: {}", e))?;
+
+    loop {
+        let (memories, next_page_token) = client
+            .list_memories(PAGE_SIZE, progress.cursor.clone())
+            .await
+            .map_err(|e| format!("Failed to list memories: {}", e))?;
+
+        for memory in memories {
+            let memory_id = memory.id.clone();
+            let target = RotationTarget { key_id: &new_key_id, suite };
+            match rotate_memory(&mut client, vault, memory, &source, &target, user_id).await {
+                Ok(true) => progress.rotated += 1,
+                Ok(false) => progress.skipped += 1,
+                Err(e) => {
+                    eprintln!("[ROTATION] Memory {} failed: {}", memory_id, e);
+                    progress.failed.push(memory_id);
+                }
+            }
+        }
+
+        progress.cursor = next_page_token;
+        progress.save(&paths.progress)?;
+
+        if progress.cursor.is_empty() {
+            break;
+        }
+    }
+
+    if !progress.failed.is_empty() {
+        // Walk again from the start next time; rotated memories will be skipped
+        let failed = progress.failed.len();
+        progress.failed.clear();
+        progress.save(&paths.progress)?;
+        return Err(format!(
+            "{} memories could not be re-encrypted; they still open with the old key. Run rotation again to retry.",
+            failed
+        ));
+    }
+
+    commit(paths)?;
+
+    let remaining = count_sealed_with(&mut client, &progress.old_key_id).await?;
+    if remaining > 0 {
+        return Err(format!(
+            "{} memories are still sealed with the old key, kept in {}. Run rotation again to move them.",
+            remaining,
+            paths.retired_keyfile.display()
+        ));
+    }
+
+    if let Err(e) = memory_keys::remove(vault, &progress.old_key_id).await {
+        eprintln!("[ROTATION] {}", e);
+    }
+    let _ = fs::remove_file(&paths.retired_keyfile);
+    let _ = fs::remove_file(&paths.progress);
+    println!("[ROTATION] Complete: {} rotated, {} skipped", progress.rotated, progress.skipped);
+    Ok(progress)
+}
+
+/// Unlock the old key and load (or create) the pending new key
+pub fn prepare(paths: &RotationPaths, passphrase: &str) -> Result<Rotation, String> {
+    let current = KeyFile::load(&paths.keyfile)
+        .map_err(|e| format!("Failed to load keyfile: {}", e))?;
+    let current_key = Arc::new(current.unlock(passphrase.as_bytes())
+        .map_err(|e| format!("Unlock failed: {}", e))?);
+
+    if let Some(rotation) = resume(paths, &current, Arc::clone(&current_key), passphrase)? {
+        return Ok(rotation);
+    }
+
+    // Rotation re-seals everything bound, so the new key never opens unbound packets
+    let settings = KeySettings { suite: current.suite, bound_only: true };
+    let (pending, new_key) = KeyFile::create_with(passphrase.as_bytes(), current.kdf.clone(), settings)
+        .map_err(|e| format!("Failed to create new key: {}", e))?;
+    pending.save(&paths.pending_keyfile)
+        .map_err(|e| format!("Failed to save pending keyfile: {}", e))?;
+
+    let progress = RotationProgress {
+        old_key_id: current.key_id,
+        new_key_id: pending.key_id,
+        ..Default::default()
+    };
+    progress.save(&paths.progress)?;
+
+    Ok(Rotation {
+        old_key: current_key,
+        new_key: Arc::new(new_key),
+        suite: pending.suite,
+        bound_only: current.bound_only,
+        progress,
+    })
+}
+
+/// Pick up an unfinished rotation, given the active keyfile and its unlocked key
+///
+/// Returns `None` when no rotation is in progress. Used at unlock as well, so
+/// memories already moved to the new key keep opening after an interruption.
+pub fn resume(
+    paths: &RotationPaths,
+    current: &KeyFile,
+    current_key: Arc<VaultKey>,
+    passphrase: &str,
+) -> Result<Option<Rotation>, String> {
+    let Some(progress) = RotationProgress::load(&paths.progress) else {
+        return Ok(None);
+    };
+
+    if let Ok(pending) = KeyFile::load(&paths.pending_keyfile) {
+        if pending.key_id == progress.new_key_id {
+            let new_key = pending.unlock(passphrase.as_bytes())
+                .map_err(|e| format!("Failed to unlock pending key: {}", e))?;
+            return Ok(Some(Rotation {
+                old_key: current_key,
+                new_key: Arc::new(new_key),
+                suite: pending.suite,
+                bound_only: current.bound_only,
+                progress,
+            }));
+        }
+    }
+
+    // Interrupted after the keyfile swap: the active key is already the new one
+    // and the old one waits in the retired keyfile
+    if current.key_id == progress.new_key_id {
+        let (old_key, bound_only) = match KeyFile::load(&paths.retired_keyfile) {
+            Ok(retired) if retired.key_id == progress.old_key_id => {
+                let old_key = retired.unlock(passphrase.as_bytes())
+                    .map_err(|e| format!("Failed to unlock retired key: {}", e))?;
+                (Arc::new(old_key), retired.bound_only)
+            }
+            _ => (Arc::clone(&current_key), current.bound_only),
+        };
+        return Ok(Some(Rotation {
+            old_key,
+            new_key: current_key,
+            suite: current.suite,
+            bound_only,
+            progress,
+        }));
+    }
+
+    Ok(None)
+}
+
+/// The daemon-held key memories are being moved from
+struct RotationSource {
+    key_id: String,
+    /// The old key itself, for legacy packets; `None` when the old key is bound-only
+    legacy_key: Option<Arc<VaultKey>>,
+}
+
+/// The daemon-held key (and suite) memories are being moved to
+struct RotationTarget<'a> {
+    key_id: &'a str,
+    suite: CipherSuiteId,
+}
+
+/// Re-encrypt one memory; returns `false` when it needed no work
+///
+/// When the old key is bound-only, an unbound packet can only have been pasted
+/// into the row, so it fails instead of being upgraded to a bound one.
+async fn rotate_memory(
+    client: &mut GrpcClient,
+    vault: &mut VaultClient,
+    memory: Memory,
+    source: &RotationSource,
+    target: &RotationTarget<'_>,
+    user_id: &str,
+) -> Result<bool, String> {
+    let key_id = match MemoryVault::packet_key_id(&memory.content) {
+        Ok(key_id) => key_id,
+        // Not an encrypted packet (e.g. plaintext note) – nothing to rotate
+        Err(_) => return Ok(false),
+    };
+    if key_id.as_deref() == Some(target.key_id) {
+        return Ok(false);
+    }
+    let bound = MemoryVault::is_bound(&memory.content).map_err(|e| e.to_string())?;
+    if source.legacy_key.is_none() && !bound {
+        return Err("Unbound packet in a vault whose memories are all bound".to_string());
+    }
+
+    let mut new_metadata = memory.metadata.clone();
+    new_metadata.insert(KEY_ID_METADATA.to_string(), target.key_id.to_string());
+    // Rows from before owners were recorded get theirs pinned while they're re-sealed;
+    // rows owned by someone else fail to bind below
+    new_metadata.entry(OWNER_METADATA.to_string()).or_insert_with(|| user_id.to_string());
+
+    let old_binding = MemoryBinding::for_row(&memory.id, &memory.metadata, user_id).map_err(|e| e.to_string())?;
+    let new_binding = MemoryBinding::for_row(&memory.id, &new_metadata, user_id).map_err(|e| e.to_string())?;
+    // Unbound and legacy packets come out bound, so rotation also upgrades them
+    let plaintext = match (key_id, &source.legacy_key) {
+        (Some(_), _) => memory_keys::open(vault, &source.key_id, &memory.content, bound.then_some(&old_binding)).await?,
+        (None, Some(legacy_key)) => MemoryVault::open(&memory.content, legacy_key)
+            .map(|plaintext| SecretBytes::new(plaintext.into_bytes()))
+            .map_err(|e| e.to_string())?,
+        (None, None) => return Err("Legacy packet in a vault whose memories are all bound".to_string()),
+    };
+    let content = memory_keys::seal(vault, target.key_id, target.suite, plaintext.as_bytes(), &new_binding).await?;
+
+    client
+        .update_memory(memory.id, content, memory.tags, new_metadata)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
+
+/// Make the pending key active, keeping the old keyfile as the retired one
+fn commit(paths: &RotationPaths) -> Result<(), String> {
+    if paths.pending_keyfile.exists() {
+        // Copy rather than move, so there is always an active keyfile on disk
+        fs::copy(&paths.keyfile, &paths.retired_keyfile)
+            .map_err(|e| format!("Failed to keep old keyfile: {}", e))?;
+        fs::rename(&paths.pending_keyfile, &paths.keyfile)
+            .map_err(|e| format!("Failed to activate new keyfile: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Number of memories whose packets name `key_id`
+async fn count_sealed_with(client: &mut GrpcClient, key_id: &str) -> Result<usize, String> {
+    let mut count = 0;
+    let mut cursor = String::new();
+    loop {
+        let (memories, next_page_token) = client
+            .list_memories(PAGE_SIZE, cursor)
+            .await
+            .map_err(|e| format!("Failed to list memories: {}", e))?;
+        count += memories
+            .iter()
+            .filter(|memory| {
+                MemoryVault::packet_key_id(&memory.content).is_ok_and(|id| id.as_deref() == Some(key_id))
+            })
+            .count();
+
+        if next_page_token.is_empty() {
+            return Ok(count);
+        }
+        cursor = next_page_token;
+    }
+}
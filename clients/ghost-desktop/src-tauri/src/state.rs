@@ -1,5 +1,5 @@
 use std::sync::Mutex;
-use aes_gcm::{Key, Aes256Gcm};
+use identra_crypto::CipherSuiteId;
 
 #[derive(Debug, Clone, serde::Serialize, PartialEq)]
 pub enum VaultStatus {
@@ -13,8 +13,15 @@ pub struct NexusState {
     pub status: Mutex<VaultStatus>,
     pub active_identity: Mutex<Option<String>>,
     pub metrics: Mutex<VaultMetrics>,
-    // This holds the session key in RAM
-    pub session_key: Mutex<Option<Key<Aes256Gcm>>>, 
+    // Fingerprint of the session key; the key itself is held by the vault daemon
+    // (see `memory_keys`), which seals and opens memories with it
+    pub session_key_id: Mutex<Option<String>>,
+    // Old key of an unfinished rotation; opens memories not yet moved to the session key
+    pub previous_key_id: Mutex<Option<String>>,
+    // Cipher suite new memories are sealed with (read from the keyfile at unlock)
+    pub cipher_suite: Mutex<CipherSuiteId>,
+    // Whether unbound (pre-binding) packets are refused; set once rotation has re-sealed them
+    pub bound_only: Mutex<bool>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -29,7 +36,10 @@ impl NexusState {
             status: Mutex::new(VaultStatus::Locked),
             active_identity: Mutex::new(None),
             metrics: Mutex::new(VaultMetrics::default()),
-            session_key: Mutex::new(None),
+            session_key_id: Mutex::new(None),
+            previous_key_id: Mutex::new(None),
+            cipher_suite: Mutex::new(CipherSuiteId::default()),
+            bound_only: Mutex::new(false),
         }
     }
 }
\ No newline at end of file
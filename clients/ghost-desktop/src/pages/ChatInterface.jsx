@@ -56,6 +56,9 @@ export default function ChatInterface() {
   const [isProcessing, setIsProcessing] = useState(false);
   const [selectedModel, setSelectedModel] = useState("claude"); // claude, gemini, gpt
   const [sessionInitialized, setSessionInitialized] = useState(false);
+  const [unlockPassphrase, setUnlockPassphrase] = useState("");
+  const [unlockError, setUnlockError] = useState("");
+  const [isUnlocking, setIsUnlocking] = useState(false);
   const [conversationHistory, setConversationHistory] = useState([]);
   const [profileOpen, setProfileOpen] = useState(false);
   const [settingsOpen, setSettingsOpen] = useState(false);
@@ -95,22 +98,32 @@ export default function ChatInterface() {
 
   const [systemStatus, setSystemStatus] = useState(null);
 
-  // Auto-initialize session on startup
+  // Onboarding may already have unlocked the vault; otherwise the unlock prompt asks for the passphrase
   useEffect(() => {
-    const initSession = async () => {
-      try {
-        await invoke("initialize_session");
-        setSessionInitialized(true);
-        console.log("✅ Session initialized - vault unlocked");
-      } catch (err) {
-        console.error("❌ Session initialization failed:", err);
-        // Retry after 2 seconds
-        setTimeout(initSession, 2000);
-      }
-    };
-    initSession();
+    invoke("get_system_status")
+      .then(status => setSessionInitialized(status.vault_status === "Unlocked"))
+      .catch(err => console.error("Failed to read vault status:", err));
   }, []);
 
+  const handleUnlock = async (e) => {
+    e.preventDefault();
+    if (!unlockPassphrase || isUnlocking) return;
+
+    setIsUnlocking(true);
+    try {
+      await invoke("initialize_session", { passphrase: unlockPassphrase });
+      setUnlockError("");
+      setSessionInitialized(true);
+      console.log("✅ Session initialized - vault unlocked");
+    } catch (err) {
+      console.error("❌ Session initialization failed:", err);
+      setUnlockError(String(err).replace(/^PASSPHRASE_REQUIRED: /, ""));
+    } finally {
+      setUnlockPassphrase("");
+      setIsUnlocking(false);
+    }
+  };
+
   useEffect(() => {
     // Load conversation history and system status after session initialized
     if (sessionInitialized) {
@@ -246,7 +259,10 @@ export default function ChatInterface() {
       console.log("📦 Encrypted content:", item.content.substring(0, 50));
 
       // Decrypt the encrypted content
-      const decryptedContent = await invoke("decrypt_memory", { encryptedVal: item.content });
+      const decryptedContent = await invoke("decrypt_memory", {
+        encryptedVal: item.content,
+        memoryId: item.id
+      });
 
       console.log("🔓 Decrypted content:", decryptedContent);
 
@@ -947,6 +963,52 @@ export default function ChatInterface() {
         </>
       )}
 
+      {/* Unlock Prompt */}
+      {!sessionInitialized && (
+        <>
+          <div className="fixed inset-0 bg-black/50 z-50 backdrop-blur-[2px]" />
+          <div className="fixed z-50 top-1/2 left-1/2 -translate-x-1/2 -translate-y-1/2 bg-identra-surface border border-identra-border-subtle rounded-xl shadow-strong p-6 w-[400px] animate-fade-in-up">
+            <div className="flex items-center gap-2 mb-4">
+              <Lock className="w-4 h-4 text-identra-primary" />
+              <h3 className="text-sm font-semibold text-identra-text-primary">Unlock Vault</h3>
+            </div>
+
+            <form onSubmit={handleUnlock}>
+              <div className="mb-4">
+                <label className="block text-xs font-medium text-identra-text-secondary uppercase tracking-wider mb-2">
+                  Vault Passphrase
+                </label>
+                <input
+                  type="password"
+                  value={unlockPassphrase}
+                  onChange={(e) => setUnlockPassphrase(e.target.value)}
+                  placeholder="Enter your vault passphrase..."
+                  className="w-full bg-identra-surface-elevated border border-identra-border rounded-lg p-3 text-sm text-identra-text-primary placeholder:text-identra-text-tertiary outline-none focus:border-identra-primary transition-colors"
+                  autoFocus
+                />
+              </div>
+
+              {unlockError && (
+                <div className="mb-4 flex items-center gap-2 text-xs text-red-400">
+                  <AlertCircle className="w-4 h-4 shrink-0" />
+                  <span>{unlockError}</span>
+                </div>
+              )}
+
+              <div className="flex justify-end">
+                <button
+                  type="submit"
+                  disabled={!unlockPassphrase || isUnlocking}
+                  className="px-4 py-2 bg-identra-primary text-white rounded-lg text-sm font-medium hover:bg-identra-primary/90 disabled:opacity-50 disabled:cursor-not-allowed transition-colors"
+                >
+                  {isUnlocking ? "Unlocking..." : "Unlock"}
+                </button>
+              </div>
+            </form>
+          </div>
+        </>
+      )}
+
       {/* Toast Notification */}
       {showToast && (
         <div className="fixed bottom-6 left-1/2 -translate-x-1/2 bg-identra-surface-elevated border border-identra-primary/30 shadow-glow px-4 py-2.5 rounded-full flex items-center gap-3 z-[60] animate-fade-in-up">
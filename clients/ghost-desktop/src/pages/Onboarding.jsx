@@ -5,6 +5,9 @@ import { invoke } from "@tauri-apps/api/core";
 export default function Onboarding({ onComplete }) {
   const [step, setStep] = useState(0);
   const [name, setName] = useState("");
+  const [passphrase, setPassphrase] = useState("");
+  const [confirmPassphrase, setConfirmPassphrase] = useState("");
+  const [error, setError] = useState("");
   const [setupComplete, setSetupComplete] = useState(false);
   const [isLoading, setIsLoading] = useState(false);
 
@@ -33,7 +36,7 @@ export default function Onboarding({ onComplete }) {
     {
       title: "Let's Get Started",
       subtitle: "What should we call you?",
-      description: "This helps personalize your experience. You can change this anytime in settings.",
+      description: "Choose a name and a vault passphrase. The passphrase unlocks your memories and cannot be recovered without a recovery kit.",
       icon: Shield,
       color: "from-orange-500 to-red-500",
       input: true
@@ -43,15 +46,17 @@ export default function Onboarding({ onComplete }) {
   const currentStep = steps[step];
   const Icon = currentStep.icon;
   const isLastStep = step === steps.length - 1;
+  const passphraseMismatch = confirmPassphrase !== "" && passphrase !== confirmPassphrase;
+  const canFinish = name.trim() && passphrase && passphrase === confirmPassphrase;
 
   const handleNext = async () => {
     if (isLastStep) {
+      if (!canFinish) return;
       setIsLoading(true);
+      setError("");
       try {
-        // Initialize session with user name
-        await invoke("initialize_session", { 
-          username: name || "User" 
-        });
+        // Creates the keyfile under this passphrase on first run, unlocks it otherwise
+        await invoke("initialize_session", { passphrase });
         
         setSetupComplete(true);
         
@@ -62,9 +67,9 @@ export default function Onboarding({ onComplete }) {
         setTimeout(() => {
           onComplete();
         }, 1500);
-      } catch (error) {
-        console.error("Setup failed:", error);
-        alert("Setup failed. Please try again.");
+      } catch (err) {
+        console.error("Setup failed:", err);
+        setError(String(err).replace(/^PASSPHRASE_REQUIRED: /, ""));
       } finally {
         setIsLoading(false);
       }
@@ -148,6 +153,27 @@ export default function Onboarding({ onComplete }) {
                 autoFocus
                 onKeyPress={(e) => e.key === "Enter" && handleNext()}
               />
+              <input
+                type="password"
+                value={passphrase}
+                onChange={(e) => setPassphrase(e.target.value)}
+                placeholder="Vault passphrase"
+                className="w-full mt-4 px-6 py-4 bg-identra-bg border border-identra-border rounded-xl text-identra-text placeholder-identra-text-tertiary focus:outline-none focus:border-identra-primary transition-colors text-center text-lg"
+                onKeyPress={(e) => e.key === "Enter" && handleNext()}
+              />
+              <input
+                type="password"
+                value={confirmPassphrase}
+                onChange={(e) => setConfirmPassphrase(e.target.value)}
+                placeholder="Confirm passphrase"
+                className="w-full mt-4 px-6 py-4 bg-identra-bg border border-identra-border rounded-xl text-identra-text placeholder-identra-text-tertiary focus:outline-none focus:border-identra-primary transition-colors text-center text-lg"
+                onKeyPress={(e) => e.key === "Enter" && handleNext()}
+              />
+              {(passphraseMismatch || error) && (
+                <p className="mt-3 text-center text-sm text-red-400">
+                  {passphraseMismatch ? "Passphrases do not match" : error}
+                </p>
+              )}
             </div>
           )}
 
@@ -163,7 +189,7 @@ export default function Onboarding({ onComplete }) {
             )}
             <button
               onClick={handleNext}
-              disabled={isLoading || (isLastStep && !name.trim())}
+              disabled={isLoading || (isLastStep && !canFinish)}
               className={`flex-1 px-8 py-4 bg-gradient-to-r ${currentStep.color} text-white rounded-xl font-medium flex items-center justify-center gap-2 hover:opacity-90 transition-opacity disabled:opacity-50 disabled:cursor-not-allowed`}
             >
               {isLoading ? (
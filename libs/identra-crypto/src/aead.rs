@@ -1,43 +1,10 @@
 use crate::error::{CryptoError, Result};
-use crate::{KEY_SIZE, NONCE_SIZE};
-use chacha20poly1305::{
-    aead::{Aead, KeyInit, OsRng},
-    ChaCha20Poly1305, Key, Nonce as ChaNonce,
-};
-use zeroize::Zeroize;
+use crate::NONCE_SIZE;
+use crate::secret::SecretKey;
+use crate::suite::{ChaCha20Poly1305Suite, CipherSuite};
 
-/// Encryption key wrapper
-#[derive(Clone, Zeroize)]
-#[zeroize(drop)]
-pub struct EncryptionKey([u8; KEY_SIZE]);
-
-impl EncryptionKey {
-    /// Create a new encryption key from bytes
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        if bytes.len() != KEY_SIZE {
-            return Err(CryptoError::InvalidKeyLength {
-                expected: KEY_SIZE,
-                actual: bytes.len(),
-            });
-        }
-        
-        let mut key = [0u8; KEY_SIZE];
-        key.copy_from_slice(bytes);
-        Ok(Self(key))
-    }
-    
-    /// Get key as bytes
-    pub fn as_bytes(&self) -> &[u8] {
-        &self.0
-    }
-    
-    /// Generate a random key
-    pub fn generate() -> Self {
-        let mut key = [0u8; KEY_SIZE];
-        getrandom::getrandom(&mut key).expect("Failed to generate random key");
-        Self(key)
-    }
-}
+/// Encryption key wrapper (see [`SecretKey`])
+pub type EncryptionKey = SecretKey;
 
 /// Nonce wrapper
 #[derive(Clone)]
@@ -81,13 +48,7 @@ impl Nonce {
 /// # Returns
 /// Encrypted ciphertext with authentication tag
 pub fn encrypt(key: &EncryptionKey, nonce: &Nonce, plaintext: &[u8]) -> Result<Vec<u8>> {
-    let cipher_key = Key::from_slice(key.as_bytes());
-    let cipher = ChaCha20Poly1305::new(cipher_key);
-    let cipher_nonce = ChaNonce::from_slice(nonce.as_bytes());
-    
-    cipher
-        .encrypt(cipher_nonce, plaintext)
-        .map_err(|e| CryptoError::Encryption(e.to_string()))
+    encrypt_with_aad(key, nonce, plaintext, &[])
 }
 
 /// Decrypt data using ChaCha20-Poly1305
@@ -100,13 +61,30 @@ pub fn encrypt(key: &EncryptionKey, nonce: &Nonce, plaintext: &[u8]) -> Result<V
 /// # Returns
 /// Decrypted plaintext if authentication succeeds
 pub fn decrypt(key: &EncryptionKey, nonce: &Nonce, ciphertext: &[u8]) -> Result<Vec<u8>> {
-    let cipher_key = Key::from_slice(key.as_bytes());
-    let cipher = ChaCha20Poly1305::new(cipher_key);
-    let cipher_nonce = ChaNonce::from_slice(nonce.as_bytes());
-    
-    cipher
-        .decrypt(cipher_nonce, ciphertext)
-        .map_err(|e| CryptoError::Decryption(e.to_string()))
+    decrypt_with_aad(key, nonce, ciphertext, &[])
+}
+
+/// Encrypt data using ChaCha20-Poly1305, authenticating `aad` alongside it
+///
+/// The associated data is not encrypted or stored, but the exact same bytes
+/// must be supplied to [`decrypt_with_aad`] or decryption fails.
+pub fn encrypt_with_aad(
+    key: &EncryptionKey,
+    nonce: &Nonce,
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>> {
+    ChaCha20Poly1305Suite.seal(key.as_bytes(), nonce.as_bytes(), plaintext, aad)
+}
+
+/// Decrypt data using ChaCha20-Poly1305, verifying the associated data
+pub fn decrypt_with_aad(
+    key: &EncryptionKey,
+    nonce: &Nonce,
+    ciphertext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>> {
+    ChaCha20Poly1305Suite.open(key.as_bytes(), nonce.as_bytes(), ciphertext, aad)
 }
 
 #[cfg(test)]
@@ -153,4 +131,20 @@ mod tests {
         let result = decrypt(&key, &nonce2, &ciphertext);
         assert!(result.is_err());
     }
+    
+    #[test]
+    fn test_wrong_aad_fails() {
+        let key = EncryptionKey::generate();
+        let nonce = Nonce::generate();
+        let plaintext = b"Bound message";
+        
+        let ciphertext = encrypt_with_aad(&key, &nonce, plaintext, b"memory-1").unwrap();
+        
+        let decrypted = decrypt_with_aad(&key, &nonce, &ciphertext, b"memory-1").unwrap();
+        assert_eq!(decrypted.as_slice(), plaintext);
+        
+        // Same ciphertext presented as a different row must not decrypt
+        assert!(decrypt_with_aad(&key, &nonce, &ciphertext, b"memory-2").is_err());
+        assert!(decrypt(&key, &nonce, &ciphertext).is_err());
+    }
 }
@@ -0,0 +1,298 @@
+use crate::error::{CryptoError, Result};
+use crate::suite::XNONCE_SIZE;
+use crate::NONCE_SIZE;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Magic bytes at the start of every envelope
+pub const ENVELOPE_MAGIC: &[u8; 4] = b"IDRA";
+
+/// Current envelope format version
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// Maximum length of a key id in bytes (stored as a single length byte)
+pub const MAX_KEY_ID_LEN: usize = u8::MAX as usize;
+
+/// Fixed part of the header: magic + version + suite + key id length
+const FIXED_HEADER_LEN: usize = ENVELOPE_MAGIC.len() + 3;
+
+/// Cipher suite identifiers recorded in the envelope header
+///
+/// Serialized by name (e.g. in a keyfile) so a vault can pin its suite.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum CipherSuiteId {
+    #[default]
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm = 1,
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305 = 2,
+    #[serde(rename = "xchacha20-poly1305")]
+    XChaCha20Poly1305 = 3,
+}
+
+impl CipherSuiteId {
+    /// Nonce size used by this suite
+    pub fn nonce_size(self) -> usize {
+        match self {
+            Self::Aes256Gcm | Self::ChaCha20Poly1305 => NONCE_SIZE,
+            Self::XChaCha20Poly1305 => XNONCE_SIZE,
+        }
+    }
+
+    /// Name used in configuration (`aes-256-gcm`, `chacha20-poly1305`, `xchacha20-poly1305`)
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Aes256Gcm => "aes-256-gcm",
+            Self::ChaCha20Poly1305 => "chacha20-poly1305",
+            Self::XChaCha20Poly1305 => "xchacha20-poly1305",
+        }
+    }
+}
+
+impl fmt::Display for CipherSuiteId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CipherSuiteId {
+    type Err = CryptoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [Self::Aes256Gcm, Self::ChaCha20Poly1305, Self::XChaCha20Poly1305]
+            .into_iter()
+            .find(|suite| suite.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| CryptoError::InvalidEnvelope(format!("Unknown cipher suite: {}", s)))
+    }
+}
+
+impl TryFrom<u8> for CipherSuiteId {
+    type Error = CryptoError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(Self::Aes256Gcm),
+            2 => Ok(Self::ChaCha20Poly1305),
+            3 => Ok(Self::XChaCha20Poly1305),
+            other => Err(CryptoError::UnsupportedCipherSuite(other)),
+        }
+    }
+}
+
+/// Self-describing ciphertext envelope
+///
+/// Wire layout (all integers big-endian):
+///
+/// | field      | size            |
+/// |------------|-----------------|
+/// | magic      | 4 (`IDRA`)      |
+/// | version    | 1               |
+/// | suite      | 1               |
+/// | key id len | 1               |
+/// | key id     | key id len      |
+/// | nonce      | suite nonce size|
+/// | aad len    | 4               |
+/// | ciphertext | remainder       |
+///
+/// Everything before the ciphertext is the header, which is authenticated
+/// as associated data so it can't be tampered with independently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub version: u8,
+    pub suite: CipherSuiteId,
+    pub key_id: String,
+    pub nonce: Vec<u8>,
+    /// Length of the caller-supplied associated data the ciphertext is bound to
+    pub aad_len: u32,
+    pub ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    /// Serialize the header (everything except the ciphertext)
+    pub fn header_bytes(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(FIXED_HEADER_LEN + self.key_id.len() + self.nonce.len() + 4);
+        header.extend_from_slice(ENVELOPE_MAGIC);
+        header.push(self.version);
+        header.push(self.suite as u8);
+        header.push(self.key_id.len() as u8);
+        header.extend_from_slice(self.key_id.as_bytes());
+        header.extend_from_slice(&self.nonce);
+        header.extend_from_slice(&self.aad_len.to_be_bytes());
+        header
+    }
+
+    /// Serialize the full envelope
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header_bytes();
+        bytes.extend_from_slice(&self.ciphertext);
+        bytes
+    }
+
+    /// Parse an envelope from raw bytes
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < FIXED_HEADER_LEN || &bytes[..ENVELOPE_MAGIC.len()] != ENVELOPE_MAGIC {
+            return Err(CryptoError::InvalidEnvelope("Missing magic bytes".to_string()));
+        }
+
+        let mut pos = ENVELOPE_MAGIC.len();
+        let version = bytes[pos];
+        if version != ENVELOPE_VERSION {
+            return Err(CryptoError::UnsupportedVersion(version));
+        }
+        let suite = CipherSuiteId::try_from(bytes[pos + 1])?;
+        let key_id_len = bytes[pos + 2] as usize;
+        pos += 3;
+
+        let key_id = take(bytes, &mut pos, key_id_len, "key id")?;
+        let key_id = String::from_utf8(key_id.to_vec())
+            .map_err(|e| CryptoError::InvalidEnvelope(format!("Key id is not UTF-8: {}", e)))?;
+
+        let nonce = take(bytes, &mut pos, suite.nonce_size(), "nonce")?.to_vec();
+
+        let aad_len = take(bytes, &mut pos, 4, "aad length")?;
+        let aad_len = u32::from_be_bytes([aad_len[0], aad_len[1], aad_len[2], aad_len[3]]);
+
+        Ok(Self {
+            version,
+            suite,
+            key_id,
+            nonce,
+            aad_len,
+            ciphertext: bytes[pos..].to_vec(),
+        })
+    }
+}
+
+fn take<'a>(bytes: &'a [u8], pos: &mut usize, len: usize, field: &str) -> Result<&'a [u8]> {
+    let end = pos
+        .checked_add(len)
+        .filter(|end| *end <= bytes.len())
+        .ok_or_else(|| CryptoError::InvalidEnvelope(format!("Truncated {}", field)))?;
+    let slice = &bytes[*pos..end];
+    *pos = end;
+    Ok(slice)
+}
+
+/// A stored ciphertext packet, either enveloped or in the pre-envelope format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Envelope(Envelope),
+    /// Original `nonce || ciphertext` AES-256-GCM packet with no header
+    Legacy { nonce: Vec<u8>, ciphertext: Vec<u8> },
+}
+
+impl Packet {
+    /// Parse raw bytes; only bytes without the envelope magic are read as legacy
+    ///
+    /// Bytes with the magic that don't parse are a damaged (or tampered)
+    /// envelope, and its error is returned rather than guessing. A legacy
+    /// packet whose nonce happens to start with the magic can still be read
+    /// with [`Self::parse_legacy`].
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.starts_with(ENVELOPE_MAGIC) {
+            return Envelope::parse(bytes).map(Self::Envelope);
+        }
+
+        Self::parse_legacy(bytes)
+    }
+
+    /// Parse bytes as a legacy `nonce || ciphertext` packet, magic or not
+    pub fn parse_legacy(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < NONCE_SIZE {
+            return Err(CryptoError::Decryption("Packet too short".to_string()));
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_SIZE);
+        Ok(Self::Legacy {
+            nonce: nonce.to_vec(),
+            ciphertext: ciphertext.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Envelope {
+        Envelope {
+            version: ENVELOPE_VERSION,
+            suite: CipherSuiteId::Aes256Gcm,
+            key_id: "session-1".to_string(),
+            nonce: vec![7u8; NONCE_SIZE],
+            aad_len: 42,
+            ciphertext: vec![1, 2, 3, 4, 5],
+        }
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let envelope = sample();
+        let parsed = Envelope::parse(&envelope.to_bytes()).unwrap();
+        assert_eq!(parsed, envelope);
+    }
+
+    #[test]
+    fn test_truncated_envelope_rejected() {
+        let bytes = sample().to_bytes();
+        let header_len = sample().header_bytes().len();
+
+        assert!(matches!(
+            Envelope::parse(&bytes[..header_len - 1]),
+            Err(CryptoError::InvalidEnvelope(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_version_and_suite_rejected() {
+        let mut bytes = sample().to_bytes();
+        bytes[4] = 9;
+        assert!(matches!(Envelope::parse(&bytes), Err(CryptoError::UnsupportedVersion(9))));
+
+        let mut bytes = sample().to_bytes();
+        bytes[5] = 77;
+        assert!(matches!(Envelope::parse(&bytes), Err(CryptoError::UnsupportedCipherSuite(77))));
+    }
+
+    #[test]
+    fn test_suite_names_roundtrip() {
+        for suite in [
+            CipherSuiteId::Aes256Gcm,
+            CipherSuiteId::ChaCha20Poly1305,
+            CipherSuiteId::XChaCha20Poly1305,
+        ] {
+            assert_eq!(suite.as_str().parse::<CipherSuiteId>().unwrap(), suite);
+            assert_eq!(CipherSuiteId::try_from(suite as u8).unwrap(), suite);
+        }
+        assert!("rot13".parse::<CipherSuiteId>().is_err());
+    }
+
+    #[test]
+    fn test_packet_without_magic_is_legacy() {
+        let mut bytes = vec![9u8; NONCE_SIZE];
+        bytes.extend_from_slice(b"ciphertext");
+
+        match Packet::parse(&bytes).unwrap() {
+            Packet::Legacy { nonce, ciphertext } => {
+                assert_eq!(nonce, vec![9u8; NONCE_SIZE]);
+                assert_eq!(ciphertext, b"ciphertext");
+            }
+            other => panic!("expected legacy packet, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_damaged_envelope_is_not_legacy() {
+        let mut bytes = sample().to_bytes();
+        bytes[ENVELOPE_MAGIC.len()] = ENVELOPE_VERSION + 1;
+        assert!(matches!(Packet::parse(&bytes), Err(CryptoError::UnsupportedVersion(_))));
+
+        let truncated = &sample().to_bytes()[..FIXED_HEADER_LEN + 2];
+        assert!(matches!(Packet::parse(truncated), Err(CryptoError::InvalidEnvelope(_))));
+        assert!(matches!(Packet::parse_legacy(truncated), Err(CryptoError::Decryption(_))));
+
+        // Explicitly asked for, the same bytes read as a legacy packet
+        assert!(matches!(Packet::parse_legacy(&bytes), Ok(Packet::Legacy { .. })));
+    }
+}
@@ -22,6 +22,30 @@ pub enum CryptoError {
     
     #[error("Encoding error: {0}")]
     Encoding(String),
+    
+    #[error("Invalid envelope: {0}")]
+    InvalidEnvelope(String),
+    
+    #[error("Unsupported envelope version: {0}")]
+    UnsupportedVersion(u8),
+    
+    #[error("Unsupported cipher suite: {0}")]
+    UnsupportedCipherSuite(u8),
+    
+    #[error("Invalid passphrase or corrupted keyfile")]
+    InvalidPassphrase,
+    
+    #[error("Keyfile error: {0}")]
+    KeyFile(String),
+    
+    #[error("Stream error: {0}")]
+    Stream(String),
+    
+    #[error("Recovery error: {0}")]
+    Recovery(String),
+    
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 pub type Result<T> = std::result::Result<T, CryptoError>;
@@ -0,0 +1,246 @@
+use crate::error::{CryptoError, Result};
+use crate::secret::SecretKey;
+use crate::suite::{ChaCha20Poly1305Suite, CipherSuite};
+use crate::vault::{MemoryVault, VaultKey};
+use crate::{KEY_SIZE, NONCE_SIZE};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+/// Size of an X25519 public key in bytes
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+/// Current sealed-box format version
+pub const SEALED_BOX_VERSION: u8 = 1;
+
+/// HKDF info string binding derived keys to this construction
+const SEALED_BOX_INFO: &[u8] = b"identra-sealed-box-v1";
+
+/// Version byte + ephemeral public key
+const SEALED_HEADER_LEN: usize = 1 + PUBLIC_KEY_SIZE;
+
+/// A user's published X25519 identity key
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IdentityPublicKey([u8; PUBLIC_KEY_SIZE]);
+
+impl IdentityPublicKey {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes: [u8; PUBLIC_KEY_SIZE] = bytes.try_into().map_err(|_| CryptoError::InvalidKeyLength {
+            expected: PUBLIC_KEY_SIZE,
+            actual: bytes.len(),
+        })?;
+        Ok(Self(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; PUBLIC_KEY_SIZE] {
+        &self.0
+    }
+
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(self.0)
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self> {
+        let bytes = BASE64
+            .decode(encoded.trim())
+            .map_err(|e| CryptoError::Encoding(format!("Base64 decode failed: {}", e)))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Short fingerprint for display and out-of-band verification
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"identra-identity-id");
+        hasher.update(self.0);
+        hasher.finalize()[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+impl fmt::Debug for IdentityPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "IdentityPublicKey({})", self.fingerprint())
+    }
+}
+
+/// X25519 identity keypair used to receive keys shared by other users
+///
+/// Not `Clone`; the private half is wiped on drop and never printed.
+pub struct IdentityKeyPair {
+    secret: StaticSecret,
+    public: IdentityPublicKey,
+}
+
+impl IdentityKeyPair {
+    /// Generate a fresh identity
+    pub fn generate() -> Self {
+        Self::from_secret(&SecretKey::generate())
+    }
+
+    /// Rebuild an identity from its stored private key
+    pub fn from_secret_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(Self::from_secret(&SecretKey::from_bytes(bytes)?))
+    }
+
+    fn from_secret(secret: &SecretKey) -> Self {
+        let mut bytes = Zeroizing::new([0u8; KEY_SIZE]);
+        bytes.copy_from_slice(secret.as_bytes());
+        let secret = StaticSecret::from(*bytes);
+        let public = IdentityPublicKey(PublicKey::from(&secret).to_bytes());
+        Self { secret, public }
+    }
+
+    pub fn public_key(&self) -> &IdentityPublicKey {
+        &self.public
+    }
+
+    /// Private key bytes, for persisting through the vault daemon's key storage
+    pub fn secret_key(&self) -> SecretKey {
+        SecretKey::from_bytes(self.secret.as_bytes()).expect("X25519 secrets are 32 bytes")
+    }
+
+    /// Open a sealed box addressed to this identity
+    pub fn open(&self, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < SEALED_HEADER_LEN {
+            return Err(CryptoError::Decryption("Sealed box too short".to_string()));
+        }
+        if sealed[0] != SEALED_BOX_VERSION {
+            return Err(CryptoError::UnsupportedVersion(sealed[0]));
+        }
+        let ephemeral = IdentityPublicKey::from_bytes(&sealed[1..SEALED_HEADER_LEN])?;
+
+        let shared = self.secret.diffie_hellman(&PublicKey::from(ephemeral.0));
+        if !shared.was_contributory() {
+            return Err(CryptoError::Decryption("Invalid ephemeral key".to_string()));
+        }
+        let key = box_key(shared.as_bytes(), &ephemeral, &self.public)?;
+
+        ChaCha20Poly1305Suite.open(
+            key.as_bytes(),
+            &[0u8; NONCE_SIZE],
+            &sealed[SEALED_HEADER_LEN..],
+            &[&sealed[..SEALED_HEADER_LEN], aad].concat(),
+        )
+    }
+
+    /// Open a sealed key produced by [`seal_key`]
+    pub fn open_key(&self, sealed: &[u8], aad: &[u8]) -> Result<VaultKey> {
+        let bytes = Zeroizing::new(self.open(sealed, aad)?);
+        MemoryVault::key_from_bytes(&bytes)
+    }
+}
+
+impl fmt::Debug for IdentityKeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdentityKeyPair")
+            .field("public", &self.public)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Encrypt `plaintext` so only the holder of `recipient`'s private key can read it
+///
+/// Layout: version || ephemeral public key || ciphertext. A fresh ephemeral
+/// key makes every box key unique, so a fixed nonce is safe. Boxes are
+/// anonymous: they prove nothing about who sealed them.
+pub fn seal(recipient: &IdentityPublicKey, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let ephemeral_secret = IdentityKeyPair::generate();
+    let shared = ephemeral_secret.secret.diffie_hellman(&PublicKey::from(recipient.0));
+    if !shared.was_contributory() {
+        return Err(CryptoError::Encryption("Invalid recipient public key".to_string()));
+    }
+    let ephemeral = ephemeral_secret.public;
+    let key = box_key(shared.as_bytes(), &ephemeral, recipient)?;
+
+    let mut sealed = Vec::with_capacity(SEALED_HEADER_LEN + plaintext.len() + 16);
+    sealed.push(SEALED_BOX_VERSION);
+    sealed.extend_from_slice(ephemeral.as_bytes());
+    let ciphertext = ChaCha20Poly1305Suite.seal(
+        key.as_bytes(),
+        &[0u8; NONCE_SIZE],
+        plaintext,
+        &[&sealed[..], aad].concat(),
+    )?;
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Wrap a memory DEK for a recipient (e.g. to share a memory)
+pub fn seal_key(recipient: &IdentityPublicKey, key: &VaultKey, aad: &[u8]) -> Result<Vec<u8>> {
+    seal(recipient, key.as_bytes(), aad)
+}
+
+/// HKDF-SHA256 over the shared secret, salted with both public keys
+fn box_key(shared: &[u8], ephemeral: &IdentityPublicKey, recipient: &IdentityPublicKey) -> Result<SecretKey> {
+    let salt = [ephemeral.as_bytes().as_slice(), recipient.as_bytes()].concat();
+    let mut okm = Zeroizing::new([0u8; KEY_SIZE]);
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(SEALED_BOX_INFO, okm.as_mut_slice())
+        .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
+    SecretKey::from_bytes(okm.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let bob = IdentityKeyPair::generate();
+        let sealed = seal(bob.public_key(), b"for bob only", b"memory-1").unwrap();
+
+        assert_eq!(bob.open(&sealed, b"memory-1").unwrap(), b"for bob only");
+        assert!(bob.open(&sealed, b"memory-2").is_err());
+        assert!(IdentityKeyPair::generate().open(&sealed, b"memory-1").is_err());
+    }
+
+    #[test]
+    fn test_seal_key_roundtrip() {
+        let bob = IdentityKeyPair::generate();
+        let dek = MemoryVault::generate_key();
+        let packet = MemoryVault::lock("shared memory", &dek).unwrap();
+
+        let sealed = seal_key(bob.public_key(), &dek, b"").unwrap();
+        let opened = bob.open_key(&sealed, b"").unwrap();
+
+        assert_eq!(opened, dek);
+        assert_eq!(MemoryVault::open(&packet, &opened).unwrap(), "shared memory");
+    }
+
+    #[test]
+    fn test_tampered_box_fails() {
+        let bob = IdentityKeyPair::generate();
+        let mut sealed = seal(bob.public_key(), b"payload", b"").unwrap();
+
+        // Ephemeral key, then ciphertext
+        for pos in [5, sealed.len() - 1] {
+            sealed[pos] ^= 0x01;
+            assert!(bob.open(&sealed, b"").is_err());
+            sealed[pos] ^= 0x01;
+        }
+        assert!(bob.open(&sealed, b"").is_ok());
+    }
+
+    #[test]
+    fn test_low_order_recipient_rejected() {
+        let zero = IdentityPublicKey::from_bytes(&[0u8; PUBLIC_KEY_SIZE]).unwrap();
+
+        assert!(seal(&zero, b"payload", b"").is_err());
+    }
+
+    #[test]
+    fn test_identity_persists_through_secret_key() {
+        let alice = IdentityKeyPair::generate();
+        let restored = IdentityKeyPair::from_secret_bytes(alice.secret_key().as_bytes()).unwrap();
+
+        assert_eq!(restored.public_key(), alice.public_key());
+        let encoded = alice.public_key().to_base64();
+        assert_eq!(&IdentityPublicKey::from_base64(&encoded).unwrap(), alice.public_key());
+        assert!(!format!("{:?}", alice).contains(&encoded));
+    }
+}
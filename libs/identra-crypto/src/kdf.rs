@@ -4,27 +4,27 @@ use argon2::{
     password_hash::{PasswordHasher, SaltString},
     Argon2, Params, Version,
 };
-use zeroize::Zeroize;
+use crate::secret::SecretKey;
+use serde::{Deserialize, Serialize};
 
-/// Derived key wrapper
-#[derive(Clone, Zeroize)]
-#[zeroize(drop)]
-pub struct DerivedKey([u8; KEY_SIZE]);
+/// Derived key wrapper (not `Clone`; wiped on drop, redacted `Debug`)
+#[derive(Debug, PartialEq, Eq)]
+pub struct DerivedKey(SecretKey);
 
 impl DerivedKey {
     /// Get key as bytes
     pub fn as_bytes(&self) -> &[u8] {
-        &self.0
+        self.0.as_bytes()
     }
     
     /// Convert to EncryptionKey
     pub fn to_encryption_key(&self) -> crate::EncryptionKey {
-        crate::EncryptionKey::from_bytes(&self.0).expect("DerivedKey always has correct size")
+        crate::EncryptionKey::from_bytes(self.0.as_bytes()).expect("DerivedKey always has correct size")
     }
 }
 
 /// Key derivation parameters
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct KeyDerivationParams {
     /// Memory cost in KiB (default: 64 MiB = 65536 KiB)
     pub memory_cost: u32,
@@ -109,10 +109,7 @@ pub fn derive_key(
         ));
     }
     
-    let mut key = [0u8; KEY_SIZE];
-    key.copy_from_slice(&hash_slice[..KEY_SIZE]);
-    
-    Ok(DerivedKey(key))
+    Ok(DerivedKey(SecretKey::from_bytes(&hash_slice[..KEY_SIZE])?))
 }
 
 #[cfg(test)]
@@ -0,0 +1,335 @@
+use crate::aead::{self, Nonce};
+use crate::envelope::CipherSuiteId;
+use crate::error::{CryptoError, Result};
+use crate::kdf::{derive_key, KeyDerivationParams};
+use crate::random::generate_salt;
+use crate::vault::{MemoryVault, VaultKey};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use zeroize::Zeroize;
+
+/// Current keyfile format version
+///
+/// Version 2 authenticates `suite` and `bound_only` along with the key id;
+/// version 1 files predate both and only unlock with their defaults.
+pub const KEYFILE_VERSION: u8 = 2;
+
+/// Version 1: the wrap only authenticated the key id
+const KEYFILE_VERSION_V1: u8 = 1;
+
+/// Vault settings a keyfile records, authenticated with the wrapped DEK
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeySettings {
+    /// Cipher suite new memories are sealed with
+    pub suite: CipherSuiteId,
+    /// Every memory under this key is bound to its row (fresh keys and rotation targets)
+    pub bound_only: bool,
+}
+
+/// Passphrase-protected data-encryption key (DEK)
+///
+/// The passphrase is stretched with Argon2id into a key-encryption key (KEK),
+/// which wraps a random DEK with ChaCha20-Poly1305. Only the salt, KDF
+/// parameters and wrapped DEK are persisted, so the keyfile on its own
+/// reveals nothing without the passphrase. The [`KeySettings`] are part of
+/// the wrap's associated data, so editing them makes the keyfile fail to unlock.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyFile {
+    pub version: u8,
+    /// Fingerprint of the wrapped DEK (see [`MemoryVault::key_id`])
+    pub key_id: String,
+    pub kdf: KeyDerivationParams,
+    /// Cipher suite this vault seals memories with (keyfiles without one predate suites: AES-256-GCM)
+    #[serde(default)]
+    pub suite: CipherSuiteId,
+    /// Every memory under this key is bound to its row, so unbound packets are refused
+    #[serde(default)]
+    pub bound_only: bool,
+    /// Base64 Argon2id salt
+    pub salt: String,
+    /// Base64 nonce used to wrap the DEK
+    pub nonce: String,
+    /// Base64 wrapped DEK (ciphertext + tag)
+    pub wrapped_key: String,
+}
+
+impl KeyFile {
+    /// Generate a fresh DEK and wrap it under `passphrase`
+    pub fn create(passphrase: &[u8], params: KeyDerivationParams) -> Result<(Self, VaultKey)> {
+        Self::create_with(passphrase, params, KeySettings::default())
+    }
+
+    /// Generate a fresh DEK and wrap it under `passphrase` with `settings`
+    pub fn create_with(passphrase: &[u8], params: KeyDerivationParams, settings: KeySettings) -> Result<(Self, VaultKey)> {
+        let dek = MemoryVault::generate_key();
+        let key_file = Self::wrap_with(&dek, passphrase, params, settings)?;
+        Ok((key_file, dek))
+    }
+
+    /// Wrap an existing DEK under `passphrase` (e.g. when migrating a raw session key)
+    pub fn wrap(dek: &VaultKey, passphrase: &[u8], params: KeyDerivationParams) -> Result<Self> {
+        Self::wrap_with(dek, passphrase, params, KeySettings::default())
+    }
+
+    /// Wrap an existing DEK under `passphrase` with `settings`
+    pub fn wrap_with(dek: &VaultKey, passphrase: &[u8], params: KeyDerivationParams, settings: KeySettings) -> Result<Self> {
+        let salt = generate_salt();
+        let kek = derive_key(passphrase, &salt, &params)?.to_encryption_key();
+        let nonce = Nonce::generate();
+        let key_id = MemoryVault::key_id(dek);
+
+        let aad = wrap_aad(KEYFILE_VERSION, &key_id, settings);
+        let wrapped = aead::encrypt_with_aad(&kek, &nonce, dek.as_bytes(), &aad)?;
+
+        Ok(Self {
+            version: KEYFILE_VERSION,
+            key_id,
+            kdf: params,
+            suite: settings.suite,
+            bound_only: settings.bound_only,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce.as_bytes()),
+            wrapped_key: BASE64.encode(wrapped),
+        })
+    }
+
+    /// Derive the KEK from `passphrase` and unwrap the DEK
+    pub fn unlock(&self, passphrase: &[u8]) -> Result<VaultKey> {
+        match self.version {
+            KEYFILE_VERSION => {}
+            // Settings a version 1 file claims can't be checked, so only its defaults are trusted
+            KEYFILE_VERSION_V1 if self.settings() == KeySettings::default() => {}
+            KEYFILE_VERSION_V1 => {
+                return Err(CryptoError::KeyFile("Version 1 keyfiles can't carry vault settings".to_string()))
+            }
+            version => return Err(CryptoError::UnsupportedVersion(version)),
+        }
+
+        let salt = decode_field(&self.salt, "salt")?;
+        let nonce = Nonce::from_bytes(&decode_field(&self.nonce, "nonce")?)?;
+        let wrapped = decode_field(&self.wrapped_key, "wrapped key")?;
+
+        let kek = derive_key(passphrase, &salt, &self.kdf)?.to_encryption_key();
+        let aad = wrap_aad(self.version, &self.key_id, self.settings());
+        let mut dek_bytes = aead::decrypt_with_aad(&kek, &nonce, &wrapped, &aad)
+            .map_err(|_| CryptoError::InvalidPassphrase)?;
+
+        let dek = MemoryVault::key_from_bytes(&dek_bytes);
+        dek_bytes.zeroize();
+        dek
+    }
+
+    /// The vault settings recorded in this keyfile
+    pub fn settings(&self) -> KeySettings {
+        KeySettings {
+            suite: self.suite,
+            bound_only: self.bound_only,
+        }
+    }
+
+    /// Re-wrap the same DEK under a new passphrase (fresh salt and nonce)
+    pub fn rewrap(&self, old_passphrase: &[u8], new_passphrase: &[u8]) -> Result<Self> {
+        let dek = self.unlock(old_passphrase)?;
+        Self::wrap_with(&dek, new_passphrase, self.kdf.clone(), self.settings())
+    }
+
+    /// Re-wrap a DEK rebuilt from a recovery code or key shares under a new passphrase
+    ///
+    /// Fails if the recovered key is not this vault's key, so a wrong code never
+    /// replaces a working keyfile.
+    pub fn recover(&self, dek: &VaultKey, new_passphrase: &[u8]) -> Result<Self> {
+        if MemoryVault::key_id(dek) != self.key_id {
+            return Err(CryptoError::Recovery(format!(
+                "Recovered key {} does not match vault key {}",
+                MemoryVault::key_id(dek),
+                self.key_id
+            )));
+        }
+        Self::wrap_with(dek, new_passphrase, self.kdf.clone(), self.settings())
+    }
+
+    /// Load a keyfile from disk
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| CryptoError::KeyFile(format!("Failed to parse keyfile: {}", e)))
+    }
+
+    /// Atomically write the keyfile to disk, readable only by the current user
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|e| CryptoError::KeyFile(format!("Failed to serialize keyfile: {}", e)))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write next to the target then rename, so a crash never leaves a half-written keyfile
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            let mut file = options.open(&tmp_path)?;
+            file.write_all(&json)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+/// Associated data binding the wrapped DEK to its key id and, from version 2, the vault settings
+fn wrap_aad(version: u8, key_id: &str, settings: KeySettings) -> Vec<u8> {
+    if version == KEYFILE_VERSION_V1 {
+        return [b"identra-keyfile-v1:".as_slice(), key_id.as_bytes()].concat();
+    }
+    let mut aad = [b"identra-keyfile-v2:".as_slice(), key_id.as_bytes()].concat();
+    aad.push(0);
+    aad.push(settings.suite as u8);
+    aad.push(u8::from(settings.bound_only));
+    aad
+}
+
+fn decode_field(value: &str, field: &str) -> Result<Vec<u8>> {
+    BASE64
+        .decode(value)
+        .map_err(|e| CryptoError::KeyFile(format!("Invalid {}: {}", field, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_unlock() {
+        let (key_file, dek) = KeyFile::create(b"correct horse", KeyDerivationParams::fast()).unwrap();
+
+        let unlocked = key_file.unlock(b"correct horse").unwrap();
+        assert_eq!(unlocked, dek);
+        assert_eq!(key_file.key_id, MemoryVault::key_id(&dek));
+    }
+
+    #[test]
+    fn test_wrong_passphrase_fails() {
+        let (key_file, _) = KeyFile::create(b"correct horse", KeyDerivationParams::fast()).unwrap();
+
+        assert!(matches!(key_file.unlock(b"battery staple"), Err(CryptoError::InvalidPassphrase)));
+    }
+
+    #[test]
+    fn test_rewrap_keeps_dek() {
+        let settings = KeySettings { bound_only: true, ..KeySettings::default() };
+        let (key_file, dek) = KeyFile::create_with(b"old", KeyDerivationParams::fast(), settings).unwrap();
+        let rewrapped = key_file.rewrap(b"old", b"new").unwrap();
+
+        assert_eq!(rewrapped.unlock(b"new").unwrap(), dek);
+        assert!(rewrapped.bound_only);
+        assert!(rewrapped.unlock(b"old").is_err());
+        assert_ne!(rewrapped.salt, key_file.salt);
+    }
+
+    #[test]
+    fn test_recover_with_recovery_code_and_shares() {
+        use crate::recovery::{combine_shares, split_key, RecoveryCode};
+
+        let (key_file, dek) = KeyFile::create(b"forgotten", KeyDerivationParams::fast()).unwrap();
+        let code = RecoveryCode::from_key(&dek).unwrap();
+        let shares = split_key(&dek, 2, 3).unwrap();
+
+        for recovered in [code.to_key().unwrap(), combine_shares(&shares[1..]).unwrap()] {
+            let restored = key_file.recover(&recovered, b"new passphrase").unwrap();
+            assert_eq!(restored.unlock(b"new passphrase").unwrap(), dek);
+            assert_eq!(restored.key_id, key_file.key_id);
+        }
+
+        assert!(matches!(
+            key_file.recover(&MemoryVault::generate_key(), b"new passphrase"),
+            Err(CryptoError::Recovery(_))
+        ));
+    }
+
+    #[test]
+    fn test_suite_defaults_for_older_keyfiles() {
+        let settings = KeySettings { suite: CipherSuiteId::XChaCha20Poly1305, ..KeySettings::default() };
+        let (key_file, _) = KeyFile::create_with(b"pass", KeyDerivationParams::fast(), settings).unwrap();
+
+        let mut json: serde_json::Value = serde_json::to_value(&key_file).unwrap();
+        assert_eq!(json["suite"], "xchacha20-poly1305");
+        assert_eq!(key_file.rewrap(b"pass", b"new").unwrap().suite, CipherSuiteId::XChaCha20Poly1305);
+
+        json.as_object_mut().unwrap().remove("suite");
+        let older: KeyFile = serde_json::from_value(json).unwrap();
+        assert_eq!(older.suite, CipherSuiteId::Aes256Gcm);
+    }
+
+    #[test]
+    fn test_edited_settings_fail_to_unlock() {
+        let settings = KeySettings { suite: CipherSuiteId::ChaCha20Poly1305, bound_only: true };
+        let (key_file, dek) = KeyFile::create_with(b"pass", KeyDerivationParams::fast(), settings).unwrap();
+        assert_eq!(key_file.unlock(b"pass").unwrap(), dek);
+
+        let relaxed = KeyFile { bound_only: false, ..key_file.clone() };
+        assert!(relaxed.unlock(b"pass").is_err());
+        let resuited = KeyFile { suite: CipherSuiteId::Aes256Gcm, ..key_file.clone() };
+        assert!(resuited.unlock(b"pass").is_err());
+        // Relabelling as version 1 doesn't skip the check either
+        let downgraded = KeyFile { version: 1, suite: CipherSuiteId::Aes256Gcm, bound_only: false, ..key_file.clone() };
+        assert!(downgraded.unlock(b"pass").is_err());
+        assert!(KeyFile { version: 1, ..key_file }.unlock(b"pass").is_err());
+    }
+
+    #[test]
+    fn test_version_1_keyfiles_still_unlock() {
+        let dek = MemoryVault::generate_key();
+        let params = KeyDerivationParams::fast();
+        let salt = generate_salt();
+        let kek = derive_key(b"pass", &salt, &params).unwrap().to_encryption_key();
+        let nonce = Nonce::generate();
+        let key_id = MemoryVault::key_id(&dek);
+        let aad = wrap_aad(KEYFILE_VERSION_V1, &key_id, KeySettings::default());
+        let wrapped = aead::encrypt_with_aad(&kek, &nonce, dek.as_bytes(), &aad).unwrap();
+        let v1 = KeyFile {
+            version: KEYFILE_VERSION_V1,
+            key_id,
+            kdf: params,
+            suite: CipherSuiteId::default(),
+            bound_only: false,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce.as_bytes()),
+            wrapped_key: BASE64.encode(wrapped),
+        };
+
+        assert_eq!(v1.unlock(b"pass").unwrap(), dek);
+        assert_eq!(v1.rewrap(b"pass", b"pass").unwrap().version, KEYFILE_VERSION);
+        assert!(KeyFile { bound_only: true, ..v1 }.unlock(b"pass").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys").join("identra.key");
+        let (key_file, dek) = KeyFile::create(b"pass", KeyDerivationParams::fast()).unwrap();
+
+        key_file.save(&path).unwrap();
+        let loaded = KeyFile::load(&path).unwrap();
+
+        assert_eq!(loaded, key_file);
+        assert_eq!(loaded.unlock(b"pass").unwrap(), dek);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -1,63 +1,69 @@
-use aes_gcm::{
-    Aes256Gcm,
-    Key,
-    Nonce,
-    // ADDED: AeadCore (This fixes the generate_nonce error)
-    aead::{Aead, AeadCore, KeyInit, OsRng}, 
-};
-use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
-
-pub struct MemoryVault;
-
-// Define a simple result alias for cleaner code
-type Result<T> = std::result::Result<T, String>;
-
-impl MemoryVault {
-    /// Generates a cryptographically secure 256-bit key
-    pub fn generate_key() -> Key<Aes256Gcm> {
-        Aes256Gcm::generate_key(&mut OsRng)
-    }
-
-    /// Encrypts a string into a Base64 packet (Nonce + Ciphertext)
-    pub fn lock(data: &str, key: &Key<Aes256Gcm>) -> Result<String> {
-        let cipher = Aes256Gcm::new(key);
-        // This line requires AeadCore to be imported
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng); 
-
-        let ciphertext = cipher.encrypt(&nonce, data.as_bytes())
-            .map_err(|_| "Encryption failed".to_string())?;
-
-        // Combine Nonce + Ciphertext
-        let mut packet = Vec::with_capacity(nonce.len() + ciphertext.len());
-        packet.extend_from_slice(&nonce);
-        packet.extend_from_slice(&ciphertext);
-
-        // Return as Base64 string
-        Ok(BASE64.encode(packet))
-    }
-
-    /// Decrypts a Base64 string back into plaintext using the provided session key.
-    pub fn open(enc_packet: &str, key: &Key<Aes256Gcm>) -> Result<String> {
-        // 1. Decode Base64
-        let packet_bytes = BASE64.decode(enc_packet)
-            .map_err(|e| format!("Base64 decode failed: {}", e))?;
-
-        // 2. Extract Nonce (First 12 bytes) and Ciphertext
-        if packet_bytes.len() < 12 {
-            return Result::Err("Packet too short".to_string());
-        }
-        let (nonce_bytes, ciphertext) = packet_bytes.split_at(12);
-        let nonce = Nonce::from_slice(nonce_bytes);
-
-        // 3. Init Cipher
-        let cipher = Aes256Gcm::new(key);
-
-        // 4. Decrypt
-        let plaintext_bytes = cipher.decrypt(nonce, ciphertext)
-            .map_err(|_| "Decryption failed (Wrong Key or Corrupted Data)".to_string())?;
-
-        // 5. Convert to String
-        String::from_utf8(plaintext_bytes)
-            .map_err(|e| format!("UTF-8 Error: {}", e))
-    }
-}
+//! Identra cryptography primitives.
+//!
+//! Everything that touches key material goes through this crate so the
+//! vault daemon and desktop client never import cipher crates directly.
+
+// ChaCha20-Poly1305 authenticated encryption
+pub mod aead;
+
+// Versioned ciphertext envelope format
+pub mod envelope;
+
+// X25519 identity keys and sealed boxes for sharing with other users
+pub mod identity;
+
+// Argon2id password-based key derivation
+pub mod kdf;
+
+// Passphrase-wrapped data-encryption keys
+pub mod keyfile;
+
+// HMAC-SHA256 tags for signing with symmetric keys
+pub mod mac;
+
+// OS-backed randomness for keys, nonces and salts
+pub mod random;
+
+// Recovery codes and Shamir key shares for a forgotten passphrase
+pub mod recovery;
+
+// Non-Clone, zeroizing, constant-time secret key handles
+pub mod secret;
+
+// Ed25519 signing keypairs
+pub mod signing;
+
+// Pluggable AEAD cipher suites (AES-256-GCM, ChaCha20-Poly1305, XChaCha20-Poly1305)
+pub mod suite;
+
+// Chunked STREAM encryption for large attachments
+pub mod stream;
+
+// Memory encryption with a per-vault cipher suite
+pub mod vault;
+
+// Error types
+mod error;
+
+/// Size of a symmetric key in bytes (256 bits)
+pub const KEY_SIZE: usize = 32;
+
+/// Size of an AEAD nonce in bytes (96 bits)
+pub const NONCE_SIZE: usize = 12;
+
+/// Size of a key-derivation salt in bytes (128 bits)
+pub const SALT_SIZE: usize = 16;
+
+pub use aead::{EncryptionKey, Nonce};
+pub use envelope::{CipherSuiteId, Envelope, Packet};
+pub use error::{CryptoError, Result};
+pub use identity::{seal, seal_key, IdentityKeyPair, IdentityPublicKey};
+pub use kdf::{derive_key, DerivedKey, KeyDerivationParams};
+pub use keyfile::{KeyFile, KeySettings};
+pub use random::{generate_key, generate_nonce, generate_random_bytes, generate_salt};
+pub use recovery::{combine_shares, split_key, KeyShare, RecoveryCode};
+pub use secret::{SecretBytes, SecretKey};
+pub use signing::{verify_signature, SigningKeyPair};
+pub use suite::{cipher_suite, Aes256GcmSuite, ChaCha20Poly1305Suite, CipherSuite, XChaCha20Poly1305Suite};
+pub use stream::{decrypt_stream, decrypt_stream_async, encrypt_stream, encrypt_stream_async};
+pub use vault::{metadata_hash, MemoryBinding, MemoryVault, VaultKey, KEY_ID_METADATA, OWNER_METADATA};
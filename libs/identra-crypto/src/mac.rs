@@ -0,0 +1,49 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Size of an HMAC-SHA256 tag in bytes
+pub const TAG_SIZE: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HMAC-SHA256 of `message` under `key`
+///
+/// Plain HMAC with no domain prefix, so tags can be checked with standard tools.
+pub fn sign(key: &[u8], message: &[u8]) -> [u8; TAG_SIZE] {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// Check a tag from [`sign`] in constant time
+pub fn verify(key: &[u8], message: &[u8], tag: &[u8]) -> bool {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.verify_slice(tag).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let tag = sign(b"key", b"message");
+
+        assert!(verify(b"key", b"message", &tag));
+        assert!(!verify(b"key", b"messagf", &tag));
+        assert!(!verify(b"other key", b"message", &tag));
+        assert!(!verify(b"key", b"message", &tag[..16]));
+    }
+
+    #[test]
+    fn test_rfc4231_vector() {
+        // RFC 4231 test case 2
+        let tag = sign(b"Jefe", b"what do ya want for nothing?");
+
+        assert_eq!(
+            tag.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
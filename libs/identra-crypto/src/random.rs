@@ -1,12 +1,9 @@
-use crate::{KEY_SIZE, NONCE_SIZE, SALT_SIZE};
-use crate::error::{CryptoError, Result};
+use crate::secret::SecretKey;
+use crate::{NONCE_SIZE, SALT_SIZE};
 
-/// Generate a random encryption key
-pub fn generate_key() -> [u8; KEY_SIZE] {
-    let mut key = [0u8; KEY_SIZE];
-    getrandom::getrandom(&mut key)
-        .expect("Failed to generate random key");
-    key
+/// Generate a random encryption key, wiped when dropped
+pub fn generate_key() -> SecretKey {
+    SecretKey::generate()
 }
 
 /// Generate a random nonce
@@ -42,8 +39,8 @@ mod tests {
         let key1 = generate_key();
         let key2 = generate_key();
         
-        assert_eq!(key1.len(), KEY_SIZE);
-        assert_eq!(key2.len(), KEY_SIZE);
+        assert_eq!(key1.as_bytes().len(), crate::KEY_SIZE);
+        assert_eq!(key2.as_bytes().len(), crate::KEY_SIZE);
         assert_ne!(key1, key2); // Should be different
     }
     
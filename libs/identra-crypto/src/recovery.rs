@@ -0,0 +1,297 @@
+use crate::error::{CryptoError, Result};
+use crate::vault::{MemoryVault, VaultKey};
+use crate::KEY_SIZE;
+use bip39::Mnemonic;
+use sha2::{Digest, Sha256};
+use sharks::{Share, Sharks};
+use std::fmt;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Prefix of an encoded key share (bump the digit if the layout changes)
+pub const SHARE_PREFIX: &str = "idshare1-";
+
+/// Checksum bytes appended to an encoded share to catch typos
+const SHARE_CHECKSUM_LEN: usize = 4;
+
+/// Printable recovery code: the master key as 24 BIP39 English words
+///
+/// Anyone holding the code can decrypt the vault, so it should be written
+/// down and stored offline, never synced.
+pub struct RecoveryCode(Zeroizing<String>);
+
+impl RecoveryCode {
+    /// Encode a master key as a recovery code
+    pub fn from_key(key: &VaultKey) -> Result<Self> {
+        let mnemonic = Mnemonic::from_entropy(key.as_bytes())
+            .map_err(|e| CryptoError::Recovery(format!("Failed to encode key: {}", e)))?;
+        Ok(Self(Zeroizing::new(mnemonic.to_string())))
+    }
+
+    /// Parse a code typed back in by the user (case and spacing are forgiven)
+    pub fn parse(code: &str) -> Result<Self> {
+        let normalized = Zeroizing::new(
+            code.split_whitespace()
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        let mnemonic = Mnemonic::parse_normalized(&normalized)
+            .map_err(|e| CryptoError::Recovery(format!("Invalid recovery code: {}", e)))?;
+        if mnemonic.word_count() != 24 {
+            return Err(CryptoError::Recovery(format!(
+                "Recovery code must have 24 words, got {}",
+                mnemonic.word_count()
+            )));
+        }
+        Ok(Self(normalized))
+    }
+
+    /// Decode the master key
+    pub fn to_key(&self) -> Result<VaultKey> {
+        let mnemonic = Mnemonic::parse_normalized(&self.0)
+            .map_err(|e| CryptoError::Recovery(format!("Invalid recovery code: {}", e)))?;
+        let (mut entropy, len) = mnemonic.to_entropy_array();
+        let key = MemoryVault::key_from_bytes(&entropy[..len]);
+        entropy.zeroize();
+        key
+    }
+
+    /// The words, space separated
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The individual words, in order
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.0.split(' ')
+    }
+}
+
+impl fmt::Debug for RecoveryCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecoveryCode(<redacted>)")
+    }
+}
+
+/// One share of a master key split with Shamir secret sharing
+///
+/// Any `threshold` distinct shares reconstruct the key; fewer reveal nothing.
+#[derive(Clone, PartialEq, Eq)]
+pub struct KeyShare {
+    threshold: u8,
+    /// Share index followed by the share value (the `sharks` wire layout)
+    share: Zeroizing<Vec<u8>>,
+}
+
+impl KeyShare {
+    /// Position of this share (1-based)
+    pub fn index(&self) -> u8 {
+        self.share[0]
+    }
+
+    /// Number of shares needed to reconstruct the key
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// Printable form: prefix, then hex of threshold || index || value || checksum
+    pub fn encode(&self) -> String {
+        let mut body = Zeroizing::new(Vec::with_capacity(1 + self.share.len() + SHARE_CHECKSUM_LEN));
+        body.push(self.threshold);
+        body.extend_from_slice(&self.share);
+        let checksum = share_checksum(&body);
+        body.extend_from_slice(&checksum);
+
+        let mut encoded = String::from(SHARE_PREFIX);
+        for byte in body.iter() {
+            encoded.push_str(&format!("{:02x}", byte));
+        }
+        encoded
+    }
+
+    /// Parse an encoded share, verifying its checksum
+    pub fn parse(encoded: &str) -> Result<Self> {
+        let compact: String = encoded.split_whitespace().collect::<String>().to_lowercase();
+        let hex = compact
+            .strip_prefix(SHARE_PREFIX)
+            .ok_or_else(|| CryptoError::Recovery("Not an Identra key share".to_string()))?;
+        let body = Zeroizing::new(decode_hex(hex)?);
+
+        // threshold + index + key + checksum
+        if body.len() != 2 + KEY_SIZE + SHARE_CHECKSUM_LEN {
+            return Err(CryptoError::Recovery("Key share has the wrong length".to_string()));
+        }
+        let (payload, checksum) = body.split_at(body.len() - SHARE_CHECKSUM_LEN);
+        if share_checksum(payload) != checksum {
+            return Err(CryptoError::Recovery("Key share checksum mismatch (typo?)".to_string()));
+        }
+
+        Ok(Self {
+            threshold: payload[0],
+            share: Zeroizing::new(payload[1..].to_vec()),
+        })
+    }
+}
+
+impl fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("index", &self.index())
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Split a master key into `shares` shares, any `threshold` of which recover it
+pub fn split_key(key: &VaultKey, threshold: u8, shares: u8) -> Result<Vec<KeyShare>> {
+    if threshold < 2 || threshold > shares {
+        return Err(CryptoError::Recovery(format!(
+            "Invalid share scheme {}-of-{}: need 2 <= threshold <= shares",
+            threshold, shares
+        )));
+    }
+
+    Ok(Sharks(threshold)
+        .dealer(key.as_bytes())
+        .take(shares as usize)
+        .map(|share| KeyShare {
+            threshold,
+            share: Zeroizing::new(Vec::from(&share)),
+        })
+        .collect())
+}
+
+/// Reconstruct a master key from at least `threshold` distinct shares
+pub fn combine_shares(shares: &[KeyShare]) -> Result<VaultKey> {
+    let threshold = shares
+        .first()
+        .map(KeyShare::threshold)
+        .ok_or_else(|| CryptoError::Recovery("No key shares provided".to_string()))?;
+    if shares.iter().any(|share| share.threshold != threshold) {
+        return Err(CryptoError::Recovery("Key shares come from different splits".to_string()));
+    }
+
+    let parsed = shares
+        .iter()
+        .map(|share| Share::try_from(share.share.as_slice()))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| CryptoError::Recovery(format!("Invalid key share: {}", e)))?;
+
+    let secret = Zeroizing::new(
+        Sharks(threshold)
+            .recover(&parsed)
+            .map_err(|e| CryptoError::Recovery(e.to_string()))?,
+    );
+    MemoryVault::key_from_bytes(&secret)
+}
+
+fn share_checksum(payload: &[u8]) -> [u8; SHARE_CHECKSUM_LEN] {
+    let digest = Sha256::new()
+        .chain_update(b"identra-share-v1")
+        .chain_update(payload)
+        .finalize();
+    let mut checksum = [0u8; SHARE_CHECKSUM_LEN];
+    checksum.copy_from_slice(&digest[..SHARE_CHECKSUM_LEN]);
+    checksum
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.is_ascii() {
+        return Err(CryptoError::Recovery("Key share contains non-hex characters".to_string()));
+    }
+    if !hex.len().is_multiple_of(2) {
+        return Err(CryptoError::Recovery("Key share has an odd number of digits".to_string()));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| CryptoError::Recovery("Key share contains non-hex characters".to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_code_roundtrip() {
+        let key = MemoryVault::generate_key();
+        let code = RecoveryCode::from_key(&key).unwrap();
+
+        assert_eq!(code.words().count(), 24);
+        assert_eq!(code.to_key().unwrap(), key);
+
+        // Re-typed with sloppy spacing and capitals
+        let retyped = code.as_str().to_uppercase().replace(' ', "   ");
+        assert_eq!(RecoveryCode::parse(&retyped).unwrap().to_key().unwrap(), key);
+    }
+
+    #[test]
+    fn test_recovery_code_rejects_typos() {
+        // Fixed key so the typo below is guaranteed to break the checksum
+        let key = MemoryVault::key_from_bytes(&[7u8; 32]).unwrap();
+        let code = RecoveryCode::from_key(&key).unwrap();
+        let mut words: Vec<&str> = code.words().collect();
+
+        words[0] = if words[0] == "abandon" { "zoo" } else { "abandon" };
+        assert!(RecoveryCode::parse(&words.join(" ")).is_err());
+        assert!(RecoveryCode::parse("abandon abandon about").is_err());
+    }
+
+    #[test]
+    fn test_recovery_code_debug_is_redacted() {
+        let code = RecoveryCode::from_key(&MemoryVault::generate_key()).unwrap();
+
+        assert_eq!(format!("{:?}", code), "RecoveryCode(<redacted>)");
+    }
+
+    #[test]
+    fn test_any_threshold_shares_recover_key() {
+        let key = MemoryVault::generate_key();
+        let shares = split_key(&key, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        for subset in [[0, 1, 2], [0, 2, 4], [1, 3, 4]] {
+            let picked: Vec<KeyShare> = subset.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(combine_shares(&picked).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_too_few_shares_fail() {
+        let key = MemoryVault::generate_key();
+        let shares = split_key(&key, 3, 5).unwrap();
+
+        assert!(combine_shares(&shares[..2]).is_err());
+        // A duplicated share doesn't count twice
+        let duplicated = [shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(combine_shares(&duplicated).is_err());
+    }
+
+    #[test]
+    fn test_share_encoding_roundtrip_and_checksum() {
+        let key = MemoryVault::generate_key();
+        let shares = split_key(&key, 2, 3).unwrap();
+        let encoded: Vec<String> = shares.iter().map(KeyShare::encode).collect();
+
+        let parsed: Vec<KeyShare> = encoded[1..].iter().map(|s| KeyShare::parse(s).unwrap()).collect();
+        assert_eq!(parsed[0], shares[1]);
+        assert_eq!(combine_shares(&parsed).unwrap(), key);
+
+        // Flip one digit of the share value
+        let mut typo = encoded[0].clone().into_bytes();
+        let pos = SHARE_PREFIX.len() + 10;
+        typo[pos] = if typo[pos] == b'0' { b'1' } else { b'0' };
+        assert!(KeyShare::parse(&String::from_utf8(typo).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_schemes_rejected() {
+        let key = MemoryVault::generate_key();
+
+        assert!(split_key(&key, 1, 3).is_err());
+        assert!(split_key(&key, 4, 3).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use crate::error::{CryptoError, Result};
+use crate::KEY_SIZE;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "mlock")]
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(feature = "mlock")]
+use std::sync::Mutex;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Mlocked pages shared by live secrets, each with the number of secrets on it
+///
+/// Small secrets share pages, and `munlock` is not reference-counted by the
+/// kernel, so a page is only unlocked once the last secret on it is dropped.
+#[cfg(feature = "mlock")]
+static LOCKED_PAGES: Mutex<BTreeMap<usize, (usize, region::LockGuard)>> = Mutex::new(BTreeMap::new());
+
+/// Best-effort page lock keeping a secret out of swap
+///
+/// Only active with the `mlock` feature; failure (e.g. `RLIMIT_MEMLOCK`) is
+/// tolerated and reported through `is_memory_locked`.
+struct MemoryLock {
+    /// Address of the first page this secret holds a lock on, and the page count
+    #[cfg(feature = "mlock")]
+    pages: Option<(usize, usize)>,
+}
+
+impl MemoryLock {
+    fn new(bytes: &[u8]) -> Self {
+        #[cfg(feature = "mlock")]
+        {
+            let pages = if bytes.is_empty() { None } else { lock_pages(bytes) };
+            Self { pages }
+        }
+
+        #[cfg(not(feature = "mlock"))]
+        {
+            let _ = bytes;
+            Self {}
+        }
+    }
+
+    fn is_locked(&self) -> bool {
+        #[cfg(feature = "mlock")]
+        {
+            self.pages.is_some()
+        }
+
+        #[cfg(not(feature = "mlock"))]
+        {
+            false
+        }
+    }
+}
+
+impl Drop for MemoryLock {
+    fn drop(&mut self) {
+        #[cfg(feature = "mlock")]
+        if let Some((first, count)) = self.pages.take() {
+            let mut pages = LOCKED_PAGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            release_pages(&mut pages, first, count);
+        }
+    }
+}
+
+/// Take a reference on every page under `bytes`, locking the ones nobody holds yet
+#[cfg(feature = "mlock")]
+fn lock_pages(bytes: &[u8]) -> Option<(usize, usize)> {
+    let page = region::page::size();
+    let first = region::page::floor(bytes.as_ptr()) as usize;
+    let end = region::page::ceil(bytes.as_ptr_range().end) as usize;
+    let count = (end - first) / page;
+
+    let mut pages = LOCKED_PAGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for index in 0..count {
+        let address = first + index * page;
+        if let Some((users, _)) = pages.get_mut(&address) {
+            *users += 1;
+            continue;
+        }
+        match region::lock(address as *const u8, page) {
+            Ok(guard) => {
+                pages.insert(address, (1, guard));
+            }
+            Err(_) => {
+                release_pages(&mut pages, first, index);
+                return None;
+            }
+        }
+    }
+    Some((first, count))
+}
+
+/// Drop one reference on each page; dropping a page's guard unlocks it
+#[cfg(feature = "mlock")]
+fn release_pages(pages: &mut BTreeMap<usize, (usize, region::LockGuard)>, first: usize, count: usize) {
+    let page = region::page::size();
+    for address in (0..count).map(|index| first + index * page) {
+        if let Some((users, _)) = pages.get_mut(&address) {
+            *users -= 1;
+            if *users == 0 {
+                pages.remove(&address);
+            }
+        }
+    }
+}
+
+/// 256-bit secret key handle
+///
+/// Deliberately not `Clone`: share it by reference or behind an `Arc` so the
+/// key exists exactly once in memory. The bytes live in their own heap
+/// allocation (page-locked with the `mlock` feature) and are wiped on drop.
+/// Equality is constant-time and `Debug` never prints the key.
+pub struct SecretKey {
+    /// Declared before `bytes` so the pages are unlocked before the buffer is freed
+    lock: MemoryLock,
+    bytes: Box<[u8; KEY_SIZE]>,
+}
+
+impl SecretKey {
+    /// Copy a key out of `bytes` (the caller remains responsible for wiping its copy)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != KEY_SIZE {
+            return Err(CryptoError::InvalidKeyLength {
+                expected: KEY_SIZE,
+                actual: bytes.len(),
+            });
+        }
+        Ok(Self::filled_with(|key| key.copy_from_slice(bytes)))
+    }
+
+    /// Generate a random key from the OS RNG
+    pub fn generate() -> Self {
+        Self::filled_with(|key| getrandom::getrandom(key).expect("Failed to generate random key"))
+    }
+
+    /// Borrow the raw key bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+
+    /// Whether the key's pages are locked in RAM
+    pub fn is_memory_locked(&self) -> bool {
+        self.lock.is_locked()
+    }
+
+    // Write the key straight into its final allocation so no stray copy is left on the stack
+    fn filled_with(fill: impl FnOnce(&mut [u8])) -> Self {
+        let mut bytes = Box::new([0u8; KEY_SIZE]);
+        fill(bytes.as_mut_slice());
+        let lock = MemoryLock::new(bytes.as_slice());
+        Self { lock, bytes }
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretKey {}
+
+impl ConstantTimeEq for SecretKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.bytes.as_slice().ct_eq(other.bytes.as_slice())
+    }
+}
+
+impl PartialEq for SecretKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SecretKey {}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey(<redacted>)")
+    }
+}
+
+/// Variable-length secret (e.g. key material held by the vault daemon)
+///
+/// Same guarantees as [`SecretKey`]: not `Clone`, wiped on drop, optionally
+/// page-locked, constant-time equality and redacted `Debug`. Serializes like
+/// a `Vec<u8>` so it can replace one in wire types unchanged.
+pub struct SecretBytes {
+    /// Declared before `bytes` so the pages are unlocked before the buffer is freed
+    lock: MemoryLock,
+    bytes: Box<[u8]>,
+}
+
+impl SecretBytes {
+    /// Copy `bytes` into a fresh locked allocation
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let bytes: Box<[u8]> = bytes.into();
+        let lock = MemoryLock::new(&bytes);
+        Self { lock, bytes }
+    }
+
+    /// Take ownership of `bytes`, wiping the original buffer
+    pub fn new(mut bytes: Vec<u8>) -> Self {
+        let secret = Self::from_slice(&bytes);
+        bytes.zeroize();
+        secret
+    }
+
+    /// Borrow the raw bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Whether the bytes' pages are locked in RAM
+    pub fn is_memory_locked(&self) -> bool {
+        self.lock.is_locked()
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretBytes {}
+
+impl ConstantTimeEq for SecretBytes {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        // Length is not secret; slices of different lengths compare unequal
+        self.bytes.ct_eq(&other.bytes)
+    }
+}
+
+impl PartialEq for SecretBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SecretBytes {}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes(<redacted {} bytes>)", self.bytes.len())
+    }
+}
+
+impl Serialize for SecretBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.bytes.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let bytes = Zeroizing::new(Vec::<u8>::deserialize(deserializer)?);
+        Ok(Self::from_slice(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_key_equality() {
+        let a = SecretKey::from_bytes(&[1u8; KEY_SIZE]).unwrap();
+        let b = SecretKey::from_bytes(&[1u8; KEY_SIZE]).unwrap();
+        let c = SecretKey::from_bytes(&[2u8; KEY_SIZE]).unwrap();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(SecretKey::generate(), SecretKey::generate());
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let key = SecretKey::from_bytes(&[0xab; KEY_SIZE]).unwrap();
+        let bytes = SecretBytes::from_slice(b"hunter2");
+
+        assert_eq!(format!("{:?}", key), "SecretKey(<redacted>)");
+        assert_eq!(format!("{:?}", bytes), "SecretBytes(<redacted 7 bytes>)");
+        assert!(!format!("{:?}", bytes).contains("hunter2"));
+    }
+
+    #[test]
+    fn test_secret_key_rejects_wrong_length() {
+        assert!(matches!(
+            SecretKey::from_bytes(&[0u8; 16]),
+            Err(CryptoError::InvalidKeyLength { expected: 32, actual: 16 })
+        ));
+    }
+
+    #[test]
+    fn test_secret_bytes_equality_and_length() {
+        assert_eq!(SecretBytes::from_slice(b"abc"), SecretBytes::new(b"abc".to_vec()));
+        assert_ne!(SecretBytes::from_slice(b"abc"), SecretBytes::from_slice(b"abd"));
+        assert_ne!(SecretBytes::from_slice(b"abc"), SecretBytes::from_slice(b"abcd"));
+    }
+
+    #[cfg(feature = "mlock")]
+    #[test]
+    fn test_shared_page_stays_locked_until_last_secret_drops() {
+        // A page inside this buffer holds nothing else, so no other test touches its count
+        let page_size = region::page::size();
+        let buffer = vec![0u8; 3 * page_size];
+        let page = region::page::ceil(buffer.as_ptr()) as usize;
+        let start = page - buffer.as_ptr() as usize;
+        let (a, b) = buffer[start..start + page_size].split_at(page_size / 2);
+        let users = || LOCKED_PAGES.lock().unwrap().get(&page).map(|(users, _)| *users);
+
+        let first = MemoryLock::new(a);
+        let second = MemoryLock::new(b);
+        if !first.is_locked() || !second.is_locked() {
+            return; // RLIMIT_MEMLOCK exhausted; locking is best-effort
+        }
+        assert_eq!(users(), Some(2));
+        drop(second);
+        assert_eq!(users(), Some(1));
+        drop(first);
+        assert_eq!(users(), None);
+    }
+
+    #[test]
+    fn test_secret_bytes_serializes_like_vec() {
+        let secret = SecretBytes::from_slice(&[1, 2, 3]);
+        let json = serde_json::to_string(&secret).unwrap();
+
+        assert_eq!(json, serde_json::to_string(&vec![1u8, 2, 3]).unwrap());
+        assert_eq!(serde_json::from_str::<SecretBytes>(&json).unwrap(), secret);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::error::{CryptoError, Result};
+use crate::secret::SecretKey;
+use crate::KEY_SIZE;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// Size of an Ed25519 public key in bytes
+pub const SIGNING_PUBLIC_KEY_SIZE: usize = 32;
+
+/// Size of an Ed25519 signature in bytes
+pub const SIGNATURE_SIZE: usize = 64;
+
+/// Ed25519 keypair for signatures anyone holding the public key can check
+///
+/// Not `Clone`; the private half is wiped on drop and never printed.
+pub struct SigningKeyPair {
+    key: SigningKey,
+}
+
+impl SigningKeyPair {
+    pub fn generate() -> Self {
+        Self::from_secret(&SecretKey::generate())
+    }
+
+    /// Rebuild a keypair from its stored 32-byte seed
+    pub fn from_secret_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(Self::from_secret(&SecretKey::from_bytes(bytes)?))
+    }
+
+    fn from_secret(secret: &SecretKey) -> Self {
+        let mut seed = Zeroizing::new([0u8; KEY_SIZE]);
+        seed.copy_from_slice(secret.as_bytes());
+        Self {
+            key: SigningKey::from_bytes(&seed),
+        }
+    }
+
+    pub fn public_key(&self) -> [u8; SIGNING_PUBLIC_KEY_SIZE] {
+        self.key.verifying_key().to_bytes()
+    }
+
+    /// Seed bytes, for persisting through the vault daemon's key storage
+    pub fn secret_key(&self) -> SecretKey {
+        SecretKey::from_bytes(self.key.as_bytes()).expect("Ed25519 seeds are 32 bytes")
+    }
+
+    pub fn sign(&self, message: &[u8]) -> [u8; SIGNATURE_SIZE] {
+        self.key.sign(message).to_bytes()
+    }
+
+    /// Check a signature made by this keypair
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        verify_signature(&self.public_key(), message, signature).unwrap_or(false)
+    }
+}
+
+impl fmt::Debug for SigningKeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKeyPair")
+            .field("public", &self.public_key())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Check an Ed25519 signature against a public key
+///
+/// Fails only if `public_key` isn't a valid key; a bad signature is `Ok(false)`.
+pub fn verify_signature(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool> {
+    let public_key: [u8; SIGNING_PUBLIC_KEY_SIZE] =
+        public_key.try_into().map_err(|_| CryptoError::InvalidKeyLength {
+            expected: SIGNING_PUBLIC_KEY_SIZE,
+            actual: public_key.len(),
+        })?;
+    let public_key = VerifyingKey::from_bytes(&public_key)
+        .map_err(|e| CryptoError::Encoding(format!("Invalid Ed25519 public key: {}", e)))?;
+    let Ok(signature) = Signature::from_slice(signature) else {
+        return Ok(false);
+    };
+    Ok(public_key.verify(message, &signature).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let keypair = SigningKeyPair::generate();
+        let signature = keypair.sign(b"message");
+
+        assert!(verify_signature(&keypair.public_key(), b"message", &signature).unwrap());
+        assert!(!verify_signature(&keypair.public_key(), b"messagf", &signature).unwrap());
+        assert!(!keypair.verify(b"message", &signature[..32]));
+        assert!(!SigningKeyPair::generate().verify(b"message", &signature));
+        assert!(verify_signature(b"short", b"message", &signature).is_err());
+    }
+
+    #[test]
+    fn test_restore_from_seed() {
+        let keypair = SigningKeyPair::generate();
+        let restored = SigningKeyPair::from_secret_bytes(keypair.secret_key().as_bytes()).unwrap();
+
+        assert_eq!(restored.public_key(), keypair.public_key());
+        assert!(restored.verify(b"message", &keypair.sign(b"message")));
+    }
+}
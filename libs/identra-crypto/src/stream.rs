@@ -0,0 +1,420 @@
+use crate::aead::EncryptionKey;
+use crate::envelope::CipherSuiteId;
+use crate::error::{CryptoError, Result};
+use chacha20poly1305::{
+    aead::{
+        stream::{DecryptorBE32, EncryptorBE32},
+        KeyInit, Payload,
+    },
+    ChaCha20Poly1305, Key,
+};
+use std::io::{Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Magic bytes at the start of every encrypted stream
+pub const STREAM_MAGIC: &[u8; 4] = b"IDRS";
+
+/// Current stream format version
+pub const STREAM_VERSION: u8 = 1;
+
+/// Default plaintext chunk size (64 KiB)
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Largest chunk size accepted when decrypting (guards against huge allocations)
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// STREAM nonce prefix: 12-byte nonce minus 4-byte counter and 1-byte last flag
+const NONCE_PREFIX_SIZE: usize = 7;
+
+/// Poly1305 authentication tag size
+const TAG_SIZE: usize = 16;
+
+/// magic + version + suite + chunk size + nonce prefix
+const HEADER_SIZE: usize = STREAM_MAGIC.len() + 2 + 4 + NONCE_PREFIX_SIZE;
+
+/// Header written before the encrypted chunks
+///
+/// Layout: magic (4) | version (1) | suite (1) | chunk size (4, BE) | nonce prefix (7).
+/// The header is authenticated as associated data of every chunk.
+struct StreamHeader {
+    chunk_size: usize,
+    nonce_prefix: [u8; NONCE_PREFIX_SIZE],
+}
+
+impl StreamHeader {
+    fn generate(chunk_size: usize) -> Result<Self> {
+        validate_chunk_size(chunk_size)?;
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        getrandom::getrandom(&mut nonce_prefix)
+            .map_err(|e| CryptoError::RandomGeneration(e.to_string()))?;
+        Ok(Self { chunk_size, nonce_prefix })
+    }
+
+    fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut header = [0u8; HEADER_SIZE];
+        header[..4].copy_from_slice(STREAM_MAGIC);
+        header[4] = STREAM_VERSION;
+        header[5] = CipherSuiteId::ChaCha20Poly1305 as u8;
+        header[6..10].copy_from_slice(&(self.chunk_size as u32).to_be_bytes());
+        header[10..].copy_from_slice(&self.nonce_prefix);
+        header
+    }
+
+    fn parse(header: &[u8; HEADER_SIZE]) -> Result<Self> {
+        if &header[..4] != STREAM_MAGIC {
+            return Err(CryptoError::Stream("Missing stream magic bytes".to_string()));
+        }
+        if header[4] != STREAM_VERSION {
+            return Err(CryptoError::UnsupportedVersion(header[4]));
+        }
+        if CipherSuiteId::try_from(header[5])? != CipherSuiteId::ChaCha20Poly1305 {
+            return Err(CryptoError::UnsupportedCipherSuite(header[5]));
+        }
+
+        let chunk_size = u32::from_be_bytes([header[6], header[7], header[8], header[9]]) as usize;
+        validate_chunk_size(chunk_size)?;
+
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        nonce_prefix.copy_from_slice(&header[10..]);
+        Ok(Self { chunk_size, nonce_prefix })
+    }
+
+    fn encryptor(&self, key: &EncryptionKey) -> EncryptorBE32<ChaCha20Poly1305> {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key.as_bytes()));
+        EncryptorBE32::from_aead(cipher, (&self.nonce_prefix).into())
+    }
+
+    fn decryptor(&self, key: &EncryptionKey) -> DecryptorBE32<ChaCha20Poly1305> {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key.as_bytes()));
+        DecryptorBE32::from_aead(cipher, (&self.nonce_prefix).into())
+    }
+}
+
+fn validate_chunk_size(chunk_size: usize) -> Result<()> {
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(CryptoError::Stream(format!(
+            "Chunk size must be between 1 and {} bytes, got {}",
+            MAX_CHUNK_SIZE, chunk_size
+        )));
+    }
+    Ok(())
+}
+
+fn chunk_error(e: chacha20poly1305::aead::Error) -> CryptoError {
+    CryptoError::Stream(format!("Chunk authentication failed: {}", e))
+}
+
+/// Encrypt everything from `reader` into `writer` using chunked ChaCha20-Poly1305 (STREAM)
+///
+/// Each chunk gets its own nonce (prefix || counter || last flag), so dropping,
+/// reordering or truncating chunks is detected on decryption. A full-size chunk
+/// is never the last one: when the input is an exact multiple of `chunk_size` an
+/// empty final chunk is emitted.
+///
+/// # Returns
+/// Number of plaintext bytes encrypted
+pub fn encrypt_stream<R: Read, W: Write>(
+    key: &EncryptionKey,
+    mut reader: R,
+    mut writer: W,
+    chunk_size: usize,
+) -> Result<u64> {
+    let header = StreamHeader::generate(chunk_size)?;
+    let header_bytes = header.to_bytes();
+    writer.write_all(&header_bytes)?;
+
+    let mut encryptor = header.encryptor(key);
+    let mut buf = vec![0u8; chunk_size];
+    let mut total = 0u64;
+
+    loop {
+        let n = read_full(&mut reader, &mut buf)?;
+        total += n as u64;
+        let payload = Payload { msg: &buf[..n], aad: &header_bytes };
+
+        if n < chunk_size {
+            let chunk = encryptor.encrypt_last(payload).map_err(chunk_error)?;
+            writer.write_all(&chunk)?;
+            break;
+        }
+
+        let chunk = encryptor.encrypt_next(payload).map_err(chunk_error)?;
+        writer.write_all(&chunk)?;
+    }
+
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Decrypt a stream produced by [`encrypt_stream`]
+///
+/// Plaintext is written chunk by chunk as it is authenticated; if an error is
+/// returned, anything already written to `writer` must be discarded.
+///
+/// # Returns
+/// Number of plaintext bytes decrypted
+pub fn decrypt_stream<R: Read, W: Write>(
+    key: &EncryptionKey,
+    mut reader: R,
+    mut writer: W,
+) -> Result<u64> {
+    let mut header_bytes = [0u8; HEADER_SIZE];
+    if read_full(&mut reader, &mut header_bytes)? != HEADER_SIZE {
+        return Err(CryptoError::Stream("Truncated stream header".to_string()));
+    }
+    let header = StreamHeader::parse(&header_bytes)?;
+
+    let mut decryptor = header.decryptor(key);
+    let mut buf = vec![0u8; header.chunk_size + TAG_SIZE];
+    let mut total = 0u64;
+
+    loop {
+        let n = read_full(&mut reader, &mut buf)?;
+        if n < TAG_SIZE {
+            return Err(CryptoError::Stream("Stream truncated before final chunk".to_string()));
+        }
+        let payload = Payload { msg: &buf[..n], aad: &header_bytes };
+
+        if n < buf.len() {
+            let chunk = decryptor.decrypt_last(payload).map_err(chunk_error)?;
+            writer.write_all(&chunk)?;
+            total += chunk.len() as u64;
+            break;
+        }
+
+        let chunk = decryptor.decrypt_next(payload).map_err(chunk_error)?;
+        writer.write_all(&chunk)?;
+        total += chunk.len() as u64;
+    }
+
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Async variant of [`encrypt_stream`] for `tokio` readers and writers
+pub async fn encrypt_stream_async<R, W>(
+    key: &EncryptionKey,
+    mut reader: R,
+    mut writer: W,
+    chunk_size: usize,
+) -> Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let header = StreamHeader::generate(chunk_size)?;
+    let header_bytes = header.to_bytes();
+    writer.write_all(&header_bytes).await?;
+
+    let mut encryptor = header.encryptor(key);
+    let mut buf = vec![0u8; chunk_size];
+    let mut total = 0u64;
+
+    loop {
+        let n = read_full_async(&mut reader, &mut buf).await?;
+        total += n as u64;
+        let payload = Payload { msg: &buf[..n], aad: &header_bytes };
+
+        if n < chunk_size {
+            let chunk = encryptor.encrypt_last(payload).map_err(chunk_error)?;
+            writer.write_all(&chunk).await?;
+            break;
+        }
+
+        let chunk = encryptor.encrypt_next(payload).map_err(chunk_error)?;
+        writer.write_all(&chunk).await?;
+    }
+
+    writer.flush().await?;
+    Ok(total)
+}
+
+/// Async variant of [`decrypt_stream`] for `tokio` readers and writers
+pub async fn decrypt_stream_async<R, W>(
+    key: &EncryptionKey,
+    mut reader: R,
+    mut writer: W,
+) -> Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut header_bytes = [0u8; HEADER_SIZE];
+    if read_full_async(&mut reader, &mut header_bytes).await? != HEADER_SIZE {
+        return Err(CryptoError::Stream("Truncated stream header".to_string()));
+    }
+    let header = StreamHeader::parse(&header_bytes)?;
+
+    let mut decryptor = header.decryptor(key);
+    let mut buf = vec![0u8; header.chunk_size + TAG_SIZE];
+    let mut total = 0u64;
+
+    loop {
+        let n = read_full_async(&mut reader, &mut buf).await?;
+        if n < TAG_SIZE {
+            return Err(CryptoError::Stream("Stream truncated before final chunk".to_string()));
+        }
+        let payload = Payload { msg: &buf[..n], aad: &header_bytes };
+
+        if n < buf.len() {
+            let chunk = decryptor.decrypt_last(payload).map_err(chunk_error)?;
+            writer.write_all(&chunk).await?;
+            total += chunk.len() as u64;
+            break;
+        }
+
+        let chunk = decryptor.decrypt_next(payload).map_err(chunk_error)?;
+        writer.write_all(&chunk).await?;
+        total += chunk.len() as u64;
+    }
+
+    writer.flush().await?;
+    Ok(total)
+}
+
+/// Fill `buf` from `reader`, stopping early only at EOF
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+async fn read_full_async<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNK: usize = 64;
+
+    fn encrypt(key: &EncryptionKey, plaintext: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        encrypt_stream(key, plaintext, &mut out, CHUNK).unwrap();
+        out
+    }
+
+    fn decrypt(key: &EncryptionKey, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        decrypt_stream(key, ciphertext, &mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_roundtrip_various_lengths() {
+        let key = EncryptionKey::generate();
+        // Empty, short, exact multiple of the chunk size, and ragged
+        for len in [0, 10, CHUNK, CHUNK * 3, CHUNK * 3 + 17] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let ciphertext = encrypt(&key, &plaintext);
+
+            assert_eq!(decrypt(&key, &ciphertext).unwrap(), plaintext, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_wrong_key_fails() {
+        let ciphertext = encrypt(&EncryptionKey::generate(), b"attachment");
+
+        assert!(decrypt(&EncryptionKey::generate(), &ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_truncation_detected() {
+        let key = EncryptionKey::generate();
+        let plaintext = vec![7u8; CHUNK * 3 + 5];
+        let ciphertext = encrypt(&key, &plaintext);
+        let full_chunk = CHUNK + TAG_SIZE;
+
+        // Dropping the final chunk leaves a stream that ends on a chunk boundary
+        let without_last = &ciphertext[..HEADER_SIZE + full_chunk * 3];
+        assert!(decrypt(&key, without_last).is_err());
+
+        // Dropping whole chunks and the tail: a full chunk can't pose as the last one
+        let two_chunks = &ciphertext[..HEADER_SIZE + full_chunk * 2];
+        assert!(decrypt(&key, two_chunks).is_err());
+
+        // Cutting into the final chunk breaks its tag
+        assert!(decrypt(&key, &ciphertext[..ciphertext.len() - 1]).is_err());
+
+        // Header only
+        assert!(decrypt(&key, &ciphertext[..HEADER_SIZE]).is_err());
+    }
+
+    #[test]
+    fn test_reordering_detected() {
+        let key = EncryptionKey::generate();
+        let plaintext: Vec<u8> = (0..CHUNK * 3).map(|i| (i % 251) as u8).collect();
+        let mut ciphertext = encrypt(&key, &plaintext);
+        let full_chunk = CHUNK + TAG_SIZE;
+
+        // Swap the first two chunks
+        let first = HEADER_SIZE;
+        let second = HEADER_SIZE + full_chunk;
+        let chunk_one = ciphertext[first..second].to_vec();
+        let chunk_two = ciphertext[second..second + full_chunk].to_vec();
+        ciphertext[first..second].copy_from_slice(&chunk_two);
+        ciphertext[second..second + full_chunk].copy_from_slice(&chunk_one);
+
+        assert!(decrypt(&key, &ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_tampered_header_detected() {
+        let key = EncryptionKey::generate();
+        let mut ciphertext = encrypt(&key, b"header is authenticated");
+        ciphertext[HEADER_SIZE - 1] ^= 0x01;
+
+        assert!(decrypt(&key, &ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_invalid_chunk_size_rejected() {
+        let key = EncryptionKey::generate();
+        let mut out = Vec::new();
+
+        assert!(encrypt_stream(&key, &b"x"[..], &mut out, 0).is_err());
+        assert!(encrypt_stream(&key, &b"x"[..], &mut out, MAX_CHUNK_SIZE + 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_async_roundtrip_and_interop() {
+        let key = EncryptionKey::generate();
+        let plaintext = vec![42u8; CHUNK * 2 + 9];
+
+        let mut ciphertext = Vec::new();
+        encrypt_stream_async(&key, plaintext.as_slice(), &mut ciphertext, CHUNK).await.unwrap();
+
+        let mut decrypted = Vec::new();
+        decrypt_stream_async(&key, ciphertext.as_slice(), &mut decrypted).await.unwrap();
+        assert_eq!(decrypted, plaintext);
+
+        // Sync and async share the same format
+        assert_eq!(decrypt(&key, &ciphertext).unwrap(), plaintext);
+    }
+
+    #[tokio::test]
+    async fn test_async_truncation_detected() {
+        let key = EncryptionKey::generate();
+        let ciphertext = encrypt(&key, &[1u8; CHUNK * 2]);
+        let truncated = &ciphertext[..HEADER_SIZE + (CHUNK + TAG_SIZE) * 2];
+
+        let mut out = Vec::new();
+        assert!(decrypt_stream_async(&key, truncated, &mut out).await.is_err());
+    }
+}
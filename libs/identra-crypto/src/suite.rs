@@ -0,0 +1,209 @@
+use crate::envelope::CipherSuiteId;
+use crate::error::{CryptoError, Result};
+use crate::KEY_SIZE;
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm,
+};
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+
+/// Size of an XChaCha20-Poly1305 extended nonce in bytes (192 bits)
+pub const XNONCE_SIZE: usize = 24;
+
+/// An AEAD cipher that can seal and open memory envelopes
+///
+/// Every suite takes a 256-bit key, so the same [`crate::VaultKey`] works with
+/// any of them; only the nonce size differs.
+pub trait CipherSuite: Send + Sync {
+    /// Identifier recorded in the envelope header
+    fn id(&self) -> CipherSuiteId;
+
+    /// Human-readable name
+    fn name(&self) -> &'static str;
+
+    /// Nonce size in bytes
+    fn nonce_size(&self) -> usize;
+
+    /// Encrypt `plaintext`, authenticating `aad` alongside it
+    fn seal(&self, key: &[u8], nonce: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decrypt `ciphertext`, verifying `aad`
+    fn open(&self, key: &[u8], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>>;
+
+    /// Generate a random nonce of the right size
+    fn generate_nonce(&self) -> Vec<u8> {
+        crate::random::generate_random_bytes(self.nonce_size())
+    }
+}
+
+/// AES-256-GCM (96-bit nonces; fastest with AES-NI)
+pub struct Aes256GcmSuite;
+
+/// ChaCha20-Poly1305 (96-bit nonces; fast without AES hardware)
+pub struct ChaCha20Poly1305Suite;
+
+/// XChaCha20-Poly1305 (192-bit nonces; random nonces are safe for billions of messages)
+pub struct XChaCha20Poly1305Suite;
+
+macro_rules! impl_cipher_suite {
+    ($suite:ty, $cipher:ty, $id:expr, $name:expr, $nonce_size:expr) => {
+        impl CipherSuite for $suite {
+            fn id(&self) -> CipherSuiteId {
+                $id
+            }
+
+            fn name(&self) -> &'static str {
+                $name
+            }
+
+            fn nonce_size(&self) -> usize {
+                $nonce_size
+            }
+
+            fn seal(&self, key: &[u8], nonce: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+                check_lengths(key, nonce, $nonce_size)?;
+                <$cipher>::new_from_slice(key)
+                    .map_err(|e| CryptoError::Encryption(e.to_string()))?
+                    .encrypt(nonce.into(), Payload { msg: plaintext, aad })
+                    .map_err(|e| CryptoError::Encryption(e.to_string()))
+            }
+
+            fn open(&self, key: &[u8], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+                check_lengths(key, nonce, $nonce_size)?;
+                <$cipher>::new_from_slice(key)
+                    .map_err(|e| CryptoError::Decryption(e.to_string()))?
+                    .decrypt(nonce.into(), Payload { msg: ciphertext, aad })
+                    .map_err(|_| CryptoError::Decryption("Wrong Key or Corrupted Data".to_string()))
+            }
+        }
+    };
+}
+
+impl_cipher_suite!(Aes256GcmSuite, Aes256Gcm, CipherSuiteId::Aes256Gcm, "AES-256-GCM", crate::NONCE_SIZE);
+impl_cipher_suite!(
+    ChaCha20Poly1305Suite,
+    ChaCha20Poly1305,
+    CipherSuiteId::ChaCha20Poly1305,
+    "ChaCha20-Poly1305",
+    crate::NONCE_SIZE
+);
+impl_cipher_suite!(
+    XChaCha20Poly1305Suite,
+    XChaCha20Poly1305,
+    CipherSuiteId::XChaCha20Poly1305,
+    "XChaCha20-Poly1305",
+    XNONCE_SIZE
+);
+
+fn check_lengths(key: &[u8], nonce: &[u8], nonce_size: usize) -> Result<()> {
+    if key.len() != KEY_SIZE {
+        return Err(CryptoError::InvalidKeyLength { expected: KEY_SIZE, actual: key.len() });
+    }
+    if nonce.len() != nonce_size {
+        return Err(CryptoError::InvalidNonceLength { expected: nonce_size, actual: nonce.len() });
+    }
+    Ok(())
+}
+
+/// Look up the implementation for a suite id
+pub fn cipher_suite(id: CipherSuiteId) -> &'static dyn CipherSuite {
+    match id {
+        CipherSuiteId::Aes256Gcm => &Aes256GcmSuite,
+        CipherSuiteId::ChaCha20Poly1305 => &ChaCha20Poly1305Suite,
+        CipherSuiteId::XChaCha20Poly1305 => &XChaCha20Poly1305Suite,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    const SUNSCREEN: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+    const ALL: [CipherSuiteId; 3] = [
+        CipherSuiteId::Aes256Gcm,
+        CipherSuiteId::ChaCha20Poly1305,
+        CipherSuiteId::XChaCha20Poly1305,
+    ];
+
+    #[test]
+    fn test_aes256gcm_vector() {
+        // NIST GCM spec test case 14: zero key, zero IV, one zero block
+        let suite = cipher_suite(CipherSuiteId::Aes256Gcm);
+        let ciphertext = suite.seal(&[0u8; 32], &[0u8; 12], &[0u8; 16], &[]).unwrap();
+
+        assert_eq!(
+            ciphertext,
+            hex("cea7403d4d606b6e074ec5d3baf39d18 d0d1c8a799996bf0265b98b5d48ab919")
+        );
+    }
+
+    #[test]
+    fn test_chacha20poly1305_vector() {
+        // RFC 8439 section 2.8.2
+        let suite = cipher_suite(CipherSuiteId::ChaCha20Poly1305);
+        let key: Vec<u8> = (0x80..=0x9f).collect();
+        let nonce = hex("070000004041424344454647");
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+
+        let ciphertext = suite.seal(&key, &nonce, SUNSCREEN, &aad).unwrap();
+        let (body, tag) = ciphertext.split_at(SUNSCREEN.len());
+
+        assert_eq!(&body[..16], hex("d31a8d34648e60db7b86afbc53ef7ec2").as_slice());
+        assert_eq!(tag, hex("1ae10b594f09e26a7e902ecbd0600691").as_slice());
+        assert_eq!(suite.open(&key, &nonce, &ciphertext, &aad).unwrap(), SUNSCREEN);
+    }
+
+    #[test]
+    fn test_xchacha20poly1305_vector() {
+        // draft-irtf-cfrg-xchacha-03 appendix A.3.1
+        let suite = cipher_suite(CipherSuiteId::XChaCha20Poly1305);
+        let key: Vec<u8> = (0x80..=0x9f).collect();
+        let nonce: Vec<u8> = (0x40..=0x57).collect();
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+
+        let ciphertext = suite.seal(&key, &nonce, SUNSCREEN, &aad).unwrap();
+        let (body, tag) = ciphertext.split_at(SUNSCREEN.len());
+
+        assert_eq!(&body[..16], hex("bd6d179d3e83d43b9576579493c0e939").as_slice());
+        assert_eq!(tag, hex("c0875924c1c7987947deafd8780acf49").as_slice());
+        assert_eq!(suite.open(&key, &nonce, &ciphertext, &aad).unwrap(), SUNSCREEN);
+    }
+
+    #[test]
+    fn test_suites_do_not_interoperate() {
+        let key = [7u8; 32];
+        let aad = b"row-1";
+
+        for sealer in ALL {
+            let sealer = cipher_suite(sealer);
+            let nonce = sealer.generate_nonce();
+            let ciphertext = sealer.seal(&key, &nonce, b"cross-suite", aad).unwrap();
+            assert_eq!(sealer.open(&key, &nonce, &ciphertext, aad).unwrap(), b"cross-suite");
+
+            for opener in ALL.into_iter().filter(|id| *id != sealer.id()) {
+                let opener = cipher_suite(opener);
+                let nonce = &nonce[..nonce.len().min(opener.nonce_size())];
+                assert!(opener.open(&key, nonce, &ciphertext, aad).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrong_nonce_length_rejected() {
+        let suite = cipher_suite(CipherSuiteId::XChaCha20Poly1305);
+
+        assert!(matches!(
+            suite.seal(&[0u8; 32], &[0u8; 12], b"x", &[]),
+            Err(CryptoError::InvalidNonceLength { expected: 24, actual: 12 })
+        ));
+    }
+}
@@ -0,0 +1,570 @@
+use crate::envelope::{CipherSuiteId, Envelope, Packet, ENVELOPE_VERSION, MAX_KEY_ID_LEN};
+use crate::error::{CryptoError, Result};
+use crate::secret::SecretKey;
+use crate::suite::cipher_suite;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use zeroize::Zeroizing;
+
+/// 256-bit key used to seal memories (valid for every [`CipherSuiteId`])
+pub type VaultKey = SecretKey;
+
+/// Memory metadata entry recording which key sealed the content
+pub const KEY_ID_METADATA: &str = "key_id";
+
+/// Memory metadata entry recording the user a memory was bound to when stored
+pub const OWNER_METADATA: &str = "owner";
+
+pub struct MemoryVault;
+
+/// The stored row a memory ciphertext belongs to
+///
+/// Its associated data is authenticated with the ciphertext, so copying the
+/// encrypted `content` into another row (or another user's row) fails to open.
+pub struct MemoryBinding<'a> {
+    pub memory_id: &'a str,
+    pub user_id: &'a str,
+    pub metadata: &'a HashMap<String, String>,
+}
+
+impl<'a> MemoryBinding<'a> {
+    pub fn new(memory_id: &'a str, user_id: &'a str, metadata: &'a HashMap<String, String>) -> Self {
+        Self { memory_id, user_id, metadata }
+    }
+
+    /// Binding for a stored row, opened by the authenticated `user_id`
+    ///
+    /// A row whose recorded owner is someone else is refused outright: its
+    /// metadata travels with it, so only the current user can tell a row
+    /// copied in from another account. Rows without an owner entry are bound
+    /// to `user_id`.
+    pub fn for_row(memory_id: &'a str, metadata: &'a HashMap<String, String>, user_id: &'a str) -> Result<Self> {
+        if metadata.get(OWNER_METADATA).is_some_and(|owner| owner != user_id) {
+            return Err(CryptoError::Decryption("Memory belongs to another user".to_string()));
+        }
+        Ok(Self::new(memory_id, user_id, metadata))
+    }
+
+    /// Canonical associated data: domain tag, memory id, user id and metadata hash
+    pub fn associated_data(&self) -> Vec<u8> {
+        let mut aad = Vec::new();
+        push_field(&mut aad, b"identra-memory-v1");
+        push_field(&mut aad, self.memory_id.as_bytes());
+        push_field(&mut aad, self.user_id.as_bytes());
+        aad.extend_from_slice(&metadata_hash(self.metadata));
+        aad
+    }
+}
+
+/// SHA-256 over the metadata entries sorted by key, each length-prefixed
+pub fn metadata_hash(metadata: &HashMap<String, String>) -> [u8; 32] {
+    let mut entries: Vec<_> = metadata.iter().collect();
+    entries.sort();
+
+    let mut hasher = Sha256::new();
+    for (key, value) in entries {
+        hasher.update((key.len() as u32).to_be_bytes());
+        hasher.update(key.as_bytes());
+        hasher.update((value.len() as u32).to_be_bytes());
+        hasher.update(value.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+fn push_field(buf: &mut Vec<u8>, field: &[u8]) {
+    buf.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    buf.extend_from_slice(field);
+}
+
+impl MemoryVault {
+    /// Generates a cryptographically secure 256-bit key
+    pub fn generate_key() -> VaultKey {
+        SecretKey::generate()
+    }
+
+    /// Rebuilds a key from raw bytes (e.g. loaded from disk or the vault daemon)
+    pub fn key_from_bytes(bytes: &[u8]) -> Result<VaultKey> {
+        SecretKey::from_bytes(bytes)
+    }
+
+    /// Stable, non-secret identifier for a key (first 8 bytes of its SHA-256 fingerprint)
+    pub fn key_id(key: &VaultKey) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"identra-key-id");
+        hasher.update(key.as_bytes());
+        hasher.finalize()[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Encrypts a string into a Base64 envelope tagged with the key's fingerprint
+    ///
+    /// The `lock*` functions seal with the default suite (AES-256-GCM); use the
+    /// `*_with_suite` variants for vaults configured with another suite.
+    pub fn lock(data: &str, key: &VaultKey) -> Result<String> {
+        Self::lock_with_key_id(data, key, &Self::key_id(key))
+    }
+
+    /// Encrypts a string into a Base64 envelope tagged with an explicit key id
+    pub fn lock_with_key_id(data: &str, key: &VaultKey, key_id: &str) -> Result<String> {
+        Self::seal(CipherSuiteId::default(), data, key, key_id, &[])
+    }
+
+    /// Encrypts a memory bound to the row it will be stored in
+    pub fn lock_bound(data: &str, key: &VaultKey, binding: &MemoryBinding) -> Result<String> {
+        Self::lock_bound_with_suite(CipherSuiteId::default(), data, key, binding)
+    }
+
+    /// Encrypts a memory bound to its row using the given cipher suite
+    pub fn lock_bound_with_suite(
+        suite: CipherSuiteId,
+        data: &str,
+        key: &VaultKey,
+        binding: &MemoryBinding,
+    ) -> Result<String> {
+        Self::seal(suite, data, key, &Self::key_id(key), &binding.associated_data())
+    }
+
+    /// Encrypts a string, authenticating caller-supplied associated data alongside it
+    pub fn lock_with_aad(data: &str, key: &VaultKey, aad: &[u8]) -> Result<String> {
+        Self::seal(CipherSuiteId::default(), data, key, &Self::key_id(key), aad)
+    }
+
+    /// Encrypts raw bytes into a binary envelope (no Base64), e.g. for the vault daemon
+    pub fn seal_bytes(data: &[u8], key: &VaultKey, aad: &[u8]) -> Result<Vec<u8>> {
+        Self::seal_bytes_with_suite(CipherSuiteId::default(), data, key, aad)
+    }
+
+    /// Like [`Self::seal_bytes`] with the given cipher suite; Base64 of the result is a packet
+    pub fn seal_bytes_with_suite(suite: CipherSuiteId, data: &[u8], key: &VaultKey, aad: &[u8]) -> Result<Vec<u8>> {
+        Ok(Self::seal_envelope(suite, data, key, &Self::key_id(key), aad)?.to_bytes())
+    }
+
+    /// Decrypts an envelope produced by [`Self::seal_bytes`]
+    pub fn open_bytes(envelope: &[u8], key: &VaultKey, aad: &[u8]) -> Result<Vec<u8>> {
+        Self::open_envelope(&Envelope::parse(envelope)?, key, aad)
+    }
+
+    /// Decrypts a Base64 packet back into plaintext using the provided session key.
+    ///
+    /// Accepts enveloped packets of any suite and legacy `nonce || ciphertext` packets.
+    /// Packets sealed with associated data must be opened with [`Self::open_with_aad`].
+    pub fn open(enc_packet: &str, key: &VaultKey) -> Result<String> {
+        Self::open_with_aad(enc_packet, key, &[])
+    }
+
+    /// Decrypts a memory, verifying it belongs to the given row
+    pub fn open_bound(enc_packet: &str, key: &VaultKey, binding: &MemoryBinding) -> Result<String> {
+        Self::open_with_aad(enc_packet, key, &binding.associated_data())
+    }
+
+    /// Decrypts a memory that must already be bound to the given row
+    ///
+    /// Unlike [`Self::open_bound`], legacy and unbound packets are refused: they
+    /// carry no associated data, so any of them would open in any row.
+    pub fn open_bound_strict(enc_packet: &str, key: &VaultKey, binding: &MemoryBinding) -> Result<String> {
+        if !Self::is_bound(enc_packet)? {
+            return Err(CryptoError::Decryption("Packet is not bound to a row".to_string()));
+        }
+        Self::open_bound(enc_packet, key, binding)
+    }
+
+    /// Whether a packet was sealed with associated data (legacy packets never are)
+    pub fn is_bound(enc_packet: &str) -> Result<bool> {
+        Ok(match Self::inspect(enc_packet)? {
+            Packet::Envelope(envelope) => envelope.aad_len > 0,
+            Packet::Legacy { .. } => false,
+        })
+    }
+
+    /// Decrypts a Base64 packet, verifying the associated data it was sealed with
+    pub fn open_with_aad(enc_packet: &str, key: &VaultKey, aad: &[u8]) -> Result<String> {
+        let packet_bytes = BASE64.decode(enc_packet)
+            .map_err(|e| CryptoError::Encoding(format!("Base64 decode failed: {}", e)))?;
+
+        let plaintext_bytes = match Packet::parse(&packet_bytes) {
+            Ok(Packet::Envelope(envelope)) => match Self::open_envelope(&envelope, key, aad) {
+                Ok(plaintext) => plaintext,
+                Err(err) if aad.is_empty() => Self::open_magic_legacy(&packet_bytes, key).map_err(|_| err)?,
+                Err(err) => return Err(err),
+            },
+            // Legacy packets predate associated data, so there is nothing to verify
+            Ok(Packet::Legacy { nonce, ciphertext }) => Self::open_legacy(&nonce, &ciphertext, key)?,
+            Err(err) if aad.is_empty() => Self::open_magic_legacy(&packet_bytes, key).map_err(|_| err)?,
+            Err(err) => return Err(err),
+        };
+
+        String::from_utf8(plaintext_bytes)
+            .map_err(|e| CryptoError::Encoding(format!("UTF-8 Error: {}", e)))
+    }
+
+    /// Re-encrypts a memory under `new_key`, moving it from `old_binding` to `new_binding`
+    ///
+    /// Packets sealed without associated data (legacy or unbound) are opened as-is
+    /// and come out bound, so rotation also upgrades older memories.
+    pub fn rekey(
+        enc_packet: &str,
+        old_key: &VaultKey,
+        new_key: &VaultKey,
+        old_binding: &MemoryBinding,
+        new_binding: &MemoryBinding,
+    ) -> Result<String> {
+        Self::rekey_with_suite(CipherSuiteId::default(), enc_packet, old_key, new_key, old_binding, new_binding)
+    }
+
+    /// Like [`Self::rekey`], re-sealing with `suite` (which may differ from the packet's)
+    pub fn rekey_with_suite(
+        suite: CipherSuiteId,
+        enc_packet: &str,
+        old_key: &VaultKey,
+        new_key: &VaultKey,
+        old_binding: &MemoryBinding,
+        new_binding: &MemoryBinding,
+    ) -> Result<String> {
+        let plaintext = Zeroizing::new(if Self::is_bound(enc_packet)? {
+            Self::open_bound(enc_packet, old_key, old_binding)?
+        } else {
+            Self::open(enc_packet, old_key)?
+        });
+
+        Self::lock_bound_with_suite(suite, &plaintext, new_key, new_binding)
+    }
+
+    /// Key id recorded in a packet's envelope (`None` for legacy packets)
+    pub fn packet_key_id(enc_packet: &str) -> Result<Option<String>> {
+        Ok(match Self::inspect(enc_packet)? {
+            Packet::Envelope(envelope) => Some(envelope.key_id),
+            Packet::Legacy { .. } => None,
+        })
+    }
+
+    /// Parses a Base64 packet without decrypting it (e.g. to read the key id)
+    pub fn inspect(enc_packet: &str) -> Result<Packet> {
+        let packet_bytes = BASE64.decode(enc_packet)
+            .map_err(|e| CryptoError::Encoding(format!("Base64 decode failed: {}", e)))?;
+        Packet::parse(&packet_bytes)
+    }
+
+    fn seal(suite: CipherSuiteId, data: &str, key: &VaultKey, key_id: &str, aad: &[u8]) -> Result<String> {
+        let envelope = Self::seal_envelope(suite, data.as_bytes(), key, key_id, aad)?;
+        Ok(BASE64.encode(envelope.to_bytes()))
+    }
+
+    fn seal_envelope(suite: CipherSuiteId, data: &[u8], key: &VaultKey, key_id: &str, aad: &[u8]) -> Result<Envelope> {
+        if key_id.len() > MAX_KEY_ID_LEN {
+            return Err(CryptoError::InvalidEnvelope(format!(
+                "Key id too long: {} bytes (max {})",
+                key_id.len(),
+                MAX_KEY_ID_LEN
+            )));
+        }
+        let aad_len = u32::try_from(aad.len())
+            .map_err(|_| CryptoError::InvalidEnvelope("Associated data too long".to_string()))?;
+
+        let cipher = cipher_suite(suite);
+        let mut envelope = Envelope {
+            version: ENVELOPE_VERSION,
+            suite,
+            key_id: key_id.to_string(),
+            nonce: cipher.generate_nonce(),
+            aad_len,
+            ciphertext: Vec::new(),
+        };
+
+        // The header is authenticated so suite/key id can't be swapped
+        let associated = [envelope.header_bytes().as_slice(), aad].concat();
+        envelope.ciphertext = cipher.seal(key.as_bytes(), &envelope.nonce, data, &associated)?;
+
+        Ok(envelope)
+    }
+
+    fn open_envelope(envelope: &Envelope, key: &VaultKey, aad: &[u8]) -> Result<Vec<u8>> {
+        if envelope.aad_len as usize != aad.len() {
+            return Err(CryptoError::Decryption(format!(
+                "Associated data mismatch: packet expects {} bytes, got {}",
+                envelope.aad_len,
+                aad.len()
+            )));
+        }
+
+        let associated = [envelope.header_bytes().as_slice(), aad].concat();
+        cipher_suite(envelope.suite).open(key.as_bytes(), &envelope.nonce, &envelope.ciphertext, &associated)
+    }
+
+    /// A legacy packet whose random nonce happens to start with the magic bytes
+    ///
+    /// Only tried where a legacy packet is acceptable (no associated data
+    /// expected); the AEAD tag still decides, so a damaged envelope fails.
+    fn open_magic_legacy(packet_bytes: &[u8], key: &VaultKey) -> Result<Vec<u8>> {
+        let Packet::Legacy { nonce, ciphertext } = Packet::parse_legacy(packet_bytes)? else {
+            return Err(CryptoError::Decryption("Not a legacy packet".to_string()));
+        };
+        Self::open_legacy(&nonce, &ciphertext, key)
+    }
+
+    fn open_legacy(nonce: &[u8], ciphertext: &[u8], key: &VaultKey) -> Result<Vec<u8>> {
+        // Pre-envelope packets were always AES-256-GCM
+        cipher_suite(CipherSuiteId::Aes256Gcm).open(key.as_bytes(), nonce, ciphertext, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::ENVELOPE_MAGIC;
+    use crate::NONCE_SIZE;
+
+    #[test]
+    fn test_seal_bytes_roundtrip() {
+        let key = MemoryVault::generate_key();
+        let sealed = MemoryVault::seal_bytes(&[0, 159, 146, 150], &key, b"row-1").unwrap();
+
+        assert_eq!(MemoryVault::open_bytes(&sealed, &key, b"row-1").unwrap(), [0, 159, 146, 150]);
+        assert!(MemoryVault::open_bytes(&sealed, &key, b"row-2").is_err());
+        assert!(MemoryVault::open_bytes(&sealed, &MemoryVault::generate_key(), b"row-1").is_err());
+    }
+
+    #[test]
+    fn test_sealed_bytes_are_packets() {
+        let key = MemoryVault::generate_key();
+        let metadata = HashMap::new();
+        let binding = MemoryBinding::new("memory-1", "user-1", &metadata);
+        let sealed = MemoryVault::seal_bytes_with_suite(
+            CipherSuiteId::XChaCha20Poly1305,
+            b"remember the milk",
+            &key,
+            &binding.associated_data(),
+        )
+        .unwrap();
+
+        let packet = BASE64.encode(&sealed);
+        assert_eq!(MemoryVault::open_bound_strict(&packet, &key, &binding).unwrap(), "remember the milk");
+        assert_eq!(MemoryVault::packet_key_id(&packet).unwrap(), Some(MemoryVault::key_id(&key)));
+    }
+
+    #[test]
+    fn test_lock_open_roundtrip() {
+        let key = MemoryVault::generate_key();
+        let packet = MemoryVault::lock("remember the milk", &key).unwrap();
+
+        assert_eq!(MemoryVault::open(&packet, &key).unwrap(), "remember the milk");
+    }
+
+    #[test]
+    fn test_open_with_wrong_key_fails() {
+        let packet = MemoryVault::lock("secret", &MemoryVault::generate_key()).unwrap();
+
+        assert!(MemoryVault::open(&packet, &MemoryVault::generate_key()).is_err());
+    }
+
+    #[test]
+    fn test_key_from_bytes_rejects_wrong_length() {
+        assert!(matches!(
+            MemoryVault::key_from_bytes(&[0u8; 16]),
+            Err(CryptoError::InvalidKeyLength { expected: 32, actual: 16 })
+        ));
+    }
+
+    #[test]
+    fn test_lock_records_key_id() {
+        let key = MemoryVault::generate_key();
+        let packet = MemoryVault::lock_with_key_id("hello", &key, "session-2024").unwrap();
+
+        match MemoryVault::inspect(&packet).unwrap() {
+            Packet::Envelope(envelope) => {
+                assert_eq!(envelope.key_id, "session-2024");
+                assert_eq!(envelope.suite, CipherSuiteId::Aes256Gcm);
+            }
+            other => panic!("expected envelope, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tampered_header_fails() {
+        let key = MemoryVault::generate_key();
+        let packet = MemoryVault::lock_with_key_id("hello", &key, "a").unwrap();
+        let mut bytes = BASE64.decode(packet).unwrap();
+        // Flip the key id byte; the header is authenticated so this must fail
+        bytes[7] = b'b';
+
+        assert!(MemoryVault::open(&BASE64.encode(bytes), &key).is_err());
+    }
+
+    #[test]
+    fn test_open_legacy_packet() {
+        use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+        use aes_gcm::Aes256Gcm;
+
+        let key = MemoryVault::generate_key();
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new_from_slice(key.as_bytes())
+            .unwrap()
+            .encrypt(&nonce, b"old memory".as_ref())
+            .unwrap();
+        let mut packet = nonce.to_vec();
+        packet.extend_from_slice(&ciphertext);
+
+        assert_eq!(MemoryVault::open(&BASE64.encode(packet), &key).unwrap(), "old memory");
+    }
+
+    #[test]
+    fn test_bound_memory_cannot_be_swapped() {
+        let key = MemoryVault::generate_key();
+        let metadata = HashMap::from([("type".to_string(), "note".to_string())]);
+        let row = MemoryBinding::new("memory-1", "user-a", &metadata);
+        let packet = MemoryVault::lock_bound("bound secret", &key, &row).unwrap();
+
+        assert_eq!(MemoryVault::open_bound(&packet, &key, &row).unwrap(), "bound secret");
+
+        // Copied into another row, another user's row, or with edited metadata
+        let other_row = MemoryBinding::new("memory-2", "user-a", &metadata);
+        let other_user = MemoryBinding::new("memory-1", "user-b", &metadata);
+        let edited = HashMap::from([("type".to_string(), "chat".to_string())]);
+        let edited_row = MemoryBinding::new("memory-1", "user-a", &edited);
+
+        assert!(MemoryVault::open_bound(&packet, &key, &other_row).is_err());
+        assert!(MemoryVault::open_bound(&packet, &key, &other_user).is_err());
+        assert!(MemoryVault::open_bound(&packet, &key, &edited_row).is_err());
+        assert!(MemoryVault::open(&packet, &key).is_err());
+    }
+
+    #[test]
+    fn test_row_binding_refuses_other_owners() {
+        let key = MemoryVault::generate_key();
+        let metadata = HashMap::from([(OWNER_METADATA.to_string(), "user-a".to_string())]);
+        let packet = MemoryVault::lock_bound("note", &key, &MemoryBinding::new("m1", "user-a", &metadata)).unwrap();
+
+        let row = MemoryBinding::for_row("m1", &metadata, "user-a").unwrap();
+        assert_eq!(MemoryVault::open_bound(&packet, &key, &row).unwrap(), "note");
+        // The whole row copied into user-b's account doesn't open for user-b
+        assert!(MemoryBinding::for_row("m1", &metadata, "user-b").is_err());
+        assert_eq!(MemoryBinding::for_row("m1", &HashMap::new(), "user-b").unwrap().user_id, "user-b");
+    }
+
+    #[test]
+    fn test_strict_open_refuses_unbound_packets() {
+        let key = MemoryVault::generate_key();
+        let metadata = HashMap::new();
+        let row = MemoryBinding::new("memory-1", "user-a", &metadata);
+        let bound = MemoryVault::lock_bound("bound", &key, &row).unwrap();
+        let unbound = MemoryVault::lock("pasted", &key).unwrap();
+        let mut legacy = vec![7u8; NONCE_SIZE];
+        legacy.extend_from_slice(&cipher_suite(CipherSuiteId::Aes256Gcm)
+            .seal(key.as_bytes(), &legacy.clone(), b"pasted", &[])
+            .unwrap());
+        let legacy = BASE64.encode(legacy);
+
+        assert_eq!(MemoryVault::open_bound_strict(&bound, &key, &row).unwrap(), "bound");
+        assert_eq!(MemoryVault::open_bound(&legacy, &key, &row).unwrap(), "pasted");
+        assert!(MemoryVault::open_bound_strict(&legacy, &key, &row).is_err());
+        assert!(MemoryVault::open_bound_strict(&unbound, &key, &row).is_err());
+    }
+
+    #[test]
+    fn test_legacy_nonce_with_magic_still_opens() {
+        let key = MemoryVault::generate_key();
+        let mut legacy = [ENVELOPE_MAGIC.as_slice(), &[0xff; NONCE_SIZE - 4]].concat();
+        legacy.extend_from_slice(&cipher_suite(CipherSuiteId::Aes256Gcm)
+            .seal(key.as_bytes(), &legacy.clone(), b"old note", &[])
+            .unwrap());
+        let legacy = BASE64.encode(legacy);
+
+        // Not an envelope, so only the explicit legacy attempt opens it
+        assert!(MemoryVault::inspect(&legacy).is_err());
+        assert_eq!(MemoryVault::open(&legacy, &key).unwrap(), "old note");
+        assert!(MemoryVault::open_with_aad(&legacy, &key, b"row-1").is_err());
+    }
+
+    #[test]
+    fn test_metadata_hash_is_order_independent() {
+        let mut a = HashMap::new();
+        a.insert("x".to_string(), "1".to_string());
+        a.insert("y".to_string(), "2".to_string());
+        let mut b = HashMap::new();
+        b.insert("y".to_string(), "2".to_string());
+        b.insert("x".to_string(), "1".to_string());
+
+        assert_eq!(metadata_hash(&a), metadata_hash(&b));
+    }
+
+    #[test]
+    fn test_rekey_moves_memory_to_new_key() {
+        let old_key = MemoryVault::generate_key();
+        let new_key = MemoryVault::generate_key();
+        let metadata = HashMap::new();
+        let mut rotated_metadata = HashMap::new();
+        rotated_metadata.insert(KEY_ID_METADATA.to_string(), MemoryVault::key_id(&new_key));
+        let old_row = MemoryBinding::new("m1", "u1", &metadata);
+        let new_row = MemoryBinding::new("m1", "u1", &rotated_metadata);
+
+        // Bound and unbound packets both rekey to a bound packet under the new key
+        for packet in [
+            MemoryVault::lock_bound("note", &old_key, &old_row).unwrap(),
+            MemoryVault::lock("note", &old_key).unwrap(),
+        ] {
+            let rekeyed = MemoryVault::rekey(&packet, &old_key, &new_key, &old_row, &new_row).unwrap();
+
+            assert_eq!(MemoryVault::open_bound(&rekeyed, &new_key, &new_row).unwrap(), "note");
+            assert!(MemoryVault::open_bound(&rekeyed, &old_key, &new_row).is_err());
+            assert_eq!(
+                MemoryVault::packet_key_id(&rekeyed).unwrap(),
+                Some(MemoryVault::key_id(&new_key))
+            );
+        }
+    }
+
+    #[test]
+    fn test_every_suite_roundtrips_and_records_suite() {
+        let key = MemoryVault::generate_key();
+        let metadata = HashMap::new();
+        let row = MemoryBinding::new("m1", "u1", &metadata);
+
+        for suite in [
+            CipherSuiteId::Aes256Gcm,
+            CipherSuiteId::ChaCha20Poly1305,
+            CipherSuiteId::XChaCha20Poly1305,
+        ] {
+            let packet = MemoryVault::lock_bound_with_suite(suite, "suite note", &key, &row).unwrap();
+
+            match MemoryVault::inspect(&packet).unwrap() {
+                Packet::Envelope(envelope) => {
+                    assert_eq!(envelope.suite, suite);
+                    assert_eq!(envelope.nonce.len(), suite.nonce_size());
+                }
+                other => panic!("expected envelope, got {:?}", other),
+            }
+            assert_eq!(MemoryVault::open_bound(&packet, &key, &row).unwrap(), "suite note");
+        }
+    }
+
+    #[test]
+    fn test_suite_byte_swap_fails() {
+        let key = MemoryVault::generate_key();
+        let packet = MemoryVault::lock_with_key_id("hello", &key, "a").unwrap();
+        let mut bytes = BASE64.decode(packet).unwrap();
+        // AES-256-GCM -> ChaCha20-Poly1305 (same nonce size, so the envelope still parses)
+        bytes[5] = CipherSuiteId::ChaCha20Poly1305 as u8;
+
+        assert!(MemoryVault::open(&BASE64.encode(bytes), &key).is_err());
+    }
+
+    #[test]
+    fn test_rekey_can_change_suite() {
+        let key = MemoryVault::generate_key();
+        let metadata = HashMap::new();
+        let row = MemoryBinding::new("m1", "u1", &metadata);
+        let packet = MemoryVault::lock_bound("note", &key, &row).unwrap();
+
+        let rekeyed = MemoryVault::rekey_with_suite(
+            CipherSuiteId::XChaCha20Poly1305, &packet, &key, &key, &row, &row,
+        ).unwrap();
+
+        match MemoryVault::inspect(&rekeyed).unwrap() {
+            Packet::Envelope(envelope) => assert_eq!(envelope.suite, CipherSuiteId::XChaCha20Poly1305),
+            other => panic!("expected envelope, got {:?}", other),
+        }
+        assert_eq!(MemoryVault::open_bound(&rekeyed, &key, &row).unwrap(), "note");
+    }
+}
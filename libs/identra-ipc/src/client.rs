@@ -0,0 +1,374 @@
+use crate::error::{IpcError, Result};
+use crate::framing::{read_frame, write_frame};
+use crate::handshake::client_handshake;
+use crate::protocol::{
+    AuditEntry, KeyAlgorithm, KeyPolicy, RestoreMode, RestoreSummary, ServerHello, StoredKey, VaultRequest,
+    VaultResponse, VaultStatus,
+};
+use crate::socket::{default_socket_name, socket_name};
+use crate::token::load_token;
+use identra_crypto::{CipherSuiteId, SecretBytes};
+use interprocess::local_socket::tokio::{prelude::*, Stream};
+use std::collections::HashMap;
+
+/// Connection to the vault daemon
+///
+/// Requests are answered in order on one connection; open one client per
+/// task rather than sharing it.
+pub struct VaultClient {
+    stream: Stream,
+    server: ServerHello,
+}
+
+impl VaultClient {
+    /// Connect to the daemon's default socket as `client`, using the install token
+    pub async fn connect(client: &str) -> Result<Self> {
+        let token = load_token()?;
+        Self::connect_to(&default_socket_name(), client, token.as_bytes()).await
+    }
+
+    /// Connect to the daemon on a specific socket name with an explicit token
+    pub async fn connect_to(socket: &str, client: &str, token: &[u8]) -> Result<Self> {
+        let name = socket_name(socket).map_err(IpcError::Connect)?;
+        let mut stream = Stream::connect(name).await.map_err(IpcError::Connect)?;
+        let server = client_handshake(&mut stream, client, token).await?;
+        Ok(Self { stream, server })
+    }
+
+    /// The daemon's handshake reply
+    pub fn server(&self) -> &ServerHello {
+        &self.server
+    }
+
+    /// Send one request and wait for its response
+    ///
+    /// Error responses are returned as [`IpcError::Remote`].
+    pub async fn request(&mut self, request: &VaultRequest) -> Result<VaultResponse> {
+        write_frame(&mut self.stream, request).await?;
+        match read_frame(&mut self.stream).await?.ok_or(IpcError::Closed)? {
+            VaultResponse::Error { code, message } => Err(IpcError::Remote { code, message }),
+            response => Ok(response),
+        }
+    }
+
+    pub async fn ping(&mut self) -> Result<()> {
+        match self.request(&VaultRequest::Ping).await? {
+            VaultResponse::Pong => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn store_key(
+        &mut self,
+        key_id: impl Into<String>,
+        key_data: &[u8],
+        metadata: HashMap<String, String>,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        self.store_key_with_policy(key_id, key_data, metadata, expires_at, KeyPolicy::default())
+            .await
+    }
+
+    /// Store a key the daemon will only release as `policy` allows
+    pub async fn store_key_with_policy(
+        &mut self,
+        key_id: impl Into<String>,
+        key_data: &[u8],
+        metadata: HashMap<String, String>,
+        expires_at: Option<i64>,
+        policy: KeyPolicy,
+    ) -> Result<()> {
+        let request = VaultRequest::StoreKey {
+            key_id: key_id.into(),
+            key_data: SecretBytes::from_slice(key_data),
+            metadata,
+            expires_at,
+            policy,
+        };
+        match self.request(&request).await? {
+            VaultResponse::Success => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Create a key inside the daemon, returning a keypair's base64 public key
+    pub async fn generate_key(
+        &mut self,
+        key_id: impl Into<String>,
+        algorithm: KeyAlgorithm,
+        exportable: bool,
+    ) -> Result<Option<String>> {
+        let request = VaultRequest::GenerateKey {
+            key_id: key_id.into(),
+            algorithm,
+            exportable,
+        };
+        match self.request(&request).await? {
+            VaultResponse::GeneratedKey { public_key } => Ok(public_key),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn retrieve_key(&mut self, key_id: impl Into<String>) -> Result<StoredKey> {
+        match self.request(&VaultRequest::RetrieveKey { key_id: key_id.into() }).await? {
+            VaultResponse::KeyData(key) => Ok(key),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn delete_key(&mut self, key_id: impl Into<String>) -> Result<()> {
+        match self.request(&VaultRequest::DeleteKey { key_id: key_id.into() }).await? {
+            VaultResponse::Success => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn key_exists(&mut self, key_id: impl Into<String>) -> Result<bool> {
+        match self.request(&VaultRequest::KeyExists { key_id: key_id.into() }).await? {
+            VaultResponse::Exists(exists) => Ok(exists),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Every key id, following pages until the daemon runs out
+    pub async fn list_keys(&mut self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut page_token = None;
+        loop {
+            let (page, next) = self.list_keys_page(None, page_token).await?;
+            keys.extend(page);
+            match next {
+                Some(token) => page_token = Some(token),
+                None => return Ok(keys),
+            }
+        }
+    }
+
+    /// One page of key ids and the token for the next page, if any
+    pub async fn list_keys_page(
+        &mut self,
+        page_size: Option<u32>,
+        page_token: Option<String>,
+    ) -> Result<(Vec<String>, Option<String>)> {
+        self.list_keys_page_with_prefix(None, page_size, page_token).await
+    }
+
+    /// Like [`Self::list_keys_page`], only returning key ids that start with `prefix`
+    pub async fn list_keys_page_with_prefix(
+        &mut self,
+        prefix: Option<String>,
+        page_size: Option<u32>,
+        page_token: Option<String>,
+    ) -> Result<(Vec<String>, Option<String>)> {
+        match self.request(&VaultRequest::ListKeys { page_size, page_token, prefix }).await? {
+            VaultResponse::KeyList { key_ids, next_page_token } => Ok((key_ids, next_page_token)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// The user's public identity key (base64) and its fingerprint
+    pub async fn get_identity(&mut self) -> Result<(String, String)> {
+        match self.request(&VaultRequest::GetIdentity).await? {
+            VaultResponse::Identity { public_key, fingerprint } => Ok((public_key, fingerprint)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn open_sealed(&mut self, sealed: Vec<u8>, aad: Vec<u8>) -> Result<SecretBytes> {
+        match self.request(&VaultRequest::OpenSealed { sealed, aad }).await? {
+            VaultResponse::Opened(plaintext) => Ok(plaintext),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Encrypt with a key that never leaves the daemon
+    pub async fn encrypt(&mut self, key_id: impl Into<String>, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        self.encrypt_with_suite(key_id, CipherSuiteId::default(), plaintext, aad).await
+    }
+
+    /// Like [`Self::encrypt`], sealing with `suite`
+    pub async fn encrypt_with_suite(
+        &mut self,
+        key_id: impl Into<String>,
+        suite: CipherSuiteId,
+        plaintext: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>> {
+        let request = VaultRequest::Encrypt {
+            key_id: key_id.into(),
+            plaintext: SecretBytes::from_slice(plaintext),
+            aad: aad.to_vec(),
+            suite,
+        };
+        match self.request(&request).await? {
+            VaultResponse::Ciphertext(ciphertext) => Ok(ciphertext),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn decrypt(&mut self, key_id: impl Into<String>, ciphertext: Vec<u8>, aad: &[u8]) -> Result<SecretBytes> {
+        let request = VaultRequest::Decrypt {
+            key_id: key_id.into(),
+            ciphertext,
+            aad: aad.to_vec(),
+        };
+        match self.request(&request).await? {
+            VaultResponse::Plaintext(plaintext) => Ok(plaintext),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// HMAC-SHA256 tag of `message`, or an Ed25519 signature with an Ed25519 key
+    pub async fn sign(&mut self, key_id: impl Into<String>, message: &[u8]) -> Result<Vec<u8>> {
+        let request = VaultRequest::Sign {
+            key_id: key_id.into(),
+            message: message.to_vec(),
+        };
+        match self.request(&request).await? {
+            VaultResponse::Signature(signature) => Ok(signature),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn verify(&mut self, key_id: impl Into<String>, message: &[u8], signature: &[u8]) -> Result<bool> {
+        let request = VaultRequest::Verify {
+            key_id: key_id.into(),
+            message: message.to_vec(),
+            signature: signature.to_vec(),
+        };
+        match self.request(&request).await? {
+            VaultResponse::Verified(valid) => Ok(valid),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Stored key `key_id`, encrypted under stored key `wrapping_key_id`
+    pub async fn wrap_key(&mut self, wrapping_key_id: impl Into<String>, key_id: impl Into<String>) -> Result<Vec<u8>> {
+        let request = VaultRequest::WrapKey {
+            wrapping_key_id: wrapping_key_id.into(),
+            key_id: key_id.into(),
+        };
+        match self.request(&request).await? {
+            VaultResponse::Ciphertext(wrapped) => Ok(wrapped),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Unwrap `wrapped` inside the daemon and store it as `key_id`
+    pub async fn unwrap_key(
+        &mut self,
+        wrapping_key_id: impl Into<String>,
+        wrapped: Vec<u8>,
+        key_id: impl Into<String>,
+        metadata: HashMap<String, String>,
+        expires_at: Option<i64>,
+        policy: KeyPolicy,
+    ) -> Result<()> {
+        let request = VaultRequest::UnwrapKey {
+            wrapping_key_id: wrapping_key_id.into(),
+            wrapped,
+            key_id: key_id.into(),
+            metadata,
+            expires_at,
+            policy,
+        };
+        match self.request(&request).await? {
+            VaultResponse::Success => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Audit entries in a time range, and whether the log's hash chain is intact
+    pub async fn get_audit_log(
+        &mut self,
+        since: Option<i64>,
+        until: Option<i64>,
+        limit: Option<u32>,
+    ) -> Result<(Vec<AuditEntry>, bool)> {
+        match self.request(&VaultRequest::GetAuditLog { since, until, limit }).await? {
+            VaultResponse::AuditLog { entries, verified } => Ok((entries, verified)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Write an encrypted backup of every key to a new file at the absolute `path` on the daemon's machine
+    ///
+    /// Returns the number of keys written.
+    pub async fn export_backup(&mut self, path: impl Into<String>, passphrase: &[u8]) -> Result<u32> {
+        let request = VaultRequest::ExportBackup {
+            path: path.into(),
+            passphrase: SecretBytes::from_slice(passphrase),
+        };
+        match self.request(&request).await? {
+            VaultResponse::BackupExported { key_count } => Ok(key_count),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Restore keys from a backup at `path`; with `dry_run` only report the changes
+    pub async fn import_backup(
+        &mut self,
+        path: impl Into<String>,
+        passphrase: &[u8],
+        mode: RestoreMode,
+        dry_run: bool,
+    ) -> Result<RestoreSummary> {
+        let request = VaultRequest::ImportBackup {
+            path: path.into(),
+            passphrase: SecretBytes::from_slice(passphrase),
+            mode,
+            dry_run,
+        };
+        match self.request(&request).await? {
+            VaultResponse::BackupImported(summary) => Ok(summary),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Set the vault's passphrase; fails if it already has one
+    pub async fn set_passphrase(&mut self, passphrase: &[u8]) -> Result<()> {
+        let request = VaultRequest::SetPassphrase {
+            passphrase: SecretBytes::from_slice(passphrase),
+        };
+        match self.request(&request).await? {
+            VaultResponse::Success => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn unlock(&mut self, passphrase: &[u8]) -> Result<()> {
+        let request = VaultRequest::Unlock {
+            passphrase: SecretBytes::from_slice(passphrase),
+        };
+        match self.request(&request).await? {
+            VaultResponse::Success => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn lock(&mut self) -> Result<()> {
+        match self.request(&VaultRequest::Lock).await? {
+            VaultResponse::Success => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub async fn status(&mut self) -> Result<VaultStatus> {
+        match self.request(&VaultRequest::Status).await? {
+            VaultResponse::Status(status) => Ok(status),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Ask the daemon to shut down; this connection is closed afterwards
+    pub async fn shutdown(mut self) -> Result<()> {
+        match self.request(&VaultRequest::Shutdown).await? {
+            VaultResponse::ShuttingDown => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+fn unexpected(response: VaultResponse) -> IpcError {
+    IpcError::UnexpectedResponse(format!("{:?}", response))
+}
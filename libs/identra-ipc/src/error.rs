@@ -0,0 +1,44 @@
+use crate::protocol::ErrorCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum IpcError {
+    #[error("Failed to connect to vault: {0}")]
+    Connect(std::io::Error),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Frame of {0} bytes exceeds the protocol limit")]
+    FrameTooLarge(usize),
+
+    #[error("Malformed message: {0}")]
+    Decode(#[from] serde_json::Error),
+
+    #[error("Connection closed by peer")]
+    Closed,
+
+    #[error("Protocol version mismatch: local {local}, remote {remote}")]
+    VersionMismatch { local: u16, remote: u16 },
+
+    #[error("IPC token error: {0}")]
+    Token(String),
+
+    #[error("Vault error ({code}): {message}")]
+    Remote { code: ErrorCode, message: String },
+
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
+}
+
+impl IpcError {
+    /// Error code reported by the daemon, if this came from the daemon
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Remote { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, IpcError>;
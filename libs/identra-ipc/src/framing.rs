@@ -0,0 +1,103 @@
+use crate::error::{IpcError, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use zeroize::Zeroizing;
+
+/// Largest frame either side will send or accept (16 MiB)
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Write `message` as one frame: a 4-byte big-endian length, then JSON
+pub async fn write_frame<W, T>(writer: &mut W, message: &T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let payload = Zeroizing::new(serde_json::to_vec(message)?);
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(IpcError::FrameTooLarge(payload.len()));
+    }
+
+    writer.write_all(&(payload.len() as u32).to_be_bytes()).await?;
+    writer.write_all(&payload).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read one frame, or `None` if the peer closed the connection between frames
+///
+/// A frame that fails to decode is fully consumed, so the stream stays usable
+/// after an [`IpcError::Decode`]. An oversized frame is not, and the
+/// connection should be dropped.
+pub async fn read_frame<R, T>(reader: &mut R) -> Result<Option<T>>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(IpcError::FrameTooLarge(len));
+    }
+
+    let mut payload = Zeroizing::new(vec![0u8; len]);
+    reader.read_exact(&mut payload).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => IpcError::Closed,
+        _ => e.into(),
+    })?;
+    Ok(Some(serde_json::from_slice(&payload)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::VaultRequest;
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        write_frame(&mut a, &VaultRequest::KeyExists { key_id: "k1".into() }).await.unwrap();
+        write_frame(&mut a, &VaultRequest::Ping).await.unwrap();
+        drop(a);
+
+        let first: Option<VaultRequest> = read_frame(&mut b).await.unwrap();
+        assert!(matches!(first, Some(VaultRequest::KeyExists { key_id }) if key_id == "k1"));
+        assert!(matches!(read_frame(&mut b).await.unwrap(), Some(VaultRequest::Ping)));
+        assert!(read_frame::<_, VaultRequest>(&mut b).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_and_truncated_frames() {
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        a.write_all(&(MAX_FRAME_LEN as u32 + 1).to_be_bytes()).await.unwrap();
+        assert!(matches!(
+            read_frame::<_, VaultRequest>(&mut b).await,
+            Err(IpcError::FrameTooLarge(_))
+        ));
+
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        a.write_all(&10u32.to_be_bytes()).await.unwrap();
+        a.write_all(b"{\"Pi").await.unwrap();
+        drop(a);
+        assert!(matches!(read_frame::<_, VaultRequest>(&mut b).await, Err(IpcError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn test_bad_frame_leaves_stream_in_sync() {
+        let (mut a, mut b) = tokio::io::duplex(1024);
+        a.write_all(&5u32.to_be_bytes()).await.unwrap();
+        a.write_all(b"nope!").await.unwrap();
+        write_frame(&mut a, &VaultRequest::Ping).await.unwrap();
+
+        assert!(matches!(
+            read_frame::<_, VaultRequest>(&mut b).await,
+            Err(IpcError::Decode(_))
+        ));
+        assert!(matches!(read_frame(&mut b).await.unwrap(), Some(VaultRequest::Ping)));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::error::{IpcError, Result};
+use crate::framing::{read_frame, write_frame};
+use crate::protocol::{
+    ClientAuth, ClientHello, ErrorCode, ServerHello, VaultResponse, PROTOCOL_VERSION,
+};
+use crate::token::{generate_token, handshake_proof, verify_proof};
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// The daemon answers a hello with either a `ServerHello` or an error response
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HelloReply {
+    Accepted(ServerHello),
+    Rejected(VaultResponse),
+}
+
+/// Client side: agree on the version, then prove we hold the install token
+pub async fn client_handshake<S>(stream: &mut S, client: &str, token: &[u8]) -> Result<ServerHello>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    write_frame(stream, &ClientHello::new(client)).await?;
+
+    let hello = match read_frame(stream).await?.ok_or(IpcError::Closed)? {
+        HelloReply::Accepted(hello) if hello.protocol_version == PROTOCOL_VERSION => hello,
+        HelloReply::Accepted(hello) => {
+            return Err(IpcError::VersionMismatch {
+                local: PROTOCOL_VERSION,
+                remote: hello.protocol_version,
+            })
+        }
+        HelloReply::Rejected(response) => return Err(rejected(response)),
+    };
+
+    let auth = ClientAuth {
+        proof: handshake_proof(token, &hello.challenge, client),
+    };
+    write_frame(stream, &auth).await?;
+
+    match read_frame(stream).await?.ok_or(IpcError::Closed)? {
+        VaultResponse::Success => Ok(hello),
+        response => Err(rejected(response)),
+    }
+}
+
+/// Daemon side: check the client's version and token proof
+///
+/// On failure the client is told why before the error is returned; the
+/// caller should then drop the connection.
+pub async fn accept_handshake<S>(stream: &mut S, server: &str, token: &[u8]) -> Result<ClientHello>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let hello: ClientHello = read_frame(stream).await?.ok_or(IpcError::Closed)?;
+
+    if hello.protocol_version != PROTOCOL_VERSION {
+        let reply = VaultResponse::error(
+            ErrorCode::UnsupportedVersion,
+            format!(
+                "Daemon speaks protocol v{}, client sent v{}",
+                PROTOCOL_VERSION, hello.protocol_version
+            ),
+        );
+        write_frame(stream, &reply).await?;
+        return Err(IpcError::VersionMismatch {
+            local: PROTOCOL_VERSION,
+            remote: hello.protocol_version,
+        });
+    }
+
+    let challenge = generate_token();
+    let reply = ServerHello {
+        protocol_version: PROTOCOL_VERSION,
+        server: server.to_string(),
+        challenge: challenge.as_bytes().to_vec(),
+    };
+    write_frame(stream, &reply).await?;
+
+    let auth: ClientAuth = read_frame(stream).await?.ok_or(IpcError::Closed)?;
+    if !verify_proof(token, challenge.as_bytes(), &hello.client, &auth.proof) {
+        let reply = VaultResponse::error(ErrorCode::Unauthorized, "Invalid IPC token");
+        write_frame(stream, &reply).await?;
+        return Err(IpcError::Token(format!("Client '{}' failed authentication", hello.client)));
+    }
+
+    write_frame(stream, &VaultResponse::Success).await?;
+    Ok(hello)
+}
+
+fn rejected(response: VaultResponse) -> IpcError {
+    match response {
+        VaultResponse::Error { code, message } => IpcError::Remote { code, message },
+        other => IpcError::UnexpectedResponse(format!("{:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &[u8] = &[9u8; 32];
+
+    #[tokio::test]
+    async fn test_handshake_accepts_matching_version_and_token() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let daemon = tokio::spawn(async move { accept_handshake(&mut server, "test-daemon", TOKEN).await });
+
+        let hello = client_handshake(&mut client, "test-client", TOKEN).await.unwrap();
+        assert_eq!(hello.server, "test-daemon");
+        assert_eq!(daemon.await.unwrap().unwrap().client, "test-client");
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_wrong_token() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let daemon = tokio::spawn(async move { accept_handshake(&mut server, "test-daemon", TOKEN).await });
+
+        let err = client_handshake(&mut client, "test-client", &[1u8; 32]).await.unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::Unauthorized));
+        assert!(matches!(daemon.await.unwrap(), Err(IpcError::Token(_))));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_other_version() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let daemon = tokio::spawn(async move { accept_handshake(&mut server, "test-daemon", TOKEN).await });
+
+        let hello = ClientHello {
+            protocol_version: PROTOCOL_VERSION + 1,
+            client: "future-client".into(),
+        };
+        write_frame(&mut client, &hello).await.unwrap();
+        let reply: VaultResponse = read_frame(&mut client).await.unwrap().unwrap();
+
+        assert!(matches!(
+            reply,
+            VaultResponse::Error { code: ErrorCode::UnsupportedVersion, .. }
+        ));
+        assert!(matches!(
+            daemon.await.unwrap(),
+            Err(IpcError::VersionMismatch { remote, .. }) if remote == PROTOCOL_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,38 @@
+//! Wire protocol between the vault daemon and its clients
+//!
+//! Every message is a length-prefixed JSON frame. A connection opens with a
+//! [`ClientHello`] / [`ServerHello`] exchange that pins the protocol version,
+//! followed by a [`ClientAuth`] proof of the per-install token, then carries
+//! one [`VaultResponse`] per [`VaultRequest`], in order.
+
+// Request/response messages, handshake and error codes
+pub mod protocol;
+
+// Length-prefixed frame I/O
+pub mod framing;
+
+// Version handshake opening every connection
+pub mod handshake;
+
+// Per-install token and handshake proofs
+pub mod token;
+
+// Socket naming shared by the daemon and clients
+pub mod socket;
+
+// Async client used by the gateway and desktop app
+pub mod client;
+
+// Error types
+mod error;
+
+pub use client::VaultClient;
+pub use error::{IpcError, Result};
+pub use handshake::{accept_handshake, client_handshake};
+pub use framing::{read_frame, write_frame, MAX_FRAME_LEN};
+pub use protocol::{
+    AuditEntry, ClientAuth, ClientHello, ErrorCode, KeyAlgorithm, KeyPolicy, RestoreMode, RestoreSummary,
+    ServerHello, StoredKey, VaultRequest, VaultResponse, VaultStatus, ALGORITHM_METADATA, DEFAULT_PAGE_SIZE,
+    MAX_PAGE_SIZE, PROTOCOL_VERSION,
+};
+pub use socket::{default_socket_name, socket_name, DEFAULT_SOCKET_NAME, SOCKET_ENV};
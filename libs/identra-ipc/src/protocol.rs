@@ -0,0 +1,471 @@
+use identra_crypto::{CipherSuiteId, SecretBytes};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Wire protocol version; bump on any incompatible message change
+pub const PROTOCOL_VERSION: u16 = 13;
+
+/// `ListKeys` page size when the client doesn't ask for one
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+
+/// Largest `ListKeys` page the daemon returns
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+/// First frame sent by a client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientHello {
+    pub protocol_version: u16,
+    /// Free-form client name for daemon logs (e.g. "tunnel-gateway")
+    pub client: String,
+}
+
+impl ClientHello {
+    pub fn new(client: impl Into<String>) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            client: client.into(),
+        }
+    }
+}
+
+/// Daemon's reply to a [`ClientHello`]
+///
+/// On a version mismatch or a rejected peer the daemon sends
+/// [`VaultResponse::Error`] instead and closes the connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerHello {
+    pub protocol_version: u16,
+    /// Daemon build version
+    pub server: String,
+    /// Random bytes the client must answer with a [`ClientAuth`] proof
+    pub challenge: Vec<u8>,
+}
+
+/// Client's answer to the [`ServerHello`] challenge
+///
+/// The daemon replies [`VaultResponse::Success`], or an
+/// [`ErrorCode::Unauthorized`] error and closes the connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientAuth {
+    /// See [`crate::token::handshake_proof`]
+    pub proof: Vec<u8>,
+}
+
+/// A key and its metadata as stored by the daemon
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredKey {
+    pub key_data: SecretBytes,
+    pub metadata: HashMap<String, String>,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub policy: KeyPolicy,
+    /// Reads and uses so far, counted while `policy.max_uses` is set
+    pub uses: u64,
+}
+
+/// Who may do what with a stored key, enforced by the daemon
+///
+/// Client lists hold what the daemon learns about the peer from the
+/// kernel: `exe:<path>` for the connecting executable, `uid:<n>` for its
+/// user, or `uid:<n>:exe:<path>` for both. The name a client gives in its
+/// hello is self-declared and is refused here. The default policy allows
+/// everything, as before policies existed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyPolicy {
+    /// Clients that may retrieve the key's bytes; `None` allows any
+    pub readers: Option<Vec<String>>,
+    /// Clients that may encrypt, decrypt, sign, verify or wrap with the key; `None` allows any
+    pub users: Option<Vec<String>>,
+    /// Clients that may delete or overwrite the key; `None` allows any
+    pub deleters: Option<Vec<String>>,
+    /// Whether the raw bytes may leave the daemon (`RetrieveKey`, `WrapKey`, backups)
+    pub exportable: bool,
+    /// Total reads and uses allowed; `None` is unlimited
+    pub max_uses: Option<u64>,
+    /// Only usable if the vault was unlocked with its passphrase this recently
+    pub reauth_secs: Option<u64>,
+}
+
+impl Default for KeyPolicy {
+    fn default() -> Self {
+        Self {
+            readers: None,
+            users: None,
+            deleters: None,
+            exportable: true,
+            max_uses: None,
+            reauth_secs: None,
+        }
+    }
+}
+
+/// Metadata entry naming a key's [`KeyAlgorithm`]; keys without one are symmetric
+pub const ALGORITHM_METADATA: &str = "algorithm";
+
+/// What kind of key `GenerateKey` creates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAlgorithm {
+    /// 256-bit key for `Encrypt`, `Decrypt`, `WrapKey` and HMAC `Sign`
+    #[default]
+    Symmetric,
+    /// Ed25519 keypair; `Sign` makes signatures others can check with the public key
+    Ed25519,
+    /// X25519 key-agreement keypair
+    X25519,
+}
+
+impl KeyAlgorithm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Symmetric => "symmetric",
+            Self::Ed25519 => "ed25519",
+            Self::X25519 => "x25519",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Symmetric, Self::Ed25519, Self::X25519]
+            .into_iter()
+            .find(|algorithm| algorithm.as_str() == name)
+    }
+
+    /// The algorithm recorded in a key's metadata
+    pub fn of(metadata: &HashMap<String, String>) -> Option<Self> {
+        match metadata.get(ALGORITHM_METADATA) {
+            Some(name) => Self::from_name(name),
+            None => Some(Self::Symmetric),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum VaultRequest {
+    StoreKey {
+        key_id: String,
+        key_data: SecretBytes,
+        metadata: HashMap<String, String>,
+        expires_at: Option<i64>, // Unix timestamp
+        #[serde(default)]
+        policy: KeyPolicy,
+    },
+    /// Create a key inside the daemon; only a keypair's public half is returned
+    GenerateKey {
+        key_id: String,
+        algorithm: KeyAlgorithm,
+        /// Whether `RetrieveKey`, `WrapKey` and backups may ever hand out the private bytes
+        exportable: bool,
+    },
+    RetrieveKey { key_id: String },
+    DeleteKey { key_id: String },
+    KeyExists { key_id: String },
+    /// One page of key ids in lexicographic order
+    ListKeys {
+        /// Daemon default when unset; capped at [`MAX_PAGE_SIZE`]
+        page_size: Option<u32>,
+        /// `next_page_token` from the previous page
+        page_token: Option<String>,
+        /// Only keys whose id starts with this (e.g. one gateway user's namespace)
+        #[serde(default)]
+        prefix: Option<String>,
+    },
+    /// Fetch (creating on first use) the user's public identity key
+    GetIdentity,
+    /// Open a sealed box addressed to the user's identity key
+    OpenSealed { sealed: Vec<u8>, aad: Vec<u8> },
+    /// Encrypt with stored key `key_id` (32 bytes); answered with a binary envelope
+    Encrypt {
+        key_id: String,
+        plaintext: SecretBytes,
+        aad: Vec<u8>,
+        /// Recorded in the envelope, so `Decrypt` needs no suite
+        #[serde(default)]
+        suite: CipherSuiteId,
+    },
+    /// Decrypt an envelope from `Encrypt`
+    Decrypt {
+        key_id: String,
+        ciphertext: Vec<u8>,
+        aad: Vec<u8>,
+    },
+    /// HMAC-SHA256 of `message` under stored key `key_id`, or an Ed25519 signature for Ed25519 keys
+    Sign { key_id: String, message: Vec<u8> },
+    /// Check a tag from `Sign`
+    Verify {
+        key_id: String,
+        message: Vec<u8>,
+        signature: Vec<u8>,
+    },
+    /// Encrypt stored key `key_id` under stored key `wrapping_key_id`, e.g. for export
+    WrapKey { wrapping_key_id: String, key_id: String },
+    /// Decrypt a wrapped key and store it as `key_id`, without returning it
+    UnwrapKey {
+        wrapping_key_id: String,
+        wrapped: Vec<u8>,
+        key_id: String,
+        metadata: HashMap<String, String>,
+        expires_at: Option<i64>,
+        #[serde(default)]
+        policy: KeyPolicy,
+    },
+    /// Audit entries with `since <= timestamp <= until`, at most the newest `limit`
+    GetAuditLog {
+        since: Option<i64>,
+        until: Option<i64>,
+        limit: Option<u32>,
+    },
+    /// Write every key and its metadata to a passphrase-encrypted archive at `path`
+    ///
+    /// `path` must be absolute, must not exist yet, and (where the daemon
+    /// sees the caller's uid) must be in a directory that uid owns.
+    ExportBackup { path: String, passphrase: SecretBytes },
+    /// Restore keys from an archive written by `ExportBackup`, under the same path rules
+    ImportBackup {
+        path: String,
+        passphrase: SecretBytes,
+        mode: RestoreMode,
+        /// Report what would change without touching the keystore
+        dry_run: bool,
+    },
+    /// Set the vault's passphrase; refused once one is set, and to clients
+    /// outside the daemon's `lock.setup_users` when that is configured
+    SetPassphrase { passphrase: SecretBytes },
+    /// Unlock the vault with its passphrase
+    ///
+    /// After a few wrong passphrases further attempts are refused with
+    /// `RateLimited` for a period that doubles with each failure.
+    Unlock { passphrase: SecretBytes },
+    /// Lock the vault until the next `Unlock`
+    Lock,
+    /// Lock state and auto-lock settings
+    Status,
+    Ping,
+    /// Stop the daemon: it answers `ShuttingDown`, then drains open connections and exits
+    Shutdown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum VaultResponse {
+    Success,
+    KeyData(StoredKey),
+    KeyList {
+        key_ids: Vec<String>,
+        /// Set when more keys follow
+        next_page_token: Option<String>,
+    },
+    Exists(bool),
+    /// From `GenerateKey`: the base64 public key of a keypair, `None` for symmetric keys
+    GeneratedKey { public_key: Option<String> },
+    Identity {
+        public_key: String,
+        fingerprint: String,
+    },
+    Opened(SecretBytes),
+    /// Envelope from `Encrypt` or `WrapKey`
+    Ciphertext(Vec<u8>),
+    Plaintext(SecretBytes),
+    Signature(Vec<u8>),
+    Verified(bool),
+    Status(VaultStatus),
+    /// Number of keys written by `ExportBackup`
+    BackupExported { key_count: u32 },
+    BackupImported(RestoreSummary),
+    AuditLog {
+        /// Oldest first
+        entries: Vec<AuditEntry>,
+        /// Whether the whole log's hash chain checked out
+        verified: bool,
+    },
+    Pong,
+    ShuttingDown,
+    Error { code: ErrorCode, message: String },
+}
+
+/// One record in the daemon's hash-chained audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, from 0 with no gaps
+    pub seq: u64,
+    /// Unix seconds
+    pub timestamp: i64,
+    pub pid: Option<u32>,
+    pub uid: Option<u32>,
+    /// Name the client gave in its hello (empty before the handshake)
+    pub client: String,
+    /// Request name, e.g. `retrieve_key`
+    pub operation: String,
+    pub key_id: Option<String>,
+    /// `ok` or an [`ErrorCode`] wire name
+    pub outcome: String,
+    /// Hex `hash` of the previous entry
+    pub prev_hash: String,
+    /// Hex HMAC-SHA256 over this entry and `prev_hash`
+    pub hash: String,
+}
+
+/// How `ImportBackup` treats keys already in the vault
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreMode {
+    /// Add keys missing from the vault; keys it already has are left alone
+    #[default]
+    Merge,
+    /// Make the vault match the archive: overwrite existing keys, delete the rest
+    Replace,
+}
+
+/// Key ids affected by `ImportBackup`, each list sorted
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreSummary {
+    /// In the archive but not the vault
+    pub added: Vec<String>,
+    /// In both and overwritten (replace mode)
+    pub replaced: Vec<String>,
+    /// In both and kept as they were (merge mode)
+    pub skipped: Vec<String>,
+    /// In the vault but not the archive, and deleted (replace mode)
+    pub removed: Vec<String>,
+    /// Nothing was changed
+    pub dry_run: bool,
+}
+
+/// Answer to [`VaultRequest::Status`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultStatus {
+    pub locked: bool,
+    /// `false` until `SetPassphrase`; the vault can't lock before that
+    pub passphrase_set: bool,
+    /// Idle time after which the vault locks itself, if enabled
+    pub auto_lock_secs: Option<u64>,
+}
+
+impl VaultResponse {
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::Error {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Machine-readable failure reason carried by [`VaultResponse::Error`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Client and daemon speak different protocol versions
+    UnsupportedVersion,
+    /// The client failed the token challenge or gave the wrong passphrase
+    Unauthorized,
+    /// The connecting process is not on the daemon's allow-list
+    PermissionDenied,
+    /// The frame could not be decoded as a request
+    InvalidRequest,
+    /// No key with the requested id
+    NotFound,
+    /// The key has expired and is waiting to be swept by the daemon
+    Expired,
+    /// The OS keychain or storage backend failed
+    Storage,
+    /// An encryption or decryption step failed
+    Crypto,
+    /// The vault is locked; send `Unlock` first
+    Locked,
+    /// The key's `max_uses` have all been used
+    UsesExhausted,
+    /// The key's policy needs a more recent `Unlock`
+    ReauthRequired,
+    /// Too many failed unlocks; wait before trying again
+    RateLimited,
+    /// Anything else
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnsupportedVersion => "unsupported_version",
+            Self::Unauthorized => "unauthorized",
+            Self::PermissionDenied => "permission_denied",
+            Self::InvalidRequest => "invalid_request",
+            Self::NotFound => "not_found",
+            Self::Expired => "expired",
+            Self::Storage => "storage",
+            Self::Crypto => "crypto",
+            Self::Locked => "locked",
+            Self::UsesExhausted => "uses_exhausted",
+            Self::ReauthRequired => "reauth_required",
+            Self::RateLimited => "rate_limited",
+            Self::Internal => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code_wire_names() {
+        for code in [
+            ErrorCode::UnsupportedVersion,
+            ErrorCode::Unauthorized,
+            ErrorCode::PermissionDenied,
+            ErrorCode::InvalidRequest,
+            ErrorCode::NotFound,
+            ErrorCode::Expired,
+            ErrorCode::Storage,
+            ErrorCode::Crypto,
+            ErrorCode::Locked,
+            ErrorCode::UsesExhausted,
+            ErrorCode::ReauthRequired,
+            ErrorCode::RateLimited,
+            ErrorCode::Internal,
+        ] {
+            let json = serde_json::to_string(&code).unwrap();
+            assert_eq!(json, format!("\"{}\"", code.as_str()));
+            assert_eq!(serde_json::from_str::<ErrorCode>(&json).unwrap(), code);
+        }
+    }
+
+    #[test]
+    fn test_key_algorithm_from_metadata() {
+        for algorithm in [KeyAlgorithm::Symmetric, KeyAlgorithm::Ed25519, KeyAlgorithm::X25519] {
+            let metadata = HashMap::from([(ALGORITHM_METADATA.to_string(), algorithm.as_str().to_string())]);
+            assert_eq!(KeyAlgorithm::of(&metadata), Some(algorithm));
+        }
+        assert_eq!(KeyAlgorithm::of(&HashMap::new()), Some(KeyAlgorithm::Symmetric));
+        let unknown = HashMap::from([(ALGORITHM_METADATA.to_string(), "rsa".to_string())]);
+        assert_eq!(KeyAlgorithm::of(&unknown), None);
+    }
+
+    #[test]
+    fn test_request_encoding_is_stable() {
+        // Other clients depend on these shapes; changing them needs a version bump
+        let json = serde_json::to_string(&VaultRequest::KeyExists { key_id: "k".into() }).unwrap();
+        assert_eq!(json, r#"{"KeyExists":{"key_id":"k"}}"#);
+        assert_eq!(serde_json::to_string(&VaultRequest::Ping).unwrap(), r#""Ping""#);
+        let generate = VaultRequest::GenerateKey {
+            key_id: "k".into(),
+            algorithm: KeyAlgorithm::Ed25519,
+            exportable: false,
+        };
+        assert_eq!(
+            serde_json::to_string(&generate).unwrap(),
+            r#"{"GenerateKey":{"key_id":"k","algorithm":"ed25519","exportable":false}}"#
+        );
+
+        let error = VaultResponse::error(ErrorCode::NotFound, "missing");
+        assert_eq!(
+            serde_json::to_string(&error).unwrap(),
+            r#"{"Error":{"code":"not_found","message":"missing"}}"#
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use interprocess::local_socket::{
+    GenericFilePath, GenericNamespaced, Name, NameType, ToFsName, ToNsName,
+};
+use std::io;
+
+/// Socket the daemon listens on unless overridden
+pub const DEFAULT_SOCKET_NAME: &str = "identra-vault.sock";
+
+/// Environment variable overriding the socket name for the daemon and clients
+pub const SOCKET_ENV: &str = "IDENTRA_VAULT_SOCKET";
+
+/// Socket name from `IDENTRA_VAULT_SOCKET`, or the default
+pub fn default_socket_name() -> String {
+    std::env::var(SOCKET_ENV)
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| DEFAULT_SOCKET_NAME.to_string())
+}
+
+/// Resolve a socket name to a platform local-socket name
+///
+/// Names containing a path separator are used as filesystem paths. Bare
+/// names use the abstract namespace on Linux and named pipes on Windows,
+/// and fall back to a file in the temp directory elsewhere (e.g. macOS).
+pub fn socket_name(name: &str) -> io::Result<Name<'static>> {
+    if name.contains(std::path::MAIN_SEPARATOR) || name.contains('/') {
+        return name.to_string().to_fs_name::<GenericFilePath>();
+    }
+    if GenericNamespaced::is_supported() {
+        return name.to_ns_name::<GenericNamespaced>().map(Name::into_owned);
+    }
+    std::env::temp_dir()
+        .join(name)
+        .to_fs_name::<GenericFilePath>()
+}
@@ -0,0 +1,236 @@
+use crate::error::{IpcError, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use hmac::{Hmac, Mac};
+use identra_crypto::SecretBytes;
+use sha2::Sha256;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Environment variable holding the token (base64), overriding the keyring
+pub const TOKEN_ENV: &str = "IDENTRA_VAULT_TOKEN";
+
+/// Environment variable naming a token file, for daemons with a non-default state directory
+pub const TOKEN_FILE_ENV: &str = "IDENTRA_VAULT_TOKEN_FILE";
+
+/// Keychain service of a daemon not using the default; its token lives under it too
+pub const SERVICE_ENV: &str = "IDENTRA_VAULT_SERVICE";
+
+/// Keyring entry holding the per-install token
+pub const TOKEN_KEYRING_SERVICE: &str = "identra-vault";
+pub const TOKEN_KEYRING_USER: &str = "ipc-token";
+
+/// Token file in the daemon's state directory, for backends without a keyring
+pub const TOKEN_FILE_NAME: &str = "ipc-token";
+
+/// Where the daemon keeps the install token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenStore {
+    /// OS keyring entry under this service
+    Keyring(String),
+    /// Owner-only file, for headless machines without a keyring
+    File(PathBuf),
+}
+
+impl Default for TokenStore {
+    fn default() -> Self {
+        Self::Keyring(TOKEN_KEYRING_SERVICE.to_string())
+    }
+}
+
+/// Per-user data directory the daemon keeps its state in by default
+pub fn default_vault_dir() -> PathBuf {
+    #[cfg(windows)]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+    #[cfg(not(windows))]
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+
+    base.unwrap_or_else(std::env::temp_dir).join("identra").join("vault")
+}
+
+/// Token and challenge size in bytes
+pub const TOKEN_LEN: usize = 32;
+
+/// Domain separator for handshake proofs
+const PROOF_CONTEXT: &[u8] = b"identra-ipc-auth-v1";
+
+/// Load the install token for a client
+///
+/// Read from `IDENTRA_VAULT_TOKEN` if set, else from the file named by
+/// `IDENTRA_VAULT_TOKEN_FILE`, else from the token file in the default state
+/// directory if a file-backed daemon created one, else from the OS keyring
+/// under `IDENTRA_VAULT_SERVICE` (or the default service).
+pub fn load_token() -> Result<SecretBytes> {
+    if let Some(token) = token_from_env()? {
+        return Ok(token);
+    }
+    if let Some(path) = std::env::var_os(TOKEN_FILE_ENV).filter(|path| !path.is_empty()) {
+        return read_token_file(Path::new(&path));
+    }
+    let default_file = default_vault_dir().join(TOKEN_FILE_NAME);
+    if default_file.exists() {
+        return read_token_file(&default_file);
+    }
+    let service = std::env::var(SERVICE_ENV)
+        .ok()
+        .filter(|service| !service.is_empty())
+        .unwrap_or_else(|| TOKEN_KEYRING_SERVICE.to_string());
+    let encoded = Zeroizing::new(
+        keyring_entry(&service)?
+            .get_password()
+            .map_err(|e| IpcError::Token(format!("Failed to read IPC token: {}", e)))?,
+    );
+    decode_token(&encoded)
+}
+
+/// Load the install token from `store`, generating and saving it on first use (daemon side)
+pub fn load_or_create_token(store: &TokenStore) -> Result<SecretBytes> {
+    if let Some(token) = token_from_env()? {
+        return Ok(token);
+    }
+    match store {
+        TokenStore::Keyring(service) => load_or_create_keyring_token(service),
+        TokenStore::File(path) if path.exists() => read_token_file(path),
+        TokenStore::File(path) => {
+            let token = generate_token();
+            write_token_file(path, &token)?;
+            Ok(token)
+        }
+    }
+}
+
+/// Read a token file written by [`load_or_create_token`]
+pub fn read_token_file(path: &Path) -> Result<SecretBytes> {
+    let encoded = Zeroizing::new(
+        fs::read_to_string(path)
+            .map_err(|e| IpcError::Token(format!("Failed to read IPC token from {}: {}", path.display(), e)))?,
+    );
+    decode_token(&encoded)
+}
+
+/// Create `path` readable by its owner only; never replaces an existing file
+fn write_token_file(path: &Path, token: &SecretBytes) -> Result<()> {
+    let save_error = |e: std::io::Error| IpcError::Token(format!("Failed to save IPC token to {}: {}", path.display(), e));
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(save_error)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let encoded = Zeroizing::new(BASE64.encode(token.as_bytes()));
+    let mut file = options.open(path).map_err(save_error)?;
+    file.write_all(encoded.as_bytes()).and_then(|_| file.sync_all()).map_err(save_error)
+}
+
+fn load_or_create_keyring_token(service: &str) -> Result<SecretBytes> {
+    let entry = keyring_entry(service)?;
+    match entry.get_password() {
+        Ok(encoded) => decode_token(&Zeroizing::new(encoded)),
+        Err(keyring::Error::NoEntry) => {
+            let token = generate_token();
+            let encoded = Zeroizing::new(BASE64.encode(token.as_bytes()));
+            entry
+                .set_password(&encoded)
+                .map_err(|e| IpcError::Token(format!("Failed to save IPC token: {}", e)))?;
+            Ok(token)
+        }
+        Err(e) => Err(IpcError::Token(format!("Failed to read IPC token: {}", e))),
+    }
+}
+
+/// Fresh random token or challenge
+pub fn generate_token() -> SecretBytes {
+    let mut bytes = Zeroizing::new(vec![0u8; TOKEN_LEN]);
+    getrandom::getrandom(&mut bytes).expect("Failed to generate random token");
+    SecretBytes::from_slice(&bytes)
+}
+
+/// Proof that the client holds `token`: HMAC-SHA256 over the daemon's challenge
+/// and the client name, so the token itself never crosses the socket
+pub fn handshake_proof(token: &[u8], challenge: &[u8], client: &str) -> Vec<u8> {
+    proof_mac(token, challenge, client).finalize().into_bytes().to_vec()
+}
+
+/// Constant-time check of a client's proof
+pub fn verify_proof(token: &[u8], challenge: &[u8], client: &str, proof: &[u8]) -> bool {
+    proof_mac(token, challenge, client).verify_slice(proof).is_ok()
+}
+
+fn proof_mac(token: &[u8], challenge: &[u8], client: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(token).expect("HMAC accepts any key length");
+    mac.update(PROOF_CONTEXT);
+    mac.update(challenge);
+    mac.update(client.as_bytes());
+    mac
+}
+
+fn token_from_env() -> Result<Option<SecretBytes>> {
+    match std::env::var(TOKEN_ENV) {
+        Ok(encoded) if !encoded.is_empty() => decode_token(&Zeroizing::new(encoded)).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn keyring_entry(service: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(service, TOKEN_KEYRING_USER)
+        .map_err(|e| IpcError::Token(format!("Failed to open keyring: {}", e)))
+}
+
+fn decode_token(encoded: &str) -> Result<SecretBytes> {
+    let token = BASE64
+        .decode(encoded.trim())
+        .map(SecretBytes::new)
+        .map_err(|e| IpcError::Token(format!("Invalid IPC token encoding: {}", e)))?;
+    if token.len() != TOKEN_LEN {
+        return Err(IpcError::Token(format!("IPC token must be {} bytes", TOKEN_LEN)));
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proof_binds_token_challenge_and_client() {
+        let token = generate_token();
+        let challenge = generate_token();
+        let proof = handshake_proof(token.as_bytes(), challenge.as_bytes(), "gateway");
+
+        assert!(verify_proof(token.as_bytes(), challenge.as_bytes(), "gateway", &proof));
+        assert!(!verify_proof(generate_token().as_bytes(), challenge.as_bytes(), "gateway", &proof));
+        assert!(!verify_proof(token.as_bytes(), generate_token().as_bytes(), "gateway", &proof));
+        assert!(!verify_proof(token.as_bytes(), challenge.as_bytes(), "desktop", &proof));
+        assert!(!verify_proof(token.as_bytes(), challenge.as_bytes(), "gateway", &proof[..16]));
+    }
+
+    #[test]
+    fn test_decode_token_checks_length() {
+        assert!(decode_token(&BASE64.encode([1u8; TOKEN_LEN])).is_ok());
+        assert!(decode_token(&BASE64.encode([1u8; 16])).is_err());
+        assert!(decode_token("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_token_file_is_created_once_and_owner_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::File(dir.path().join("state").join(TOKEN_FILE_NAME));
+        let TokenStore::File(path) = &store else { unreachable!() };
+
+        let token = load_or_create_token(&store).unwrap();
+        assert_eq!(load_or_create_token(&store).unwrap().as_bytes(), token.as_bytes());
+        assert_eq!(read_token_file(path).unwrap().as_bytes(), token.as_bytes());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Client <-> server round trips over a real local socket
+
+use identra_crypto::SecretBytes;
+use identra_ipc::{
+    accept_handshake, read_frame, socket_name, write_frame, ClientHello, ErrorCode, IpcError,
+    KeyPolicy, StoredKey, VaultClient, VaultRequest, VaultResponse, PROTOCOL_VERSION,
+};
+use interprocess::local_socket::{tokio::prelude::*, tokio::Stream, ListenerOptions};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const TOKEN: &[u8] = &[5u8; 32];
+
+fn unique_socket() -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "identra-ipc-test-{}-{}.sock",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Minimal daemon answering from a fixed key table
+async fn serve_one(socket: String) -> tokio::task::JoinHandle<()> {
+    let listener = ListenerOptions::new()
+        .name(socket_name(&socket).unwrap())
+        .create_tokio()
+        .unwrap();
+
+    tokio::spawn(async move {
+        let mut stream = listener.accept().await.unwrap();
+        if accept_handshake(&mut stream, "test-daemon", TOKEN).await.is_err() {
+            return;
+        }
+        loop {
+            let request = match read_frame::<_, VaultRequest>(&mut stream).await {
+                Ok(Some(request)) => request,
+                Ok(None) => break,
+                Err(IpcError::Decode(e)) => {
+                    let reply = VaultResponse::error(ErrorCode::InvalidRequest, e.to_string());
+                    write_frame(&mut stream, &reply).await.unwrap();
+                    continue;
+                }
+                Err(e) => panic!("read failed: {}", e),
+            };
+            let response = match request {
+                VaultRequest::Ping => VaultResponse::Pong,
+                VaultRequest::KeyExists { key_id } => VaultResponse::Exists(key_id == "known"),
+                VaultRequest::RetrieveKey { key_id } if key_id == "known" => {
+                    VaultResponse::KeyData(StoredKey {
+                        key_data: SecretBytes::from_slice(b"secret"),
+                        metadata: HashMap::from([("purpose".into(), "test".into())]),
+                        created_at: 1,
+                        expires_at: None,
+                        policy: KeyPolicy::default(),
+                        uses: 0,
+                    })
+                }
+                VaultRequest::RetrieveKey { .. } => {
+                    VaultResponse::error(ErrorCode::NotFound, "Key not found")
+                }
+                _ => VaultResponse::error(ErrorCode::Internal, "not implemented in test"),
+            };
+            write_frame(&mut stream, &response).await.unwrap();
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_client_roundtrip_over_socket() {
+    let socket = unique_socket();
+    let server = serve_one(socket.clone()).await;
+
+    let mut client = VaultClient::connect_to(&socket, "test-client", TOKEN).await.unwrap();
+    assert_eq!(client.server().server, "test-daemon");
+    assert_eq!(client.server().protocol_version, PROTOCOL_VERSION);
+
+    client.ping().await.unwrap();
+    assert!(client.key_exists("known").await.unwrap());
+    assert!(!client.key_exists("other").await.unwrap());
+
+    let key = client.retrieve_key("known").await.unwrap();
+    assert_eq!(key.key_data.as_bytes(), b"secret");
+    assert_eq!(key.metadata["purpose"], "test");
+
+    let err = client.retrieve_key("missing").await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::NotFound));
+
+    drop(client);
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_malformed_frame_gets_error_and_connection_survives() {
+    use tokio::io::AsyncWriteExt;
+
+    let socket = unique_socket();
+    let server = serve_one(socket.clone()).await;
+
+    let mut stream = Stream::connect(socket_name(&socket).unwrap()).await.unwrap();
+    identra_ipc::client_handshake(&mut stream, "raw-client", TOKEN).await.unwrap();
+
+    stream.write_all(&4u32.to_be_bytes()).await.unwrap();
+    stream.write_all(b"junk").await.unwrap();
+    let reply: VaultResponse = read_frame(&mut stream).await.unwrap().unwrap();
+    assert!(matches!(reply, VaultResponse::Error { code: ErrorCode::InvalidRequest, .. }));
+
+    write_frame(&mut stream, &VaultRequest::Ping).await.unwrap();
+    let reply: VaultResponse = read_frame(&mut stream).await.unwrap().unwrap();
+    assert!(matches!(reply, VaultResponse::Pong));
+
+    drop(stream);
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_version_mismatch_is_rejected_over_socket() {
+    let socket = unique_socket();
+    let server = serve_one(socket.clone()).await;
+
+    let mut stream = Stream::connect(socket_name(&socket).unwrap()).await.unwrap();
+    let hello = ClientHello {
+        protocol_version: PROTOCOL_VERSION + 1,
+        client: "future-client".into(),
+    };
+    write_frame(&mut stream, &hello).await.unwrap();
+    let reply: VaultResponse = read_frame(&mut stream).await.unwrap().unwrap();
+
+    assert!(matches!(reply, VaultResponse::Error { code: ErrorCode::UnsupportedVersion, .. }));
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_wrong_token_is_rejected_over_socket() {
+    let socket = unique_socket();
+    let server = serve_one(socket.clone()).await;
+
+    let err = VaultClient::connect_to(&socket, "test-client", &[6u8; 32])
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Some(ErrorCode::Unauthorized));
+    server.await.unwrap();
+}
@@ -9,6 +9,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "proto/memory.proto",
                 "proto/health.proto",
                 "proto/auth.proto",
+                "proto/identity.proto",
             ],
             &["proto"],
         )?;
@@ -15,3 +15,6 @@ pub mod auth {
     tonic::include_proto!("identra.auth");
 }
 
+pub mod identity {
+    tonic::include_proto!("identra.identity.v1");
+}